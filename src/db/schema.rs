@@ -27,6 +27,13 @@ pub fn create_tables(conn: &Connection) -> Result<()> {
             metadata TEXT
         );
 
+        CREATE INDEX IF NOT EXISTS idx_turns_type ON turns(turn_type);
+
+        -- Failed commands: each error turn links to its bash_cmd turn
+        CREATE INDEX IF NOT EXISTS idx_turns_error_command
+            ON turns(json_extract(metadata, '$.command_turn_id'))
+            WHERE turn_type = 'error';
+
         -- FTS5 for turns
         CREATE VIRTUAL TABLE IF NOT EXISTS turns_fts USING fts5(
             content, content_summary, tokenize='porter unicode61'
@@ -86,6 +93,24 @@ pub fn create_tables(conn: &Connection) -> Result<()> {
             PRIMARY KEY (from_symbol_id, to_symbol_id, ref_type)
         );
 
+        -- Call sites (callee name per line), for callers/callees lookups
+        CREATE TABLE IF NOT EXISTS symbol_calls (
+            file_path TEXT NOT NULL,
+            line INTEGER NOT NULL,
+            callee TEXT NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS idx_symbol_calls_callee ON symbol_calls(callee);
+        CREATE INDEX IF NOT EXISTS idx_symbol_calls_file ON symbol_calls(file_path);
+
+        -- Line ranges touched by code_edit turns (post-edit line numbers)
+        CREATE TABLE IF NOT EXISTS edit_regions (
+            turn_id INTEGER NOT NULL REFERENCES turns(id),
+            file_path TEXT NOT NULL,
+            start_line INTEGER NOT NULL,
+            end_line INTEGER NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS idx_edit_regions_file ON edit_regions(file_path);
+
         -- Git state tracking (for session-start catch-up)
         CREATE TABLE IF NOT EXISTS git_state (
             project_dir TEXT PRIMARY KEY,
@@ -148,6 +173,13 @@ pub fn create_tables(conn: &Connection) -> Result<()> {
         );
         CREATE INDEX IF NOT EXISTS idx_bg_tasks_status ON background_tasks(status);
 
+        -- Running stdio MCP servers (one per Claude window), with heartbeats
+        CREATE TABLE IF NOT EXISTS mcp_servers (
+            pid INTEGER PRIMARY KEY,
+            started_at TEXT DEFAULT (datetime('now')),
+            last_seen TEXT DEFAULT (datetime('now'))
+        );
+
         -- Plans (tracked implementation plans for crash recovery)
         CREATE TABLE IF NOT EXISTS plans (
             id INTEGER PRIMARY KEY AUTOINCREMENT,
@@ -174,6 +206,21 @@ pub fn create_tables(conn: &Connection) -> Result<()> {
             PRIMARY KEY (plan_id, file_path)
         );
 
+        -- TodoWrite lists, one row per item per version of a session's list
+        -- (a cleared list is a single row at position -1)
+        CREATE TABLE IF NOT EXISTS todos (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            session_id TEXT NOT NULL REFERENCES sessions(id),
+            version INTEGER NOT NULL,
+            position INTEGER NOT NULL,
+            content TEXT NOT NULL,
+            status TEXT NOT NULL,
+            active_form TEXT,
+            previous_status TEXT,
+            created_at TEXT DEFAULT (datetime('now'))
+        );
+        CREATE INDEX IF NOT EXISTS idx_todos_session ON todos(session_id, version);
+
         -- Hook invocation log (always-on, append-only)
         CREATE TABLE IF NOT EXISTS hook_log (
             id INTEGER PRIMARY KEY,
@@ -188,6 +235,13 @@ pub fn create_tables(conn: &Connection) -> Result<()> {
 
     // Forward migrations for existing databases
     migrate_add_column(conn, "symbols", "parent_name", "TEXT");
+    migrate_add_column(conn, "sessions", "git_branch", "TEXT");
+    migrate_add_column(conn, "sessions", "transcript_offset", "INTEGER DEFAULT 0");
+    migrate_add_column(conn, "turns", "reconciled", "INTEGER DEFAULT 0");
+    migrate_add_column(conn, "sessions", "parent_session_id", "TEXT REFERENCES sessions(id)");
+    migrate_add_column(conn, "sessions", "start_source", "TEXT");
+    migrate_add_column(conn, "sessions", "agent_type", "TEXT");
+    migrate_add_column(conn, "sessions", "agent_id", "TEXT");
 
     Ok(())
 }
@@ -1,6 +1,7 @@
 use anyhow::Result;
-use rusqlite::Connection;
+use rusqlite::{Connection, OptionalExtension};
 use serde::Serialize;
+use std::time::Instant;
 
 /// Sanitize a user query for SQLite FTS5.
 ///
@@ -19,8 +20,17 @@ fn sanitize_fts_query(query: &str) -> String {
         .join(" ")
 }
 
+/// Build an FTS5 query matching any of `terms` (each quoted as a literal).
+fn any_terms_query(terms: &[String]) -> String {
+    terms
+        .iter()
+        .map(|t| format!("\"{}\"", t.replace('"', "")))
+        .collect::<Vec<_>>()
+        .join(" OR ")
+}
+
 /// A search result from the turns FTS index.
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Serialize)]
 pub struct TurnSearchResult {
     pub turn_id: i64,
     pub session_id: String,
@@ -139,6 +149,93 @@ pub fn search_knowledge(
     query: &str,
     limit: usize,
     category: Option<&str>,
+) -> Result<Vec<KnowledgeSearchResult>> {
+    knowledge_matching(conn, &sanitize_fts_query(query), limit, category)
+}
+
+/// Search knowledge entries matching any of `terms`, best BM25 match first.
+pub fn search_knowledge_any(
+    conn: &Connection,
+    terms: &[String],
+    limit: usize,
+) -> Result<Vec<KnowledgeSearchResult>> {
+    if terms.is_empty() {
+        return Ok(Vec::new());
+    }
+    knowledge_matching(conn, &any_terms_query(terms), limit, None)
+}
+
+/// Find current knowledge in `categories` that mentions `term` verbatim
+/// (case-insensitive), e.g. a file name or a symbol name.
+pub fn knowledge_mentioning(
+    conn: &Connection,
+    term: &str,
+    categories: &[&str],
+    limit: usize,
+) -> Result<Vec<KnowledgeSearchResult>> {
+    // FTS narrows the candidates; the stemmed match is then checked literally
+    let needle = term.to_lowercase();
+    Ok(knowledge_matching(conn, &sanitize_fts_query(term), limit * 4, None)?
+        .into_iter()
+        .filter(|k| categories.contains(&k.category.as_str()))
+        .filter(|k| {
+            k.subject.to_lowercase().contains(&needle) || k.content.to_lowercase().contains(&needle)
+        })
+        .take(limit)
+        .collect())
+}
+
+/// Current knowledge in `categories`, most confident (then newest) first.
+pub fn top_knowledge(
+    conn: &Connection,
+    categories: &[&str],
+    limit: usize,
+) -> Result<Vec<KnowledgeSearchResult>> {
+    let placeholders = (2..categories.len() + 2)
+        .map(|i| format!("?{}", i))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let sql = format!(
+        "SELECT id, category, subject, content, confidence, created_at, 0.0
+         FROM knowledge
+         WHERE superseded_by IS NULL AND category IN ({placeholders})
+         ORDER BY confidence DESC, created_at DESC
+         LIMIT ?1"
+    );
+    let mut stmt = conn.prepare(&sql)?;
+
+    let mut params: Vec<Box<dyn rusqlite::types::ToSql>> = vec![Box::new(limit as i64)];
+    for category in categories {
+        params.push(Box::new(category.to_string()));
+    }
+    let param_refs: Vec<&dyn rusqlite::types::ToSql> =
+        params.iter().map(|p| p.as_ref()).collect();
+
+    let rows = stmt.query_map(param_refs.as_slice(), |row| {
+        Ok(KnowledgeSearchResult {
+            id: row.get(0)?,
+            category: row.get(1)?,
+            subject: row.get(2)?,
+            content: row.get(3)?,
+            confidence: row.get(4)?,
+            created_at: row.get(5)?,
+            rank: row.get(6)?,
+        })
+    })?;
+
+    let mut results = Vec::new();
+    for row in rows {
+        results.push(row?);
+    }
+    Ok(results)
+}
+
+/// Run a knowledge FTS query with an already-built MATCH expression.
+fn knowledge_matching(
+    conn: &Connection,
+    match_expr: &str,
+    limit: usize,
+    category: Option<&str>,
 ) -> Result<Vec<KnowledgeSearchResult>> {
     let sql = if category.is_some() {
         "SELECT k.id, k.category, k.subject, k.content, k.confidence, k.created_at,
@@ -164,7 +261,7 @@ pub fn search_knowledge(
     let mut stmt = conn.prepare(sql)?;
 
     let mut params: Vec<Box<dyn rusqlite::types::ToSql>> = Vec::new();
-    params.push(Box::new(sanitize_fts_query(query)));
+    params.push(Box::new(match_expr.to_string()));
     if let Some(cat) = category {
         params.push(Box::new(cat.to_string()));
     }
@@ -192,11 +289,140 @@ pub fn search_knowledge(
     Ok(results)
 }
 
+/// Find user requests from other sessions matching any of `terms`, best
+/// BM25 match first.
+pub fn past_requests(
+    conn: &Connection,
+    terms: &[String],
+    exclude_session: &str,
+    limit: usize,
+) -> Result<Vec<TurnSearchResult>> {
+    if terms.is_empty() {
+        return Ok(Vec::new());
+    }
+    let mut stmt = conn.prepare(
+        "SELECT t.id, t.session_id, t.turn_number, t.timestamp,
+                t.role, t.turn_type, t.content, t.content_summary, fts.rank
+         FROM turns_fts fts
+         JOIN turns t ON t.id = fts.rowid
+         WHERE turns_fts MATCH ?1
+           AND t.turn_type = 'request'
+           AND t.session_id != ?2
+         ORDER BY fts.rank
+         LIMIT ?3",
+    )?;
+
+    let rows = stmt.query_map(
+        rusqlite::params![any_terms_query(terms), exclude_session, limit as i64],
+        |row| {
+            Ok(TurnSearchResult {
+                turn_id: row.get(0)?,
+                session_id: row.get(1)?,
+                turn_number: row.get(2)?,
+                timestamp: row.get(3)?,
+                role: row.get(4)?,
+                turn_type: row.get(5)?,
+                content: row.get(6)?,
+                content_summary: row.get(7)?,
+                rank: row.get(8)?,
+                files: Vec::new(),
+            })
+        },
+    )?;
+
+    let mut results = Vec::new();
+    for row in rows {
+        results.push(row?);
+    }
+    Ok(results)
+}
+
+/// Files edited in response to a request: edits after `turn_number` in the
+/// same session, up to the next request.
+pub fn request_followup_files(
+    conn: &Connection,
+    session_id: &str,
+    turn_number: i64,
+    limit: usize,
+) -> Result<Vec<String>> {
+    let mut stmt = conn.prepare(
+        "SELECT tf.file_path, MIN(t.turn_number) AS first_edit
+         FROM turns t
+         JOIN turn_files tf ON tf.turn_id = t.id
+         WHERE t.session_id = ?1
+           AND t.turn_number > ?2
+           AND t.turn_number < COALESCE(
+               (SELECT MIN(turn_number) FROM turns
+                WHERE session_id = ?1 AND turn_number > ?2 AND turn_type = 'request'),
+               ?2 + 1000)
+           AND t.turn_type IN ('code_edit', 'notebook_edit')
+         GROUP BY tf.file_path
+         ORDER BY first_edit
+         LIMIT ?3",
+    )?;
+    let rows = stmt.query_map(
+        rusqlite::params![session_id, turn_number, limit as i64],
+        |row| row.get(0),
+    )?;
+    let mut files = Vec::new();
+    for row in rows {
+        files.push(row?);
+    }
+    Ok(files)
+}
+
+/// Escape `%`, `_` and `\` for a LIKE pattern with `ESCAPE '\'`.
+fn escape_like(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")
+}
+
+/// Indexed file paths that end with `name` (a path or file name mentioned
+/// in a prompt).
+pub fn files_matching(conn: &Connection, name: &str, limit: usize) -> Result<Vec<String>> {
+    let name = name.replace('\\', "/");
+    let name = name.trim_start_matches("./");
+    let escaped = escape_like(name);
+    let mut stmt = conn.prepare(
+        "SELECT DISTINCT file_path FROM turn_files
+         WHERE file_path = ?1 OR file_path LIKE ?2 ESCAPE '\\'
+         LIMIT ?3",
+    )?;
+    let rows = stmt.query_map(
+        rusqlite::params![name, format!("%/{}", escaped), limit as i64],
+        |row| row.get(0),
+    )?;
+    let mut files = Vec::new();
+    for row in rows {
+        files.push(row?);
+    }
+    Ok(files)
+}
+
+/// Files read in a session, most recently read first.
+pub fn session_files_read(conn: &Connection, session_id: &str, limit: usize) -> Result<Vec<String>> {
+    let mut stmt = conn.prepare(
+        "SELECT tf.file_path, MAX(t.id) AS last_read
+         FROM turns t
+         JOIN turn_files tf ON tf.turn_id = t.id
+         WHERE t.session_id = ?1 AND t.turn_type = 'file_read'
+         GROUP BY tf.file_path
+         ORDER BY last_read DESC
+         LIMIT ?2",
+    )?;
+    let rows = stmt.query_map(rusqlite::params![session_id, limit as i64], |row| row.get(0))?;
+    let mut files = Vec::new();
+    for row in rows {
+        files.push(row?);
+    }
+    Ok(files)
+}
+
 /// Get recent session summaries.
 pub fn recent_sessions(conn: &Connection, limit: usize) -> Result<Vec<SessionSummary>> {
     let mut stmt = conn.prepare(
-        "SELECT id, project_dir, started_at, ended_at, summary
+        "SELECT id, project_dir, started_at, ended_at, summary, git_branch
          FROM sessions
+         WHERE COALESCE(start_source, '') != 'subagent'
          ORDER BY started_at DESC
          LIMIT ?1",
     )?;
@@ -208,6 +434,7 @@ pub fn recent_sessions(conn: &Connection, limit: usize) -> Result<Vec<SessionSum
             started_at: row.get(2)?,
             ended_at: row.get(3)?,
             summary: row.get(4)?,
+            git_branch: row.get(5)?,
         })
     })?;
 
@@ -225,6 +452,195 @@ pub struct SessionSummary {
     pub started_at: String,
     pub ended_at: Option<String>,
     pub summary: Option<String>,
+    pub git_branch: Option<String>,
+}
+
+/// Filters for `list_sessions`. All fields are optional and AND'd together.
+#[derive(Debug, Default)]
+pub struct SessionFilter<'a> {
+    /// Sessions started on or after this date (`YYYY-MM-DD`, inclusive)
+    pub since: Option<&'a str>,
+    /// Sessions started on or before this date (`YYYY-MM-DD`, inclusive)
+    pub until: Option<&'a str>,
+    /// Git branch the session started on
+    pub branch: Option<&'a str>,
+    /// Substring of the session summary or any of its user requests
+    pub keyword: Option<&'a str>,
+}
+
+/// A session row with activity counts, returned by `list_sessions`.
+#[derive(Debug, Serialize)]
+pub struct SessionListEntry {
+    pub session: SessionSummary,
+    pub request_count: i64,
+    pub edit_count: i64,
+    pub first_request: Option<String>,
+}
+
+/// List sessions matching a filter, newest first.
+pub fn list_sessions(
+    conn: &Connection,
+    filter: &SessionFilter,
+    limit: usize,
+) -> Result<Vec<SessionListEntry>> {
+    // Subagent runs are listed under their parent's summary turns
+    let mut conditions = vec!["COALESCE(s.start_source, '') != 'subagent'".to_string()];
+    let mut params: Vec<Box<dyn rusqlite::types::ToSql>> = Vec::new();
+
+    if let Some(since) = filter.since {
+        params.push(Box::new(since.to_string()));
+        conditions.push(format!("date(s.started_at) >= date(?{})", params.len()));
+    }
+    if let Some(until) = filter.until {
+        params.push(Box::new(until.to_string()));
+        conditions.push(format!("date(s.started_at) <= date(?{})", params.len()));
+    }
+    if let Some(branch) = filter.branch {
+        params.push(Box::new(branch.to_string()));
+        conditions.push(format!("s.git_branch = ?{}", params.len()));
+    }
+    if let Some(keyword) = filter.keyword {
+        params.push(Box::new(format!("%{}%", keyword)));
+        let n = params.len();
+        conditions.push(format!(
+            "(COALESCE(s.summary, '') LIKE ?{n} OR EXISTS (
+                 SELECT 1 FROM turns k
+                 WHERE k.session_id = s.id AND k.turn_type = 'request' AND k.content LIKE ?{n}))"
+        ));
+    }
+
+    let where_clause = format!("WHERE {}", conditions.join(" AND "));
+
+    params.push(Box::new(limit as i64));
+    let sql = format!(
+        "SELECT s.id, s.project_dir, s.started_at, s.ended_at, s.summary, s.git_branch,
+                (SELECT COUNT(*) FROM turns t WHERE t.session_id = s.id AND t.turn_type = 'request'),
+                (SELECT COUNT(*) FROM turns t WHERE t.session_id = s.id AND t.turn_type = 'code_edit'),
+                (SELECT t.content FROM turns t WHERE t.session_id = s.id AND t.turn_type = 'request'
+                 ORDER BY t.turn_number ASC LIMIT 1)
+         FROM sessions s
+         {}
+         ORDER BY s.started_at DESC
+         LIMIT ?{}",
+        where_clause,
+        params.len(),
+    );
+
+    let param_refs: Vec<&dyn rusqlite::types::ToSql> =
+        params.iter().map(|p| p.as_ref()).collect();
+
+    let mut stmt = conn.prepare(&sql)?;
+    let rows = stmt.query_map(param_refs.as_slice(), |row| {
+        Ok(SessionListEntry {
+            session: SessionSummary {
+                id: row.get(0)?,
+                project_dir: row.get(1)?,
+                started_at: row.get(2)?,
+                ended_at: row.get(3)?,
+                summary: row.get(4)?,
+                git_branch: row.get(5)?,
+            },
+            request_count: row.get(6)?,
+            edit_count: row.get(7)?,
+            first_request: row.get(8)?,
+        })
+    })?;
+
+    let mut results = Vec::new();
+    for row in rows {
+        results.push(row?);
+    }
+    Ok(results)
+}
+
+/// Resolve a full or abbreviated session ID to a stored session.
+/// Prefixes match the most recently started session.
+pub fn find_session(conn: &Connection, id_or_prefix: &str) -> Result<Option<SessionSummary>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, project_dir, started_at, ended_at, summary, git_branch
+         FROM sessions
+         WHERE id = ?1 OR id LIKE ?1 || '%'
+         ORDER BY id = ?1 DESC, started_at DESC
+         LIMIT 1",
+    )?;
+
+    let mut rows = stmt.query([id_or_prefix])?;
+    match rows.next()? {
+        Some(row) => Ok(Some(SessionSummary {
+            id: row.get(0)?,
+            project_dir: row.get(1)?,
+            started_at: row.get(2)?,
+            ended_at: row.get(3)?,
+            summary: row.get(4)?,
+            git_branch: row.get(5)?,
+        })),
+        None => Ok(None),
+    }
+}
+
+/// One step of a session timeline: a user request and the activity it caused.
+#[derive(Debug, Serialize)]
+pub struct TimelineEntry {
+    /// The request turn, or `None` for activity before the first request
+    /// (e.g. git catch-up at session start)
+    pub request: Option<TurnSearchResult>,
+    /// Follow-up turns (edits, reads, commands, ...) until the next request
+    pub activity: Vec<TurnSearchResult>,
+}
+
+/// Group a session's turns into requests and their follow-up activity.
+pub fn session_timeline(conn: &Connection, session_id: &str) -> Result<Vec<TimelineEntry>> {
+    let turns = session_turns(conn, session_id)?;
+    Ok(group_by_request(turns))
+}
+
+/// Split a chronologically ordered turn list at each `request` turn.
+fn group_by_request(turns: Vec<TurnSearchResult>) -> Vec<TimelineEntry> {
+    let mut timeline: Vec<TimelineEntry> = Vec::new();
+    for turn in turns {
+        if turn.turn_type == "request" {
+            timeline.push(TimelineEntry {
+                request: Some(turn),
+                activity: Vec::new(),
+            });
+        } else {
+            match timeline.last_mut() {
+                Some(entry) => entry.activity.push(turn),
+                None => timeline.push(TimelineEntry {
+                    request: None,
+                    activity: vec![turn],
+                }),
+            }
+        }
+    }
+    timeline
+}
+
+/// Subagent run sessions started from `parent_session_id`, oldest first.
+pub fn child_sessions(conn: &Connection, parent_session_id: &str) -> Result<Vec<String>> {
+    let mut stmt = conn.prepare(
+        "SELECT id FROM sessions
+         WHERE parent_session_id = ?1 AND start_source = 'subagent'
+         ORDER BY started_at ASC, id ASC",
+    )?;
+    let ids = stmt
+        .query_map([parent_session_id], |row| row.get(0))?
+        .collect::<rusqlite::Result<_>>()?;
+    Ok(ids)
+}
+
+/// A session's turns interleaved with those of its subagent runs, in the
+/// order they were indexed.
+pub fn session_turns_with_subagents(
+    conn: &Connection,
+    session_id: &str,
+) -> Result<Vec<TurnSearchResult>> {
+    let mut turns = session_turns(conn, session_id)?;
+    for child in child_sessions(conn, session_id)? {
+        turns.extend(session_turns(conn, &child)?);
+    }
+    turns.sort_by_key(|t| t.turn_id);
+    Ok(turns)
 }
 
 /// Retrieve all turns from a session, ordered by turn number.
@@ -338,7 +754,7 @@ pub fn codebase_map(conn: &Connection) -> Result<Vec<FileMapEntry>> {
     }
 
     // Sort by score descending (most important files first)
-    files.sort_by(|a, b| b.score.cmp(&a.score));
+    files.sort_by_key(|f| std::cmp::Reverse(f.score));
 
     Ok(files)
 }
@@ -443,7 +859,7 @@ pub fn project_structure(conn: &Connection) -> Result<ProjectStructure> {
     }
 
     let mut directories: Vec<(String, usize)> = dir_counts.into_iter().collect();
-    directories.sort_by(|a, b| b.1.cmp(&a.1));
+    directories.sort_by_key(|d| std::cmp::Reverse(d.1));
 
     Ok(ProjectStructure {
         total_files,
@@ -559,18 +975,662 @@ pub fn search_symbols_by_keywords(
         params.iter().map(|p| p.as_ref()).collect();
 
     let mut stmt = conn.prepare(&sql)?;
-    let rows = stmt.query_map(param_refs.as_slice(), |row| {
-        Ok(SymbolMatch {
-            file_path: row.get(0)?,
-            name: row.get(1)?,
-            kind: row.get(2)?,
-            start_line: row.get(3)?,
-            end_line: row.get(4)?,
-            signature: row.get(5)?,
-            parent_name: row.get(6)?,
-            doc_comment: row.get(7)?,
+    let rows = stmt.query_map(param_refs.as_slice(), symbol_from_row)?;
+
+    let mut results = Vec::new();
+    for row in rows {
+        results.push(row?);
+    }
+    Ok(results)
+}
+
+/// Columns expected by `symbol_from_row`, in order.
+const SYMBOL_COLUMNS: &str =
+    "file_path, name, kind, start_line, end_line, signature, parent_name, doc_comment";
+
+fn symbol_from_row(row: &rusqlite::Row) -> rusqlite::Result<SymbolMatch> {
+    Ok(SymbolMatch {
+        file_path: row.get(0)?,
+        name: row.get(1)?,
+        kind: row.get(2)?,
+        start_line: row.get(3)?,
+        end_line: row.get(4)?,
+        signature: row.get(5)?,
+        parent_name: row.get(6)?,
+        doc_comment: row.get(7)?,
+    })
+}
+
+/// Look up symbols by exact name, optionally qualified with a parent
+/// (`Parent::name` or `Parent.name`) and restricted to files whose path
+/// contains `file_filter`.
+pub fn find_symbols(
+    conn: &Connection,
+    qualified_name: &str,
+    file_filter: Option<&str>,
+    limit: usize,
+) -> Result<Vec<SymbolMatch>> {
+    let normalized = qualified_name.replace("::", ".");
+    let (parent, name) = match normalized.rsplit_once('.') {
+        Some((p, n)) => (Some(p.rsplit('.').next().unwrap_or(p)), n),
+        None => (None, normalized.as_str()),
+    };
+
+    let sql = format!(
+        "SELECT {SYMBOL_COLUMNS} FROM symbols
+         WHERE name = ?1
+           AND (?2 IS NULL OR parent_name = ?2)
+           AND (?3 IS NULL OR file_path LIKE '%' || ?3 || '%' ESCAPE '\\')
+           AND kind NOT IN ('import', 'variable')
+         ORDER BY file_path, start_line
+         LIMIT ?4"
+    );
+    let mut stmt = conn.prepare(&sql)?;
+    let rows = stmt.query_map(
+        rusqlite::params![name, parent, file_filter.map(escape_like), limit as i64],
+        symbol_from_row,
+    )?;
+
+    let mut results = Vec::new();
+    for row in rows {
+        results.push(row?);
+    }
+    Ok(results)
+}
+
+/// Find the innermost symbol whose range contains `line` in `file_path`.
+pub fn enclosing_symbol(
+    conn: &Connection,
+    file_path: &str,
+    line: i64,
+) -> Result<Option<SymbolMatch>> {
+    let sql = format!(
+        "SELECT {SYMBOL_COLUMNS} FROM symbols
+         WHERE file_path = ?1 AND start_line <= ?2 AND end_line >= ?2
+           AND kind NOT IN ('import', 'variable')
+         ORDER BY end_line - start_line ASC
+         LIMIT 1"
+    );
+    let mut stmt = conn.prepare(&sql)?;
+    let mut rows = stmt.query_map(rusqlite::params![file_path, line], symbol_from_row)?;
+    Ok(rows.next().transpose()?)
+}
+
+/// A call site of some symbol, with the symbol it occurs in (if indexed).
+#[derive(Debug)]
+pub struct CallSite {
+    pub file_path: String,
+    pub line: i64,
+    pub caller: Option<SymbolMatch>,
+}
+
+/// Find call sites of `name` across the project.
+pub fn symbol_callers(conn: &Connection, name: &str, limit: usize) -> Result<Vec<CallSite>> {
+    let mut stmt = conn.prepare(
+        "SELECT file_path, line FROM symbol_calls
+         WHERE callee = ?1
+         ORDER BY file_path, line
+         LIMIT ?2",
+    )?;
+    let sites: Vec<(String, i64)> = stmt
+        .query_map(rusqlite::params![name, limit as i64], |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })?
+        .collect::<rusqlite::Result<_>>()?;
+
+    let mut results = Vec::new();
+    for (file_path, line) in sites {
+        let caller = enclosing_symbol(conn, &file_path, line)?;
+        results.push(CallSite {
+            file_path,
+            line,
+            caller,
+        });
+    }
+    Ok(results)
+}
+
+/// Find indexed symbols called from lines `start..=end` of `file_path`.
+/// Calls that don't resolve to a known symbol (std/library calls) are dropped.
+pub fn symbol_callees(
+    conn: &Connection,
+    file_path: &str,
+    start: i64,
+    end: i64,
+    limit: usize,
+) -> Result<Vec<SymbolMatch>> {
+    let sql = format!(
+        "SELECT {SYMBOL_COLUMNS} FROM symbols
+         WHERE name IN (
+             SELECT DISTINCT callee FROM symbol_calls
+             WHERE file_path = ?1 AND line BETWEEN ?2 AND ?3
+         )
+           AND kind NOT IN ('import', 'variable', 'impl')
+           AND NOT (file_path = ?1 AND start_line = ?2)
+         ORDER BY (file_path = ?1) DESC, name, file_path
+         LIMIT ?4"
+    );
+    let mut stmt = conn.prepare(&sql)?;
+    let rows = stmt.query_map(
+        rusqlite::params![file_path, start, end, limit as i64],
+        symbol_from_row,
+    )?;
+
+    let mut results = Vec::new();
+    for row in rows {
+        results.push(row?);
+    }
+    Ok(results)
+}
+
+/// Find the user request that led to a turn: the latest `request` turn in
+/// the same session before `turn_number`.
+pub fn preceding_request(
+    conn: &Connection,
+    session_id: &str,
+    turn_number: i64,
+) -> Result<Option<TurnSearchResult>> {
+    let mut stmt = conn.prepare(
+        "SELECT t.id, t.session_id, t.turn_number, t.timestamp,
+                t.role, t.turn_type, t.content, t.content_summary, 0.0 as rank
+         FROM turns t
+         WHERE t.session_id = ?1 AND t.turn_number < ?2 AND t.turn_type = 'request'
+         ORDER BY t.turn_number DESC
+         LIMIT 1",
+    )?;
+
+    let mut rows = stmt.query_map(rusqlite::params![session_id, turn_number], |row| {
+        Ok(TurnSearchResult {
+            turn_id: row.get(0)?,
+            session_id: row.get(1)?,
+            turn_number: row.get(2)?,
+            timestamp: row.get(3)?,
+            role: row.get(4)?,
+            turn_type: row.get(5)?,
+            content: row.get(6)?,
+            content_summary: row.get(7)?,
+            rank: row.get(8)?,
+            files: Vec::new(),
         })
     })?;
+    Ok(rows.next().transpose()?)
+}
+
+/// Get `code_edit` turns whose recorded edit regions overlap lines
+/// `start..=end` of `file_path`, oldest first.
+pub fn region_edits(
+    conn: &Connection,
+    file_path: &str,
+    start: i64,
+    end: i64,
+    limit: usize,
+) -> Result<Vec<TurnSearchResult>> {
+    let mut stmt = conn.prepare(
+        "SELECT DISTINCT t.id, t.session_id, t.turn_number, t.timestamp,
+                t.role, t.turn_type, t.content, t.content_summary, 0.0 as rank
+         FROM turns t
+         JOIN edit_regions er ON er.turn_id = t.id
+         WHERE er.file_path = ?1 AND er.start_line <= ?3 AND er.end_line >= ?2
+         ORDER BY t.id DESC
+         LIMIT ?4",
+    )?;
+
+    let rows = stmt.query_map(
+        rusqlite::params![file_path, start, end, limit as i64],
+        |row| {
+            Ok(TurnSearchResult {
+                turn_id: row.get(0)?,
+                session_id: row.get(1)?,
+                turn_number: row.get(2)?,
+                timestamp: row.get(3)?,
+                role: row.get(4)?,
+                turn_type: row.get(5)?,
+                content: row.get(6)?,
+                content_summary: row.get(7)?,
+                rank: row.get(8)?,
+                files: vec![file_path.to_string()],
+            })
+        },
+    )?;
+
+    let mut results = Vec::new();
+    for row in rows {
+        results.push(row?);
+    }
+    results.reverse();
+    Ok(results)
+}
+
+/// A past failure matching an error snippet, with what happened next.
+#[derive(Debug)]
+pub struct ErrorOccurrence {
+    /// The `bash_cmd` turn whose output contained the error
+    pub failure: TurnSearchResult,
+    /// Turns after the failure, up to and including the resolving command
+    pub followups: Vec<TurnSearchResult>,
+    /// The re-run of the failing command that no longer showed the error
+    pub resolved_by: Option<TurnSearchResult>,
+}
+
+/// Maximum turns to follow after a failure while looking for its resolution.
+const MAX_FOLLOWUPS: usize = 40;
+
+/// Most recent failed runs considered by `command_failures`.
+const MAX_FAILURE_CANDIDATES: i64 = 50;
+
+/// The command line of a `bash_cmd` turn (`$ cmd\noutput`).
+fn bash_command(content: &str) -> &str {
+    content
+        .lines()
+        .next()
+        .unwrap_or("")
+        .trim_start_matches("$ ")
+        .trim()
+}
+
+/// Lowercased alphanumeric terms of an error snippet, for matching output.
+fn error_terms(snippet: &str) -> Vec<String> {
+    snippet
+        .split(|c: char| !c.is_alphanumeric() && c != '_')
+        .filter(|t| !t.is_empty())
+        .map(|t| t.to_lowercase())
+        .collect()
+}
+
+/// Whether `content` contains every term of the error snippet.
+fn shows_error(content: &str, terms: &[String]) -> bool {
+    let content = content.to_lowercase();
+    terms.iter().all(|t| content.contains(t.as_str()))
+}
+
+/// Find past `bash_cmd` failures whose output matches `snippet`, newest
+/// first. For each, follow the session forward through edits and commands
+/// until the same command is re-run without the error.
+///
+/// Repeated failures inside one resolution window are reported once.
+pub fn error_occurrences(
+    conn: &Connection,
+    snippet: &str,
+    limit: usize,
+) -> Result<Vec<ErrorOccurrence>> {
+    let terms = error_terms(snippet);
+    if terms.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut failures = search_turns(conn, snippet, 100, None, Some("bash_cmd"))?;
+    failures.retain(|t| shows_error(&t.content, &terms));
+    failures.sort_by_key(|t| t.turn_id);
+
+    let mut occurrences: Vec<ErrorOccurrence> = Vec::new();
+    for failure in failures {
+        let covered = occurrences.iter().any(|o| {
+            o.failure.session_id == failure.session_id
+                && o.followups.iter().any(|f| f.turn_id == failure.turn_id)
+        });
+        if covered {
+            continue;
+        }
+
+        let command = bash_command(&failure.content).to_string();
+        let mut followups = Vec::new();
+        let mut resolved_by = None;
+        for turn in session_turns_after(conn, &failure.session_id, failure.turn_number, MAX_FOLLOWUPS)? {
+            let rerun = turn.turn_type == "bash_cmd" && bash_command(&turn.content) == command;
+            let fixed = rerun && !shows_error(&turn.content, &terms);
+            if fixed {
+                resolved_by = Some(turn);
+                break;
+            }
+            followups.push(turn);
+        }
+
+        occurrences.push(ErrorOccurrence {
+            failure,
+            followups,
+            resolved_by,
+        });
+    }
+
+    occurrences.reverse();
+    occurrences.truncate(limit);
+    Ok(occurrences)
+}
+
+/// The command line of an `error` turn ("Command failed (exit 1): cmd").
+pub fn failed_command(error_content: &str) -> Option<&str> {
+    let header = error_content.lines().next()?;
+    if let Some(cmd) = header.strip_prefix("Command interrupted: ") {
+        return Some(cmd);
+    }
+    let rest = header.strip_prefix("Command failed")?;
+    Some(rest.split_once("): ").map(|(_, cmd)| cmd).unwrap_or(rest))
+}
+
+/// Whether the `bash_cmd` turn `turn_id` failed: an `error` turn links back
+/// to it through `command_turn_id` in its metadata.
+pub fn run_failed(conn: &Connection, turn_id: i64) -> Result<bool> {
+    let failed = conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM turns
+                       WHERE turn_type = 'error'
+                         AND json_extract(metadata, '$.command_turn_id') = ?1)",
+        [turn_id],
+        |row| row.get(0),
+    )?;
+    Ok(failed)
+}
+
+/// For the `error` turn at `turns[idx]` (a session's turns in order), find
+/// the later run of the same command that didn't fail.
+pub fn error_resolution<'a>(
+    conn: &Connection,
+    turns: &'a [TurnSearchResult],
+    idx: usize,
+) -> Result<Option<&'a TurnSearchResult>> {
+    let Some(command) = failed_command(&turns[idx].content) else {
+        return Ok(None);
+    };
+    let first_line = format!("$ {command}");
+    for t in &turns[idx + 1..] {
+        let rerun = t.turn_type == "bash_cmd" && t.content.lines().next() == Some(first_line.as_str());
+        if rerun && !run_failed(conn, t.turn_id)? {
+            return Ok(Some(t));
+        }
+    }
+    Ok(None)
+}
+
+/// `error` turns in a session whose command hasn't since run successfully,
+/// one per command (the latest failure).
+pub fn unresolved_errors<'a>(
+    conn: &Connection,
+    turns: &'a [TurnSearchResult],
+) -> Result<Vec<&'a TurnSearchResult>> {
+    let mut unresolved: Vec<&TurnSearchResult> = Vec::new();
+    for (idx, turn) in turns.iter().enumerate() {
+        if turn.turn_type != "error" || error_resolution(conn, turns, idx)?.is_some() {
+            continue;
+        }
+        let command = failed_command(&turn.content);
+        unresolved.retain(|e| failed_command(&e.content) != command);
+        unresolved.push(turn);
+    }
+    Ok(unresolved)
+}
+
+/// A recent edit to a file made by another live session.
+#[derive(Debug)]
+pub struct ConcurrentEdit {
+    pub session_id: String,
+    pub minutes_ago: i64,
+    pub summary: String,
+}
+
+/// The latest edit to `file_path` by each of `sessions` (their subagent
+/// runs included) in the last `minutes`.
+pub fn recent_edits_by(
+    conn: &Connection,
+    file_path: &str,
+    sessions: &[String],
+    minutes: i64,
+) -> Result<Vec<ConcurrentEdit>> {
+    let mut stmt = conn.prepare(
+        "SELECT COALESCE(t.content_summary, t.content),
+                CAST((julianday('now') - julianday(t.timestamp)) * 1440 AS INTEGER)
+         FROM turns t
+         JOIN turn_files tf ON tf.turn_id = t.id
+         WHERE tf.file_path = ?1
+           AND t.turn_type IN ('code_edit', 'notebook_edit')
+           AND (t.session_id = ?2 OR t.session_id IN (
+                   SELECT id FROM sessions
+                   WHERE parent_session_id = ?2 AND start_source = 'subagent'))
+           AND t.timestamp >= datetime('now', ?3)
+         ORDER BY t.id DESC
+         LIMIT 1",
+    )?;
+    let window = format!("-{} minutes", minutes);
+    let mut edits = Vec::new();
+    for session_id in sessions {
+        let edit = stmt
+            .query_row(rusqlite::params![file_path, session_id, window], |row| {
+                Ok(ConcurrentEdit {
+                    session_id: session_id.clone(),
+                    summary: row.get(0)?,
+                    minutes_ago: row.get(1)?,
+                })
+            })
+            .ok();
+        edits.extend(edit);
+    }
+    Ok(edits)
+}
+
+/// A past failure of a command, with how it was eventually resolved.
+#[derive(Debug)]
+pub struct CommandFailure {
+    /// The `error` turn recorded for the failed run
+    pub error: TurnSearchResult,
+    /// The later successful run of the same command or of a variant of it
+    pub resolved_by: Option<TurnSearchResult>,
+    /// Files edited between the failure and the successful run
+    pub edited_files: Vec<String>,
+}
+
+/// A command line without the `cd dir &&` and `VAR=value` prefixes that
+/// don't change which program runs.
+fn command_body(command: &str) -> &str {
+    let mut rest = command.trim();
+    loop {
+        if let Some(after_cd) = rest.strip_prefix("cd ") {
+            let Some(end) = after_cd.find("&&").map(|i| i + 2).or_else(|| after_cd.find(';').map(|i| i + 1)) else {
+                return rest;
+            };
+            rest = after_cd[end..].trim_start();
+            continue;
+        }
+        let first = rest.split_whitespace().next().unwrap_or("");
+        let is_assignment = first.split_once('=').is_some_and(|(name, _)| {
+            !name.is_empty()
+                && !name.starts_with(|c: char| c.is_ascii_digit())
+                && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        });
+        if !is_assignment {
+            return rest;
+        }
+        rest = rest[first.len()..].trim_start();
+    }
+}
+
+/// Whether two command lines are the same program and subcommand with
+/// mostly the same arguments (`cargo test` and `cargo test --features x`),
+/// ignoring `cd` and environment prefixes.
+pub fn similar_commands(a: &str, b: &str) -> bool {
+    let a: Vec<&str> = command_body(a).split_whitespace().collect();
+    let b: Vec<&str> = command_body(b).split_whitespace().collect();
+    if a.is_empty() || b.is_empty() || a[..a.len().min(2)] != b[..b.len().min(2)] {
+        return false;
+    }
+    let a: std::collections::HashSet<&str> = a.into_iter().collect();
+    let b: std::collections::HashSet<&str> = b.into_iter().collect();
+    let shared = a.intersection(&b).count();
+    shared as f64 / a.union(&b).count() as f64 >= 0.5
+}
+
+/// Find past failures of `command` or commands similar to it, newest first,
+/// one per session and command. For each, follow the session forward to the
+/// first successful run of the same command or a variant of it.
+///
+/// Returns nothing if the latest run of exactly `command` succeeded: the
+/// failures are history by then. Stops with what it has found once
+/// `deadline` passes.
+pub fn command_failures(
+    conn: &Connection,
+    command: &str,
+    limit: usize,
+    deadline: Instant,
+) -> Result<Vec<CommandFailure>> {
+    let first_line = command.lines().next().unwrap_or("").trim();
+    // Program and subcommand: a similar command's line contains them
+    let key = command_body(first_line)
+        .split_whitespace()
+        .take(2)
+        .collect::<Vec<_>>()
+        .join(" ");
+    if key.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    // Failed runs whose command line mentions the key, through the link
+    // from each error turn to its command turn
+    let mut stmt = conn.prepare(
+        "SELECT e.id, e.session_id, e.turn_number, e.timestamp,
+                e.role, e.turn_type, e.content, e.content_summary, 0.0 as rank, c.id
+         FROM turns e
+         JOIN turns c ON c.id = json_extract(e.metadata, '$.command_turn_id')
+         WHERE e.turn_type = 'error'
+           AND instr(substr(c.content, 1, instr(c.content || char(10), char(10))), ?1) > 0
+         ORDER BY e.id DESC
+         LIMIT ?2",
+    )?;
+    let candidates: Vec<(TurnSearchResult, i64)> = stmt
+        .query_map(rusqlite::params![key, MAX_FAILURE_CANDIDATES], |row| {
+            Ok((
+                TurnSearchResult {
+                    turn_id: row.get(0)?,
+                    session_id: row.get(1)?,
+                    turn_number: row.get(2)?,
+                    timestamp: row.get(3)?,
+                    role: row.get(4)?,
+                    turn_type: row.get(5)?,
+                    content: row.get(6)?,
+                    content_summary: row.get(7)?,
+                    rank: row.get(8)?,
+                    files: Vec::new(),
+                },
+                row.get(9)?,
+            ))
+        })?
+        .collect::<rusqlite::Result<_>>()?;
+    let Some(oldest_run) = candidates.iter().map(|(_, run)| *run).min() else {
+        return Ok(Vec::new());
+    };
+
+    // The latest run of exactly `command` since those failures
+    let latest_run: Option<i64> = conn
+        .query_row(
+            "SELECT id FROM turns
+             WHERE id >= ?2 AND turn_type = 'bash_cmd'
+               AND (content = ?1 OR substr(content, 1, length(?1) + 1) = ?1 || char(10))
+             ORDER BY id DESC LIMIT 1",
+            rusqlite::params![format!("$ {first_line}"), oldest_run],
+            |row| row.get(0),
+        )
+        .optional()?;
+    if let Some(turn_id) = latest_run {
+        if !run_failed(conn, turn_id)? {
+            return Ok(Vec::new());
+        }
+    }
+
+    let mut failures: Vec<CommandFailure> = Vec::new();
+    for (error, _) in candidates {
+        if Instant::now() > deadline {
+            break;
+        }
+        let Some(failed) = failed_command(&error.content).map(|c| c.trim().to_string()) else {
+            continue;
+        };
+        let identical = failed == first_line;
+        if !identical && !similar_commands(&failed, first_line) {
+            continue;
+        }
+        let seen = failures.iter().any(|f| {
+            f.error.session_id == error.session_id
+                && failed_command(&f.error.content).map(str::trim) == Some(failed.as_str())
+        });
+        if seen {
+            continue;
+        }
+
+        let followups = session_turns_after(conn, &error.session_id, error.turn_number, MAX_FOLLOWUPS)?;
+        let mut resolved_by = None;
+        let mut edited_files: Vec<String> = Vec::new();
+        for turn in &followups {
+            if turn.turn_type == "code_edit" || turn.turn_type == "notebook_edit" {
+                let mut file_stmt =
+                    conn.prepare_cached("SELECT file_path FROM turn_files WHERE turn_id = ?1")?;
+                for f in file_stmt.query_map([turn.turn_id], |row| row.get::<_, String>(0))? {
+                    let f = f?;
+                    if !edited_files.contains(&f) {
+                        edited_files.push(f);
+                    }
+                }
+                continue;
+            }
+            if turn.turn_type != "bash_cmd" {
+                continue;
+            }
+            let ran = bash_command(&turn.content);
+            if (ran == failed || similar_commands(ran, &failed)) && !run_failed(conn, turn.turn_id)? {
+                resolved_by = Some(turn.clone());
+                break;
+            }
+        }
+
+        // A similar command only matters for what made it work, and not
+        // when that is the command about to run
+        if !identical {
+            match &resolved_by {
+                Some(r) if bash_command(&r.content) != first_line => {}
+                _ => continue,
+            }
+        }
+
+        failures.push(CommandFailure {
+            error,
+            resolved_by,
+            edited_files,
+        });
+        if failures.len() >= limit {
+            break;
+        }
+    }
+    Ok(failures)
+}
+
+/// Get up to `limit` turns of a session after `turn_number`, in order.
+fn session_turns_after(
+    conn: &Connection,
+    session_id: &str,
+    turn_number: i64,
+    limit: usize,
+) -> Result<Vec<TurnSearchResult>> {
+    let mut stmt = conn.prepare(
+        "SELECT t.id, t.session_id, t.turn_number, t.timestamp,
+                t.role, t.turn_type, t.content, t.content_summary, 0.0 as rank
+         FROM turns t
+         WHERE t.session_id = ?1 AND t.turn_number > ?2
+         ORDER BY t.turn_number ASC
+         LIMIT ?3",
+    )?;
+
+    let rows = stmt.query_map(
+        rusqlite::params![session_id, turn_number, limit as i64],
+        |row| {
+            Ok(TurnSearchResult {
+                turn_id: row.get(0)?,
+                session_id: row.get(1)?,
+                turn_number: row.get(2)?,
+                timestamp: row.get(3)?,
+                role: row.get(4)?,
+                turn_type: row.get(5)?,
+                content: row.get(6)?,
+                content_summary: row.get(7)?,
+                rank: row.get(8)?,
+                files: Vec::new(),
+            })
+        },
+    )?;
 
     let mut results = Vec::new();
     for row in rows {
@@ -578,3 +1638,179 @@ pub fn search_symbols_by_keywords(
     }
     Ok(results)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Db;
+    use crate::indexer::conversation;
+
+    #[test]
+    fn test_session_timeline_groups_by_request() {
+        let db = Db::open_memory().unwrap();
+        conversation::ensure_session(&db, "s1", "/nonexistent").unwrap();
+        conversation::index_turn(&db, "s1", "system", "git_catchup", "2 commits", None, &[]).unwrap();
+        conversation::index_turn(&db, "s1", "user", "request", "fix the parser", None, &[]).unwrap();
+        conversation::index_turn(&db, "s1", "assistant", "file_read", "Read file: a.rs", None, &[]).unwrap();
+        conversation::index_turn(&db, "s1", "assistant", "code_edit", "Edit a.rs:", None, &[]).unwrap();
+        conversation::index_turn(&db, "s1", "user", "request", "now add tests", None, &[]).unwrap();
+        conversation::index_turn(&db, "s1", "assistant", "bash_cmd", "$ cargo test", None, &[]).unwrap();
+
+        let timeline = session_timeline(&db.conn(), "s1").unwrap();
+        assert_eq!(timeline.len(), 3);
+        assert!(timeline[0].request.is_none());
+        assert_eq!(timeline[0].activity.len(), 1);
+        assert_eq!(timeline[1].request.as_ref().unwrap().content, "fix the parser");
+        assert_eq!(timeline[1].activity.len(), 2);
+        assert_eq!(timeline[2].activity[0].turn_type, "bash_cmd");
+    }
+
+    #[test]
+    fn test_list_sessions_keyword_matches_requests() {
+        let db = Db::open_memory().unwrap();
+        conversation::ensure_session(&db, "s1", "/nonexistent").unwrap();
+        conversation::ensure_session(&db, "s2", "/nonexistent").unwrap();
+        conversation::index_turn(&db, "s1", "user", "request", "refactor ranking", None, &[]).unwrap();
+        conversation::index_turn(&db, "s2", "user", "request", "update README", None, &[]).unwrap();
+
+        let conn = db.conn();
+        let filter = SessionFilter {
+            keyword: Some("ranking"),
+            ..Default::default()
+        };
+        let sessions = list_sessions(&conn, &filter, 10).unwrap();
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].session.id, "s1");
+        assert_eq!(sessions[0].request_count, 1);
+
+        let found = find_session(&conn, "s2").unwrap().unwrap();
+        assert_eq!(found.id, "s2");
+    }
+
+    #[test]
+    fn test_error_occurrences_follow_until_rerun_succeeds() {
+        let db = Db::open_memory().unwrap();
+        conversation::ensure_session(&db, "s1", "/nonexistent").unwrap();
+        let run = |role: &str, ty: &str, content: &str| {
+            conversation::index_turn(&db, "s1", role, ty, content, None, &[]).unwrap();
+        };
+        run("assistant", "bash_cmd", "$ cargo build\nerror[E0308]: mismatched types");
+        run("assistant", "code_edit", "Edit src/lib.rs: return i64");
+        run("assistant", "bash_cmd", "$ cargo build\nerror[E0308]: mismatched types");
+        run("assistant", "code_edit", "Edit src/lib.rs: cast with as");
+        run("assistant", "bash_cmd", "$ cargo build\nFinished dev profile");
+        run("assistant", "bash_cmd", "$ cargo test\nok");
+
+        let found = error_occurrences(&db.conn(), "mismatched types", 5).unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].followups.len(), 3);
+        let fix = found[0].resolved_by.as_ref().unwrap();
+        assert!(fix.content.contains("Finished"));
+    }
+
+    fn later() -> Instant {
+        Instant::now() + std::time::Duration::from_secs(60)
+    }
+
+    /// Index a `bash_cmd` turn; a failed one also gets its linked `error` turn,
+    /// indexed after `between` (turns that landed in the meantime).
+    fn bash_run(db: &Db, command: &str, output: &str, error: Option<&str>, between: &[&str]) {
+        let turn_id = conversation::index_turn(
+            db,
+            "s1",
+            "assistant",
+            "bash_cmd",
+            &format!("$ {command}\n{output}"),
+            None,
+            &[],
+        )
+        .unwrap();
+        for reply in between {
+            conversation::index_turn(db, "s1", "assistant", "explanation", reply, None, &[]).unwrap();
+        }
+        if let Some(error) = error {
+            let metadata = serde_json::json!({"command_turn_id": turn_id});
+            let content = format!("Command failed (exit 101): {command}\n{error}");
+            conversation::index_turn(db, "s1", "assistant", "error", &content, Some(&metadata), &[])
+                .unwrap();
+        }
+    }
+
+    #[test]
+    fn test_unresolved_errors_clear_after_successful_rerun() {
+        let db = Db::open_memory().unwrap();
+        conversation::ensure_session(&db, "s1", "/nonexistent").unwrap();
+        bash_run(&db, "cargo build", "[exit code 101]", Some("error[E0308]"), &[]);
+        bash_run(&db, "cargo test", "[exit code 101]", Some("1 test failed"), &[]);
+        conversation::index_turn(&db, "s1", "assistant", "code_edit", "Edit src/lib.rs:", None, &[])
+            .unwrap();
+        bash_run(&db, "cargo build", "Finished", None, &[]);
+        // A reply indexed between a run and its error turn doesn't make the
+        // run look successful
+        bash_run(&db, "cargo test", "[exit code 101]", Some("1 test failed"), &["Still failing."]);
+
+        let conn = db.conn();
+        let turns = session_turns(&conn, "s1").unwrap();
+        let unresolved = unresolved_errors(&conn, &turns).unwrap();
+        assert_eq!(unresolved.len(), 1);
+        assert_eq!(failed_command(&unresolved[0].content), Some("cargo test"));
+        assert_eq!(unresolved[0].turn_id, turns.last().unwrap().turn_id);
+        let fix = error_resolution(&conn, &turns, 1).unwrap().unwrap();
+        assert!(fix.content.contains("Finished"));
+    }
+
+    #[test]
+    fn test_command_failures_report_successful_variant() {
+        let db = Db::open_memory().unwrap();
+        conversation::ensure_session(&db, "s1", "/nonexistent").unwrap();
+        bash_run(&db, "cargo test", "[exit code 101]", Some("error: feature `x` is required"), &[]);
+        bash_run(&db, "cargo test --features x", "ok", None, &[]);
+
+        let conn = db.conn();
+        let failures = command_failures(&conn, "cargo test", 3, later()).unwrap();
+        assert_eq!(failures.len(), 1);
+        let fix = failures[0].resolved_by.as_ref().unwrap();
+        assert_eq!(bash_command(&fix.content), "cargo test --features x");
+        // Already running the variant that worked: nothing to say
+        assert!(command_failures(&conn, "cargo test --features x", 3, later()).unwrap().is_empty());
+        // Unrelated commands don't match
+        assert!(command_failures(&conn, "cargo build", 3, later()).unwrap().is_empty());
+        // `cd` and environment prefixes don't change the program
+        let prefixed = command_failures(&conn, "cd /p && RUST_LOG=debug cargo test", 3, later()).unwrap();
+        assert_eq!(prefixed.len(), 1);
+        assert!(!similar_commands("cd foo && cargo test", "cd foo && make"));
+        // Past the deadline: nothing more is looked up
+        assert!(command_failures(&conn, "cargo test", 3, Instant::now()).unwrap().is_empty());
+        drop(conn);
+
+        // Once the exact command succeeds, its old failures are history
+        bash_run(&db, "cargo test", "ok", None, &[]);
+        assert!(command_failures(&db.conn(), "cargo test", 3, later()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_region_edits_overlap() {
+        let db = Db::open_memory().unwrap();
+        conversation::ensure_session(&db, "s1", "/nonexistent").unwrap();
+        for (content, region) in [("top", (1, 3)), ("middle", (5, 8)), ("bottom", (10, 12))] {
+            let id = conversation::index_turn(&db, "s1", "assistant", "code_edit", content, None, &[])
+                .unwrap();
+            conversation::record_edit_regions(&db, id, "src/lib.rs", &[region]).unwrap();
+        }
+
+        let conn = db.conn();
+        let contents = |start, end| -> Vec<String> {
+            region_edits(&conn, "src/lib.rs", start, end, 10)
+                .unwrap()
+                .into_iter()
+                .map(|t| t.content)
+                .collect()
+        };
+        assert_eq!(contents(4, 9), ["middle"]);
+        // Shared boundary lines count, oldest first
+        assert_eq!(contents(3, 5), ["top", "middle"]);
+        assert_eq!(contents(12, 20), ["bottom"]);
+        assert!(contents(13, 20).is_empty());
+        assert!(region_edits(&conn, "src/main.rs", 1, 12, 10).unwrap().is_empty());
+    }
+}
@@ -80,6 +80,42 @@ pub fn clear_shutdown_tasks(db: &Db) -> Result<()> {
     Ok(())
 }
 
+/// Seconds without a heartbeat after which a registered server is presumed
+/// gone (killed without unregistering).
+const SERVER_TIMEOUT_SECS: u64 = 90;
+
+/// Register a running stdio MCP server, or refresh its heartbeat.
+pub fn register_server(db: &Db, pid: u32) -> Result<()> {
+    let conn = db.conn();
+    conn.execute(
+        "INSERT INTO mcp_servers (pid) VALUES (?1)
+         ON CONFLICT(pid) DO UPDATE SET last_seen = datetime('now')",
+        params![pid],
+    )?;
+    Ok(())
+}
+
+/// Remove a server from the registry when it exits.
+pub fn unregister_server(db: &Db, pid: u32) -> Result<()> {
+    let conn = db.conn();
+    conn.execute("DELETE FROM mcp_servers WHERE pid = ?1", params![pid])?;
+    Ok(())
+}
+
+/// Count registered servers other than `pid` with a recent heartbeat. With
+/// two Claude windows open on a project, a `shutdown` task may come from
+/// either window's SessionEnd, so no server can tell it's meant for itself.
+pub fn other_live_servers(db: &Db, pid: u32) -> Result<i64> {
+    let conn = db.conn();
+    let count = conn.query_row(
+        "SELECT COUNT(*) FROM mcp_servers
+         WHERE pid != ?1 AND last_seen >= datetime('now', ?2)",
+        params![pid, format!("-{} seconds", SERVER_TIMEOUT_SECS)],
+        |row| row.get(0),
+    )?;
+    Ok(count)
+}
+
 /// Recover tasks stuck in 'running' state (e.g. from a crash).
 /// Resets them back to 'pending' so they'll be retried.
 pub fn recover_stuck_tasks(db: &Db) -> Result<u64> {
@@ -0,0 +1,608 @@
+use anyhow::Result;
+use rusqlite::Connection;
+use serde::Deserialize;
+use std::path::Path;
+
+use crate::db::Db;
+use crate::db::search;
+use crate::hooks::pre_tool_use::make_relative;
+use crate::indexer::plans;
+use crate::inject::keywords::extract_keywords;
+use crate::inject::ranking;
+use crate::inject::tokens::{estimate, truncate};
+use crate::llm::LlmConfig;
+
+/// A source of briefing data.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Corpus {
+    /// Indexed symbols matching the task's keywords
+    Symbols,
+    /// Knowledge of any category matching the task's keywords
+    Knowledge,
+    /// Decisions and architecture knowledge, matching ones first
+    Decisions,
+    /// Conventions and preferences, whatever the task
+    Conventions,
+    /// The active plan, if any
+    Plans,
+    /// The active plan's target files and which have been edited
+    PlanTargets,
+    /// Recent edits and commands touching the files of matched symbols
+    FileActivity,
+    /// Files the main conversation has already read this session
+    SessionReads,
+}
+
+/// How to brief one type of subagent.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BriefingProfile {
+    pub corpora: Vec<Corpus>,
+    /// Maximum tokens of index data (before synthesis)
+    pub budget: usize,
+    /// Synthesize the data into a report with the configured LLM
+    pub synthesize: bool,
+}
+
+/// A `[briefings.<subagent type>]` section of the config file. Unset keys
+/// keep the built-in profile's value.
+///
+/// ```toml
+/// [briefings.Plan]
+/// corpora = ["decisions", "plans", "conventions"]
+/// budget = 1500                 # tokens
+/// synthesize = false
+///
+/// [briefings.default]     # user-defined agents without their own section
+/// enabled = false
+/// ```
+#[derive(Debug, Default, Deserialize)]
+struct ProfileConfig {
+    enabled: Option<bool>,
+    corpora: Option<Vec<Corpus>>,
+    budget: Option<usize>,
+    synthesize: Option<bool>,
+}
+
+/// Built-in profile for a subagent type. Types without their own profile
+/// (user-defined agents) get `default`.
+pub fn builtin_profile(subagent_type: &str) -> BriefingProfile {
+    use Corpus::*;
+    match subagent_type {
+        // What the main thread already knows, so Explore doesn't redo it
+        "Explore" => BriefingProfile {
+            corpora: vec![Symbols, Knowledge, SessionReads, FileActivity, PlanTargets],
+            budget: 2_500,
+            synthesize: true,
+        },
+        // Planning needs the why and the current plan, not raw symbols
+        "Plan" => BriefingProfile {
+            corpora: vec![Decisions, Plans, Conventions],
+            budget: 1_500,
+            synthesize: true,
+        },
+        "general-purpose" => BriefingProfile {
+            corpora: vec![Knowledge, Conventions, Symbols],
+            budget: 1_000,
+            synthesize: false,
+        },
+        _ => BriefingProfile {
+            corpora: vec![Conventions, Knowledge],
+            budget: 750,
+            synthesize: false,
+        },
+    }
+}
+
+/// The profile for a subagent type: the built-in one with the config's
+/// `[briefings.<type>]` (or `[briefings.default]` for types without a
+/// built-in) applied on top. None if briefings are disabled for the type.
+/// `[budgets] briefing` sets the budget for all types; a per-type `budget`
+/// overrides it.
+///
+/// The project's `.claude/claude-rlm.toml` takes priority over the global
+/// config.
+pub fn profile_for(subagent_type: &str, project_dir: &str) -> Option<BriefingProfile> {
+    resolve_profile(subagent_type, |section, key| {
+        crate::llm::config_value(project_dir, section, key)
+    })
+}
+
+/// `profile_for` with config values looked up by `setting(section, key)`.
+fn resolve_profile(
+    subagent_type: &str,
+    setting: impl Fn(&str, &str) -> Option<toml::Value>,
+) -> Option<BriefingProfile> {
+    let mut profile = builtin_profile(subagent_type);
+    if let Some(budget) = setting("budgets", "briefing").and_then(|v| v.as_integer()) {
+        profile.budget = budget.max(0) as usize;
+    }
+
+    let has_builtin = matches!(subagent_type, "Explore" | "Plan" | "general-purpose");
+    let config = parse_profile_config(subagent_type, setting("briefings", subagent_type)).or_else(|| {
+        if has_builtin {
+            None
+        } else {
+            parse_profile_config("default", setting("briefings", "default"))
+        }
+    });
+
+    if let Some(config) = config {
+        if config.enabled == Some(false) {
+            return None;
+        }
+        if let Some(corpora) = config.corpora {
+            profile.corpora = corpora;
+        }
+        if let Some(budget) = config.budget {
+            profile.budget = budget;
+        }
+        if let Some(synthesize) = config.synthesize {
+            profile.synthesize = synthesize;
+        }
+    }
+    Some(profile)
+}
+
+/// Parse a `[briefings.<name>]` section.
+fn parse_profile_config(name: &str, section: Option<toml::Value>) -> Option<ProfileConfig> {
+    match section?.try_into() {
+        Ok(config) => Some(config),
+        Err(e) => {
+            eprintln!("[claude-rlm] Warning: invalid [briefings.{}] config: {}", name, e);
+            None
+        }
+    }
+}
+
+/// Build a briefing for a subagent from the corpora in its profile.
+///
+/// 1. Extracts keywords from the prompt and finds matching symbols
+/// 2. Gathers each corpus, in profile order, within the budget; no corpus
+///    takes more than half of it, and knowledge appearing in one section is
+///    left out of later ones
+/// 3. If the profile asks for it and an LLM is configured, synthesizes a
+///    report; otherwise injects the data directly
+pub fn build_briefing(
+    project_dir: &str,
+    session_id: &str,
+    subagent_type: &str,
+    profile: &BriefingProfile,
+    prompt: &str,
+) -> Result<Option<String>> {
+    let db = Db::open(Path::new(project_dir))?;
+
+    let keywords = extract_keywords(prompt);
+    let keyword_only = profile
+        .corpora
+        .iter()
+        .all(|c| matches!(c, Corpus::Symbols | Corpus::Knowledge | Corpus::FileActivity));
+    if keywords.is_empty() && keyword_only {
+        return Ok(None);
+    }
+
+    // Looked up before `conn` is held: it locks the connection itself
+    let wants_plan = profile
+        .corpora
+        .iter()
+        .any(|c| matches!(c, Corpus::Plans | Corpus::PlanTargets));
+    let active_plan = if wants_plan {
+        plans::active_plan(&db, session_id)?
+    } else {
+        None
+    };
+
+    let prefix = project_dir.replace('\\', "/");
+    let prefix = prefix.trim_end_matches('/');
+
+    let mut data = String::new();
+    {
+        let conn = db.conn();
+
+        let symbols = if keywords.is_empty() {
+            Vec::new()
+        } else {
+            search::search_symbols_by_keywords(&conn, &keywords, 50)?
+        };
+        // Files of matched symbols, in match order
+        let mut matched_files: Vec<String> = Vec::new();
+        for sym in &symbols {
+            if !matched_files.contains(&sym.file_path) {
+                matched_files.push(sym.file_path.clone());
+            }
+        }
+
+        let mut seen_knowledge = std::collections::HashSet::new();
+        for corpus in &profile.corpora {
+            let remaining = profile.budget.saturating_sub(estimate(&data));
+            if remaining < 50 {
+                break;
+            }
+            let budget = remaining.min(profile.budget / 2);
+            let section = match corpus {
+                Corpus::Symbols => symbols_section(&symbols, prefix, budget),
+                Corpus::Knowledge => {
+                    knowledge_section(&conn, &keywords, &mut seen_knowledge, budget)?
+                }
+                Corpus::Decisions => {
+                    decisions_section(&conn, &keywords, &mut seen_knowledge, budget)?
+                }
+                Corpus::Conventions => conventions_section(&conn, &mut seen_knowledge, budget)?,
+                Corpus::Plans => active_plan
+                    .as_ref()
+                    .map(|plan| crate::inject::format_plan_section(plan, budget))
+                    .unwrap_or_default(),
+                Corpus::PlanTargets => active_plan
+                    .as_ref()
+                    .map(|plan| plan_targets_section(plan, budget))
+                    .unwrap_or_default(),
+                Corpus::FileActivity => {
+                    file_activity_section(&conn, &matched_files, &keywords, budget)?
+                }
+                Corpus::SessionReads => {
+                    session_reads_section(&conn, session_id, &matched_files, prefix, budget)?
+                }
+            };
+            if !section.is_empty() {
+                if !data.is_empty() {
+                    data.push('\n');
+                }
+                data.push_str(&section);
+            }
+        }
+    }
+
+    if data.is_empty() {
+        return Ok(None);
+    }
+
+    if profile.synthesize {
+        if let Some(llm) = LlmConfig::from_env() {
+            match synthesize_briefing(&llm, subagent_type, prompt, &data) {
+                Ok(report) => {
+                    return Ok(Some(format!(
+                        "## Pre-computed Briefing\n\
+                         This was prepared from the project's indexed memory.\n\
+                         Use it as your starting point — only explore files for gaps.\n\n\
+                         {}",
+                        report
+                    )));
+                }
+                Err(e) => {
+                    eprintln!("[claude-rlm] Haiku synthesis failed, using raw data: {}", e);
+                }
+            }
+        }
+    }
+
+    // Fallback: inject raw data directly
+    Ok(Some(format!(
+        "## Pre-computed Index Data\n\
+         The following was found in the project's indexed memory.\n\
+         Use this as your starting point.\n\n\
+         {}",
+        data
+    )))
+}
+
+/// Append `entry` to `section` if it fits in `budget`.
+fn push_entry(section: &mut String, entry: &str, budget: usize) -> bool {
+    if estimate(section) + estimate(entry) > budget {
+        return false;
+    }
+    section.push_str(entry);
+    true
+}
+
+/// Symbols matching the keywords.
+fn symbols_section(symbols: &[search::SymbolMatch], prefix: &str, budget: usize) -> String {
+    if symbols.is_empty() {
+        return String::new();
+    }
+
+    let mut out = String::from("### Matching Symbols\n");
+    for sym in symbols {
+        let rel_path = make_relative(&sym.file_path, prefix);
+        let parent = sym
+            .parent_name
+            .as_deref()
+            .map(|p| format!("{}::", p))
+            .unwrap_or_default();
+
+        let mut entry = format!(
+            "- {} {}{} — {}:{}-{}\n",
+            sym.kind, parent, sym.name, rel_path, sym.start_line, sym.end_line,
+        );
+        if let Some(sig) = &sym.signature {
+            if !sig.is_empty() {
+                entry.push_str(&format!("  `{}`\n", truncate(sig, 30)));
+            }
+        }
+        if let Some(doc) = &sym.doc_comment {
+            if !doc.is_empty() {
+                entry.push_str(&format!("  {}\n", truncate(doc, 30)));
+            }
+        }
+        if !push_entry(&mut out, &entry, budget) {
+            break;
+        }
+    }
+    out
+}
+
+/// Files the main conversation has read this session, those holding
+/// matched symbols first.
+fn session_reads_section(
+    conn: &Connection,
+    session_id: &str,
+    matched_files: &[String],
+    prefix: &str,
+    budget: usize,
+) -> Result<String> {
+    let mut files = search::session_files_read(conn, session_id, 50)?;
+    if files.is_empty() {
+        return Ok(String::new());
+    }
+    // Stable sort keeps most recent first within each group
+    files.sort_by_key(|f| !matched_files.contains(f));
+
+    let mut out = String::from(
+        "### Already Read in This Session\n\
+         The main conversation has read these files. Don't re-read them just to \
+         get an overview; open them only for details this briefing lacks.\n",
+    );
+    for f in &files {
+        let marker = if matched_files.contains(f) { " (matches task)" } else { "" };
+        let entry = format!("- {}{}\n", make_relative(f, prefix), marker);
+        if !push_entry(&mut out, &entry, budget) {
+            break;
+        }
+    }
+    Ok(out)
+}
+
+/// Recent activity (edits, commands, decisions) touching the files of
+/// matched symbols, ranked by type, recency, file affinity and relevance to
+/// the task's keywords.
+fn file_activity_section(
+    conn: &Connection,
+    matched_files: &[String],
+    keywords: &[String],
+    budget: usize,
+) -> Result<String> {
+    let mut turns: Vec<search::TurnSearchResult> = Vec::new();
+    for file in matched_files.iter().take(8) {
+        for turn in search::file_history(conn, file, 5)? {
+            // Reads are covered by the session reads section
+            if turn.turn_type == "file_read" {
+                continue;
+            }
+            match turns.iter_mut().find(|t| t.turn_id == turn.turn_id) {
+                Some(existing) => existing.files.extend(turn.files),
+                None => turns.push(turn),
+            }
+        }
+    }
+    if turns.is_empty() {
+        return Ok(String::new());
+    }
+
+    let heading = "### Recent Activity on These Files\n";
+    let ranked = ranking::ranked_select(&turns, matched_files, keywords, budget.saturating_sub(estimate(heading)));
+    if ranked.is_empty() {
+        return Ok(String::new());
+    }
+    Ok(format!("{}{}", heading, ranked))
+}
+
+/// The active plan's target files, marked done once edited.
+fn plan_targets_section(plan: &plans::PlanInfo, budget: usize) -> String {
+    if plan.target_files.is_empty() {
+        return String::new();
+    }
+    let title = plan.title.as_deref().unwrap_or("Untitled Plan");
+    let mut out = format!("### Active Plan Targets: {} [{}]\n", title, plan.status);
+    for target in &plan.target_files {
+        let edited = plan
+            .progress
+            .iter()
+            .any(|p| p.file_path.ends_with(target.as_str()) || target.ends_with(&p.file_path));
+        let entry = format!("- [{}] {}\n", if edited { "x" } else { " " }, target);
+        if !push_entry(&mut out, &entry, budget) {
+            break;
+        }
+    }
+    out
+}
+
+/// Knowledge matching the keywords: per-keyword searches with OR semantics.
+fn knowledge_section(
+    conn: &Connection,
+    keywords: &[String],
+    seen: &mut std::collections::HashSet<i64>,
+    budget: usize,
+) -> Result<String> {
+    let mut knowledge = Vec::new();
+    let mut seen_ids = std::collections::HashSet::new();
+    for kw in keywords.iter().take(5) {
+        if let Ok(results) = search::search_knowledge(conn, kw, 3, None) {
+            for r in results {
+                if seen_ids.insert(r.id) {
+                    knowledge.push(r);
+                }
+            }
+        }
+    }
+    knowledge.truncate(8);
+    Ok(format_knowledge("### Relevant Knowledge\n", &knowledge, seen, budget))
+}
+
+/// Decisions and architecture knowledge: keyword matches, then the most
+/// confident of the rest.
+fn decisions_section(
+    conn: &Connection,
+    keywords: &[String],
+    seen: &mut std::collections::HashSet<i64>,
+    budget: usize,
+) -> Result<String> {
+    const CATEGORIES: &[&str] = &["decision", "architecture"];
+    let mut decisions: Vec<_> = search::search_knowledge_any(conn, keywords, 20)?
+        .into_iter()
+        .filter(|k| CATEGORIES.contains(&k.category.as_str()))
+        .take(5)
+        .collect();
+    for k in search::top_knowledge(conn, CATEGORIES, 10)? {
+        if decisions.len() >= 10 {
+            break;
+        }
+        if !decisions.iter().any(|d| d.id == k.id) {
+            decisions.push(k);
+        }
+    }
+    Ok(format_knowledge("### Decisions\n", &decisions, seen, budget))
+}
+
+/// Conventions and preferences, most confident first.
+fn conventions_section(
+    conn: &Connection,
+    seen: &mut std::collections::HashSet<i64>,
+    budget: usize,
+) -> Result<String> {
+    let conventions = search::top_knowledge(conn, &["convention", "preference"], 8)?;
+    Ok(format_knowledge("### Conventions\n", &conventions, seen, budget))
+}
+
+/// Format knowledge entries under a heading, skipping entries already in
+/// an earlier section. Empty if there are none left.
+fn format_knowledge(
+    heading: &str,
+    knowledge: &[search::KnowledgeSearchResult],
+    seen: &mut std::collections::HashSet<i64>,
+    budget: usize,
+) -> String {
+    let mut out = String::from(heading);
+    for k in knowledge {
+        if seen.contains(&k.id) {
+            continue;
+        }
+        let entry = format!(
+            "- [{}] **{}**: {}\n",
+            k.category,
+            k.subject,
+            truncate(&k.content, 50)
+        );
+        if !push_entry(&mut out, &entry, budget) {
+            break;
+        }
+        seen.insert(k.id);
+    }
+    if out.len() == heading.len() {
+        return String::new();
+    }
+    out
+}
+
+/// Call Haiku to synthesize a briefing from the raw data.
+fn synthesize_briefing(
+    llm: &LlmConfig,
+    subagent_type: &str,
+    prompt: &str,
+    data: &str,
+) -> Result<String> {
+    let system = match subagent_type {
+        "Plan" => "\
+You are a project analyst preparing a briefing for a planning agent.
+Given the agent's task and the project's recorded memory, produce a concise report:
+1. Past decisions and architecture that constrain the plan, with their reasons
+2. The state of any active plan and what is left in it
+3. Conventions the plan must follow
+4. Flag open questions the plan should resolve
+
+Be concise and factual. No preamble. Use markdown formatting.",
+        _ => "\
+You are a codebase analyst preparing a briefing for a code exploration agent.
+Given the agent's task and indexed project data, produce a concise report:
+1. Group related symbols and explain how they connect
+2. Note file locations using file:line format
+3. Include any relevant project knowledge or decisions
+4. Flag gaps — what the agent should still investigate via file reads
+
+Be concise and factual. No preamble. Use markdown formatting.",
+    };
+
+    let user_msg = format!(
+        "## Agent's Task\n{}\n\n## Indexed Data\n{}\n\nProduce a briefing report.",
+        prompt, data
+    );
+
+    llm.complete(system, &user_msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_profile_config_overrides_builtin() {
+        let config: toml::Table = "[budgets]\nbriefing = 600\n\n[briefings.Plan]\nbudget = 1000\n\n[briefings.default]\ncorpora = [\"plans\"]\n\n[briefings.reviewer]\nenabled = false\n"
+            .parse()
+            .unwrap();
+        let profile = |subagent_type: &str| {
+            resolve_profile(subagent_type, |section, key| {
+                config.get(section)?.as_table()?.get(key).cloned()
+            })
+        };
+
+        let plan = profile("Plan").unwrap();
+        assert_eq!(plan.budget, 1000);
+        assert_eq!(plan.corpora, builtin_profile("Plan").corpora);
+
+        // User-defined agents fall back to [briefings.default]
+        let custom = profile("my-agent").unwrap();
+        assert_eq!(custom.corpora, vec![Corpus::Plans]);
+        // ...but built-in types don't, and take the [budgets] budget
+        let explore = profile("Explore").unwrap();
+        assert_eq!(explore.corpora, builtin_profile("Explore").corpora);
+        assert_eq!(explore.budget, 600);
+
+        assert!(profile("reviewer").is_none());
+    }
+
+    #[test]
+    fn test_explore_briefing_includes_session_reads_and_activity() {
+        use crate::indexer::{code, conversation};
+        use crate::treesitter::languages::Lang;
+
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let project_dir = dir.to_string_lossy().to_string();
+        let file = dir.join("tokenizer.rs");
+        std::fs::write(&file, "fn tokenize_input(s: &str) -> Vec<String> {\n    Vec::new()\n}\n").unwrap();
+        let file_path = file.to_string_lossy().to_string();
+
+        let db = Db::open(dir).unwrap();
+        code::index_file(&db, &file, Lang::Rust).unwrap();
+        conversation::ensure_session(&db, "s1", &project_dir).unwrap();
+        let touch = |ty: &str, content: &str, action: &str| {
+            let files = [(file_path.clone(), action.to_string())];
+            conversation::index_turn(&db, "s1", "assistant", ty, content, None, &files).unwrap();
+        };
+        touch("file_read", "Read tokenizer.rs", "read");
+        touch("code_edit", "Edit tokenizer.rs: return an empty Vec", "edit");
+
+        let profile = BriefingProfile {
+            synthesize: false,
+            ..builtin_profile("Explore")
+        };
+        let briefing = build_briefing(&project_dir, "s1", "Explore", &profile, "how does tokenize_input work")
+            .unwrap()
+            .unwrap();
+        assert!(briefing.contains("### Matching Symbols"));
+        assert!(briefing.contains("### Already Read in This Session\n"));
+        assert!(briefing.contains("- tokenizer.rs (matches task)"));
+        assert!(briefing.contains("### Recent Activity on These Files"));
+        assert!(briefing.contains("return an empty Vec"));
+        // The read is listed once, not repeated as activity
+        assert!(!briefing.contains("Read tokenizer.rs"));
+    }
+}
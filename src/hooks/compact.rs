@@ -1,7 +1,26 @@
-use anyhow::Result;
+use anyhow::{anyhow, Result};
+use serde_json::json;
+use std::time::Duration;
 
-use crate::db::Db;
+use crate::db::{search, Db};
 use crate::hooks::{self, HookInput};
+use crate::inject::ranking::type_label;
+use crate::inject::tokens::{estimate, truncate};
+use crate::llm::LlmConfig;
+
+/// Header of a checkpoint summarized by the LLM. Each one builds on the
+/// previous checkpoint, so the latest covers the whole session.
+pub const LLM_CHECKPOINT_HEADER: &str = "[Pre-Compaction Checkpoint: summary]";
+
+/// Header of a checkpoint built from the session's requests and edits.
+const HEURISTIC_CHECKPOINT_HEADER: &str = "[Pre-Compaction Checkpoint]";
+
+/// Time allowed for the LLM to summarize; past it, the heuristic summary is
+/// stored instead. Well within the PreCompact hook's timeout.
+const CHECKPOINT_TIME_LIMIT: Duration = Duration::from_secs(12);
+
+/// Tokens of session activity sent to the LLM, newest first.
+const TRANSCRIPT_BUDGET: usize = 3_000;
 
 /// Handle PreCompact hook: ensure all context is indexed before compaction.
 ///
@@ -21,7 +40,8 @@ pub fn handle(input: &HookInput) -> Result<()> {
 
     // 1. Generate checkpoint summary — this is the critical part that
     //    survives compaction. Must complete before compaction proceeds.
-    generate_checkpoint_summary(&db, &session_id)?;
+    let llm = LlmConfig::from_env();
+    generate_checkpoint_summary(&db, &session_id, llm.as_ref())?;
     eprintln!("[claude-rlm] PreCompact: checkpoint saved");
 
     // 2. Enqueue stale-file re-indexing as a background task for the MCP server.
@@ -32,20 +52,124 @@ pub fn handle(input: &HookInput) -> Result<()> {
     Ok(())
 }
 
-/// Generate a checkpoint summary of the session so far and store it as a special turn.
-fn generate_checkpoint_summary(db: &Db, session_id: &str) -> Result<()> {
-    let conn = db.conn();
+/// Generate a checkpoint summary of the session so far and store it as a
+/// special turn. With an LLM configured, the activity since the previous
+/// checkpoint is summarized on top of that checkpoint; on failure or timeout
+/// the heuristic summary is used.
+fn generate_checkpoint_summary(db: &Db, session_id: &str, llm: Option<&LlmConfig>) -> Result<()> {
+    let turns = search::session_turns(&db.conn(), session_id)?;
+    if turns.is_empty() {
+        return Ok(());
+    }
 
-    // Gather stats
-    let turn_count: i64 = conn.query_row(
-        "SELECT COUNT(*) FROM turns WHERE session_id = ?1",
-        [session_id],
-        |row| row.get(0),
+    let previous = turns.iter().rev().find(|t| t.turn_type == "checkpoint");
+    // Nothing happened since the last checkpoint: it still stands
+    if previous.is_some_and(|p| p.turn_id == turns[turns.len() - 1].turn_id) {
+        return Ok(());
+    }
+    let (summary, generator) = match llm {
+        Some(llm) => match summarize_since(llm, previous, &turns) {
+            Ok(summary) => (summary, "llm"),
+            Err(e) => {
+                eprintln!("[claude-rlm] LLM checkpoint failed, using heuristic summary: {}", e);
+                (heuristic_summary(db, session_id)?, "heuristic")
+            }
+        },
+        None => (heuristic_summary(db, session_id)?, "heuristic"),
+    };
+
+    let metadata = json!({
+        "generator": generator,
+        "previous_checkpoint": previous.map(|t| t.turn_id),
+    });
+    crate::indexer::conversation::index_turn(
+        db,
+        session_id,
+        "system",
+        "checkpoint",
+        &summary,
+        Some(&metadata),
+        &[],
     )?;
 
-    if turn_count == 0 {
-        return Ok(());
+    Ok(())
+}
+
+/// Ask the LLM to update the previous checkpoint with the turns after it.
+fn summarize_since(
+    llm: &LlmConfig,
+    previous: Option<&search::TurnSearchResult>,
+    turns: &[search::TurnSearchResult],
+) -> Result<String> {
+    let since = previous.map(|t| t.turn_number).unwrap_or(0);
+    let transcript = checkpoint_transcript(turns, since);
+    if transcript.is_empty() {
+        return Err(anyhow!("no activity since the previous checkpoint"));
+    }
+
+    let system = "\
+You write the checkpoint a coding assistant reads after its context is compacted.
+Given the previous checkpoint (if any) and the session activity since, produce an
+updated checkpoint that replaces the previous one. Use these markdown sections:
+## Goal — what the user is trying to achieve, in their terms
+## Current state — what is working, what is in progress, the current hypothesis
+## Tried — approaches attempted and how each turned out, failures included
+## Decisions — choices made and why
+## Next steps — what to do next, most immediate first
+## Files — files changed and their role
+Carry forward everything from the previous checkpoint that still holds and drop
+what has been superseded. Be concrete: names, commands, error messages. No preamble.
+Stay under 400 words.";
+
+    let previous_text = previous
+        .map(|t| t.content.as_str())
+        .unwrap_or("(none: this is the session's first checkpoint)");
+    let user_msg = format!(
+        "## Previous Checkpoint\n{}\n\n## Activity Since\n{}",
+        previous_text, transcript
+    );
+
+    let response = llm.complete_within(system, &user_msg, CHECKPOINT_TIME_LIMIT)?;
+    let response = response.trim();
+    if response.is_empty() {
+        return Err(anyhow!("empty response"));
+    }
+    Ok(format!("{}\n{}\n", LLM_CHECKPOINT_HEADER, response))
+}
+
+/// The session's turns after turn number `since`, one line each, keeping
+/// the newest within TRANSCRIPT_BUDGET tokens.
+fn checkpoint_transcript(turns: &[search::TurnSearchResult], since: i64) -> String {
+    let mut lines = Vec::new();
+    let mut used = 0;
+    for turn in turns.iter().rev().filter(|t| t.turn_number > since) {
+        if turn.turn_type == "checkpoint" {
+            continue;
+        }
+        let files = if turn.files.is_empty() {
+            String::new()
+        } else {
+            format!(" [files: {}]", turn.files.join(", "))
+        };
+        let line = format!(
+            "[{}]{} {}\n",
+            type_label(&turn.turn_type),
+            files,
+            truncate(&turn.content.replace('\n', " "), 120)
+        );
+        used += estimate(&line);
+        if used > TRANSCRIPT_BUDGET {
+            break;
+        }
+        lines.push(line);
     }
+    lines.reverse();
+    lines.concat()
+}
+
+/// Checkpoint from the session's requests, modified files and last edits.
+fn heuristic_summary(db: &Db, session_id: &str) -> Result<String> {
+    let conn = db.conn();
 
     // Get all user requests
     let mut stmt = conn.prepare(
@@ -81,17 +205,11 @@ fn generate_checkpoint_summary(db: &Db, session_id: &str) -> Result<()> {
         .collect();
 
     // Build the checkpoint summary
-    let mut summary = String::from("[Pre-Compaction Checkpoint]\n");
+    let mut summary = format!("{}\n", HEURISTIC_CHECKPOINT_HEADER);
 
     summary.push_str("Tasks:\n");
     for (i, req) in requests.iter().enumerate() {
-        let truncated = if req.len() > 200 {
-            let end = req.floor_char_boundary(200);
-            format!("{}...", &req[..end])
-        } else {
-            req.clone()
-        };
-        summary.push_str(&format!("  {}. {}\n", i + 1, truncated));
+        summary.push_str(&format!("  {}. {}\n", i + 1, truncate(req, 50)));
     }
 
     if !modified_files.is_empty() {
@@ -104,26 +222,45 @@ fn generate_checkpoint_summary(db: &Db, session_id: &str) -> Result<()> {
     if !recent_edits.is_empty() {
         summary.push_str("\nRecent edits:\n");
         for edit in recent_edits.iter().rev() {
-            let truncated = if edit.len() > 300 {
-                let end = edit.floor_char_boundary(300);
-                format!("{}...", &edit[..end])
-            } else {
-                edit.clone()
-            };
-            summary.push_str(&format!("  - {}\n", truncated));
+            summary.push_str(&format!("  - {}\n", truncate(edit, 75)));
         }
     }
 
-    // Store as a special "checkpoint" turn
-    crate::indexer::conversation::index_turn(
-        db,
-        session_id,
-        "system",
-        "checkpoint",
-        &summary,
-        None,
-        &[],
-    )?;
+    Ok(summary)
+}
 
-    Ok(())
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::indexer::conversation;
+
+    #[test]
+    fn test_checkpoints_chain() {
+        let db = Db::open_memory().unwrap();
+        conversation::ensure_session(&db, "s1", "/p").unwrap();
+        let add = |ty: &str, content: &str| {
+            conversation::index_turn(&db, "s1", "user", ty, content, None, &[]).unwrap();
+        };
+        add("request", "Fix the tokenizer crash");
+        add("error", "thread panicked at lexer.rs: index out of bounds");
+
+        // Without an LLM the heuristic summary is stored
+        generate_checkpoint_summary(&db, "s1", None).unwrap();
+        add("request", "Now add a regression test");
+
+        let turns = search::session_turns(&db.conn(), "s1").unwrap();
+        let checkpoint = turns.iter().find(|t| t.turn_type == "checkpoint").unwrap();
+        assert!(checkpoint.content.starts_with(HEURISTIC_CHECKPOINT_HEADER));
+        assert!(checkpoint.content.contains("Fix the tokenizer crash"));
+
+        // The next summary only covers what came after the checkpoint
+        let transcript = checkpoint_transcript(&turns, checkpoint.turn_number);
+        assert_eq!(transcript, "[User] Now add a regression test\n");
+
+        // A second compaction with nothing new keeps the existing checkpoint
+        generate_checkpoint_summary(&db, "s1", None).unwrap();
+        generate_checkpoint_summary(&db, "s1", None).unwrap();
+        let turns = search::session_turns(&db.conn(), "s1").unwrap();
+        assert_eq!(turns.iter().filter(|t| t.turn_type == "checkpoint").count(), 2);
+    }
 }
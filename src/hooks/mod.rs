@@ -3,6 +3,8 @@ pub mod tool_use;
 pub mod compact;
 pub mod session;
 pub mod pre_tool_use;
+pub mod briefing;
+pub mod stop;
 
 use anyhow::Result;
 use serde::Deserialize;
@@ -26,7 +28,7 @@ pub struct HookInput {
     /// The prompt content (for UserPromptSubmit hooks)
     pub prompt: Option<String>,
 
-    /// The transcript path (for SessionEnd hooks)
+    /// The transcript path (JSONL conversation log, sent with every hook)
     pub transcript_path: Option<String>,
 
     /// The session source (for SessionStart: "startup" or "compact")
@@ -68,6 +70,24 @@ pub fn session_id(input: &HookInput) -> String {
         .unwrap_or_else(|| uuid::Uuid::new_v4().to_string())
 }
 
+/// The session a tool call belongs to, created if needed: the subagent run
+/// it came from, else the hook's own session.
+pub fn tool_session(db: &crate::db::Db, input: &HookInput, project_dir: &str) -> Result<String> {
+    let session_id = session_id(input);
+    crate::indexer::conversation::ensure_session(db, &session_id, project_dir)?;
+    let extra_str = |key: &str| input.extra.get(key).and_then(|v| v.as_str());
+    let owner = crate::indexer::subagents::attribute(
+        db,
+        &session_id,
+        extra_str("agent_id"),
+        extra_str("agent_type"),
+    )?;
+    if owner != session_id {
+        crate::indexer::conversation::ensure_session(db, &owner, project_dir)?;
+    }
+    Ok(owner)
+}
+
 /// Log a hook invocation to the hook_log table.
 /// Fire-and-forget — hook logging must never break a hook.
 pub fn log_hook(db: &crate::db::Db, input: &HookInput, hook_event: &str, detail: &str) {
@@ -1,29 +1,49 @@
 use anyhow::Result;
 use serde_json::json;
 use std::path::Path;
+use std::time::{Duration, Instant};
 
 use crate::db::Db;
 use crate::db::search;
-use crate::hooks::{self, HookInput};
-use crate::llm::LlmConfig;
+use crate::hooks::{self, briefing, HookInput};
+use crate::indexer::{conversation, subagents};
+use crate::inject::tokens::truncate;
+
+/// Maximum past failures shown before a command runs.
+const MAX_BASH_WARNINGS: usize = 3;
+
+/// Hard limit on looking up past failures. The lookup runs before every
+/// command, so it gives up rather than delay one.
+const BASH_WARNING_TIME_LIMIT: Duration = Duration::from_millis(250);
+
+/// Maximum warnings injected before an edit.
+const MAX_EDIT_WARNINGS: usize = 4;
+
+/// Knowledge categories worth a reminder before an edit.
+const WARNING_CATEGORIES: &[&str] = &["bug_fix", "convention", "decision"];
 
 /// Handle PreToolUse hook.
 ///
-/// For Task(subagent_type=Explore), builds a briefing from the project's
-/// indexed symbols and knowledge. If Haiku is configured, the raw data is
-/// synthesized into a focused report. Otherwise the raw data is injected
-/// directly. Either way the subagent starts with answers instead of
-/// instructions it would ignore.
+/// - Task: briefs subagents according to their type's profile (see
+///   `hooks::briefing`)
+/// - Edit/Write/MultiEdit: warns about past bug fixes, conventions and
+///   decisions that concern the file or the symbols being edited
+/// - Bash: warns if the command, or one like it, failed before
 pub fn handle(input: &HookInput) -> Result<()> {
-    let tool_name = match &input.tool_name {
-        Some(name) => name.as_str(),
-        None => return Ok(()),
-    };
-
-    if tool_name != "Task" {
-        return Ok(());
+    match input.tool_name.as_deref() {
+        Some("Task") => handle_task(input),
+        Some("Edit" | "Write" | "MultiEdit") => handle_edit(input),
+        Some("Bash") => handle_bash(input),
+        _ => Ok(()),
     }
+}
 
+/// For Task, builds a briefing from the project's memory using the profile
+/// for the subagent's type (Explore gets symbols and knowledge, Plan gets
+/// decisions and the active plan, ...). The briefing is prepended to the
+/// subagent's prompt, so it starts with answers instead of instructions it
+/// would ignore.
+fn handle_task(input: &HookInput) -> Result<()> {
     let tool_input = match &input.tool_input {
         Some(v) => v,
         None => return Ok(()),
@@ -38,12 +58,27 @@ pub fn handle(input: &HookInput) -> Result<()> {
     let project_dir = hooks::project_dir(input);
     let log_db = Db::open(Path::new(&project_dir)).ok();
 
-    if subagent_type != "Explore" {
+    // Open a session for the run, so the subagent's tool calls are
+    // attributed to it rather than mixed into this session
+    if let Some(db) = &log_db {
+        let session_id = hooks::session_id(input);
+        let key = input
+            .extra
+            .get("tool_use_id")
+            .and_then(|v| v.as_str())
+            .map(str::to_string)
+            .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+        if let Err(e) = subagents::start(db, &session_id, &project_dir, &key, subagent_type) {
+            eprintln!("[claude-rlm] Subagent tracking failed: {}", e);
+        }
+    }
+
+    let Some(profile) = briefing::profile_for(subagent_type, &project_dir) else {
         if let Some(db) = &log_db {
-            hooks::log_hook(db, input, "PreToolUse", &format!("Task/{}: skipped", subagent_type));
+            hooks::log_hook(db, input, "PreToolUse", &format!("Task/{}: disabled", subagent_type));
         }
         return Ok(());
-    }
+    };
 
     let original_prompt = tool_input
         .get("prompt")
@@ -52,15 +87,16 @@ pub fn handle(input: &HookInput) -> Result<()> {
 
     if original_prompt.is_empty() {
         if let Some(db) = &log_db {
-            hooks::log_hook(db, input, "PreToolUse", "Task/Explore: no prompt");
+            hooks::log_hook(db, input, "PreToolUse", &format!("Task/{}: no prompt", subagent_type));
         }
         return Ok(());
     }
 
-    match build_explore_briefing(&project_dir, original_prompt) {
+    let session_id = hooks::session_id(input);
+    match briefing::build_briefing(&project_dir, &session_id, subagent_type, &profile, original_prompt) {
         Ok(Some(briefing)) => {
             if let Some(db) = &log_db {
-                let detail = format!("Task/Explore: briefing ({} bytes)", briefing.len());
+                let detail = format!("Task/{}: briefing ({} bytes)", subagent_type, briefing.len());
                 hooks::log_hook(db, input, "PreToolUse", &detail);
             }
 
@@ -68,219 +104,298 @@ pub fn handle(input: &HookInput) -> Result<()> {
                 "{}\n\n---\nOriginal task:\n{}",
                 briefing, original_prompt
             );
+            // Keep the rest of the input (subagent_type, description, ...)
+            let mut updated_input = tool_input.clone();
+            updated_input["prompt"] = json!(augmented_prompt);
 
             let output = json!({
                 "hookSpecificOutput": {
                     "hookEventName": "PreToolUse",
                     "permissionDecision": "allow",
-                    "updatedInput": {
-                        "prompt": augmented_prompt
-                    }
+                    "updatedInput": updated_input
                 }
             });
             println!("{}", serde_json::to_string(&output)?);
         }
         Ok(None) => {
             if let Some(db) = &log_db {
-                hooks::log_hook(db, input, "PreToolUse", "Task/Explore: no data");
+                hooks::log_hook(db, input, "PreToolUse", &format!("Task/{}: no data", subagent_type));
             }
         }
         Err(e) => {
             if let Some(db) = &log_db {
-                hooks::log_hook(db, input, "PreToolUse", &format!("Task/Explore: error: {}", e));
+                let detail = format!("Task/{}: error: {}", subagent_type, e);
+                hooks::log_hook(db, input, "PreToolUse", &detail);
             }
-            eprintln!("[claude-rlm] Explore briefing failed: {}", e);
+            eprintln!("[claude-rlm] {} briefing failed: {}", subagent_type, e);
         }
     }
 
     Ok(())
 }
 
-/// Build a briefing for an Explore subagent.
-///
-/// 1. Extracts keywords from the prompt
-/// 2. Queries the symbol index for matches
-/// 3. Queries knowledge FTS for relevant entries
-/// 4. If Haiku is configured, synthesizes a report; otherwise formats raw data
-fn build_explore_briefing(project_dir: &str, prompt: &str) -> Result<Option<String>> {
-    let db = Db::open(Path::new(project_dir))?;
-    let conn = db.conn();
+/// Before an edit, remind Claude of knowledge tied to the file or to the
+/// symbols in the edited range ("last time this function broke because X").
+/// The edit proceeds through the normal permission flow either way.
+fn handle_edit(input: &HookInput) -> Result<()> {
+    let tool_name = input.tool_name.as_deref().unwrap_or("Edit");
+    let Some(tool_input) = &input.tool_input else {
+        return Ok(());
+    };
+    let Some(file_path) = tool_input.get("file_path").and_then(|v| v.as_str()) else {
+        return Ok(());
+    };
+
+    let project_dir = hooks::project_dir(input);
+    let db = Db::open(Path::new(&project_dir))?;
+
+    // Another open window editing the same file comes first: the file may
+    // have changed since it was read
+    let session_id = hooks::session_id(input);
+    let mut warnings = concurrent_edit_warnings(&db, &session_id, file_path)?;
+    warnings.extend(edit_warnings(&db, tool_name, tool_input, file_path)?);
+    let detail = format!("{}: {} warnings", tool_name, warnings.len());
+    hooks::log_hook(&db, input, "PreToolUse", &detail);
+    if warnings.is_empty() {
+        return Ok(());
+    }
 
-    // 1. Extract search keywords from the prompt
-    let keywords = extract_keywords(prompt);
-    if keywords.is_empty() {
-        return Ok(None);
+    let file_name = file_path.replace('\\', "/");
+    let file_name = file_name.rsplit('/').next().unwrap_or(file_path);
+    let mut context = format!("[ClaudeRLM] Before editing {}, from project memory:\n", file_name);
+    for w in &warnings {
+        context.push_str(w);
     }
 
-    // 2. Query symbols by keywords
-    let symbols = search::search_symbols_by_keywords(&conn, &keywords, 50)?;
-
-    // 3. Query knowledge — do per-keyword searches with OR semantics
-    let mut knowledge = Vec::new();
-    let mut seen_ids = std::collections::HashSet::new();
-    for kw in keywords.iter().take(5) {
-        if let Ok(results) = search::search_knowledge(&conn, kw, 3, None) {
-            for r in results {
-                if seen_ids.insert(r.id) {
-                    knowledge.push(r);
+    let output = json!({
+        "hookSpecificOutput": {
+            "hookEventName": "PreToolUse",
+            "additionalContext": context
+        }
+    });
+    println!("{}", serde_json::to_string(&output)?);
+    Ok(())
+}
+
+/// Warn about recent edits to the file by other live sessions.
+fn concurrent_edit_warnings(db: &Db, session_id: &str, file_path: &str) -> Result<Vec<String>> {
+    let live = conversation::live_sessions(db, session_id)?;
+    if live.is_empty() {
+        return Ok(Vec::new());
+    }
+    let conn = db.conn();
+    let edits = search::recent_edits_by(&conn, file_path, &live, conversation::LIVE_SESSION_MINUTES)?;
+    Ok(edits
+        .iter()
+        .map(|e| {
+            let id_end = e.session_id.floor_char_boundary(8.min(e.session_id.len()));
+            format!(
+                "- **Concurrent edit**: session {} (still open) edited this file {} min ago: {}. \
+                 Re-read the file before editing, and avoid undoing its changes.\n",
+                &e.session_id[..id_end],
+                e.minutes_ago,
+                truncate(e.summary.lines().next().unwrap_or(""), 40)
+            )
+        })
+        .collect())
+}
+
+/// Find knowledge that mentions the symbols in the edited range or the file
+/// itself, symbols first, and format each as a one-line reminder.
+fn edit_warnings(
+    db: &Db,
+    tool_name: &str,
+    tool_input: &serde_json::Value,
+    file_path: &str,
+) -> Result<Vec<String>> {
+    // Edited ranges, located in the file as it is before the edit
+    let ranges = match std::fs::read_to_string(file_path) {
+        Ok(source) => edit_targets(tool_name, tool_input)
+            .iter()
+            .flat_map(|old| super::tool_use::line_ranges_of(&source, old, 1))
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+
+    let conn = db.conn();
+    let mut symbols: Vec<String> = Vec::new();
+    for (start, end) in ranges {
+        for line in [start, end] {
+            if let Some(sym) = search::enclosing_symbol(&conn, file_path, line)? {
+                if !symbols.contains(&sym.name) {
+                    symbols.push(sym.name);
                 }
             }
         }
     }
-    knowledge.truncate(8);
 
-    // Release DB lock before any network call
-    drop(conn);
+    let file_name = file_path.replace('\\', "/");
+    let file_name = file_name.rsplit('/').next().unwrap_or(file_path).to_string();
+
+    let mut seen = std::collections::HashSet::new();
+    let mut warnings = Vec::new();
+    // Very short names match too much prose to be useful
+    let terms = symbols
+        .iter()
+        .map(|s| (s.as_str(), true))
+        .chain(std::iter::once((file_name.as_str(), false)))
+        .filter(|(term, _)| term.len() >= 4);
+    for (term, is_symbol) in terms {
+        for k in search::knowledge_mentioning(&conn, term, WARNING_CATEGORIES, MAX_EDIT_WARNINGS)? {
+            if warnings.len() >= MAX_EDIT_WARNINGS {
+                return Ok(warnings);
+            }
+            if !seen.insert(k.id) {
+                continue;
+            }
+            let label = match k.category.as_str() {
+                "bug_fix" => "Past bug fix",
+                "convention" => "Convention",
+                _ => "Decision",
+            };
+            let target = if is_symbol {
+                format!(" (`{}`)", term)
+            } else {
+                String::new()
+            };
+            warnings.push(format!(
+                "- {}{}: **{}** — {}\n",
+                label,
+                target,
+                k.subject,
+                truncate(&k.content.replace('\n', " "), 50)
+            ));
+        }
+    }
+    Ok(warnings)
+}
 
-    if symbols.is_empty() && knowledge.is_empty() {
-        return Ok(None);
+/// The text an edit replaces (`old_string` of each edit). Empty for Write,
+/// which replaces the whole file.
+fn edit_targets(tool_name: &str, tool_input: &serde_json::Value) -> Vec<String> {
+    let old_string = |v: &serde_json::Value| {
+        v.get("old_string")
+            .and_then(|s| s.as_str())
+            .filter(|s| !s.is_empty())
+            .map(|s| s.to_string())
+    };
+    match tool_name {
+        "Edit" => old_string(tool_input).into_iter().collect(),
+        "MultiEdit" => tool_input
+            .get("edits")
+            .and_then(|v| v.as_array())
+            .map(|edits| edits.iter().filter_map(old_string).collect())
+            .unwrap_or_default(),
+        _ => Vec::new(),
     }
+}
 
-    // 4. Format raw data
-    let data_section = format_raw_data(&symbols, &knowledge, project_dir);
-
-    // 5. Try Haiku synthesis, fall back to raw data
-    if let Some(llm) = LlmConfig::from_env() {
-        match synthesize_briefing(&llm, prompt, &data_section) {
-            Ok(report) => {
-                return Ok(Some(format!(
-                    "## Pre-computed Briefing\n\
-                     This was prepared from the project's indexed symbols and knowledge.\n\
-                     Use it as your starting point — only explore files for gaps.\n\n\
-                     {}",
-                    report
-                )));
-            }
+/// Before a command runs, show how the same or a similar command failed
+/// before and what made it work: edits, or a variant of the command.
+/// Local only (no LLM call); can be turned off per project.
+fn handle_bash(input: &HookInput) -> Result<()> {
+    let Some(command) = input
+        .tool_input
+        .as_ref()
+        .and_then(|v| v.get("command"))
+        .and_then(|v| v.as_str())
+    else {
+        return Ok(());
+    };
+
+    let project_dir = hooks::project_dir(input);
+    if is_bash_warning_disabled(&project_dir) {
+        return Ok(());
+    }
+    let db = Db::open(Path::new(&project_dir))?;
+
+    let failures = {
+        let deadline = Instant::now() + BASH_WARNING_TIME_LIMIT;
+        let conn = db.conn();
+        // Interrupt any query still running at the deadline
+        conn.progress_handler(1_000, Some(move || Instant::now() > deadline));
+        let found = search::command_failures(&conn, command, MAX_BASH_WARNINGS, deadline);
+        conn.progress_handler(0, None::<fn() -> bool>);
+        match found {
+            Ok(failures) => failures,
             Err(e) => {
-                eprintln!("[claude-rlm] Haiku synthesis failed, using raw data: {}", e);
+                eprintln!("[claude-rlm] Past failure lookup cut short: {}", e);
+                Vec::new()
             }
         }
+    };
+    hooks::log_hook(&db, input, "PreToolUse", &format!("Bash: {} past failures", failures.len()));
+    if failures.is_empty() {
+        return Ok(());
     }
 
-    // Fallback: inject raw data directly
-    Ok(Some(format!(
-        "## Pre-computed Index Data\n\
-         The following symbols and knowledge were found in the project index.\n\
-         Use this as your starting point.\n\n\
-         {}",
-        data_section
-    )))
+    let output = json!({
+        "hookSpecificOutput": {
+            "hookEventName": "PreToolUse",
+            "additionalContext": format_command_failures(&failures, &project_dir)
+        }
+    });
+    println!("{}", serde_json::to_string(&output)?);
+    Ok(())
 }
 
-/// Format symbol matches and knowledge into a readable data section.
-fn format_raw_data(
-    symbols: &[search::SymbolMatch],
-    knowledge: &[search::KnowledgeSearchResult],
-    project_dir: &str,
-) -> String {
+/// Format past failures as a short reminder.
+fn format_command_failures(failures: &[search::CommandFailure], project_dir: &str) -> String {
     let prefix = project_dir.replace('\\', "/");
     let prefix = prefix.trim_end_matches('/');
 
-    let mut out = String::new();
-
-    if !symbols.is_empty() {
-        out.push_str("### Matching Symbols\n");
-        for sym in symbols {
-            let rel_path = make_relative(&sym.file_path, prefix);
-            let parent = sym
-                .parent_name
-                .as_deref()
-                .map(|p| format!("{}::", p))
-                .unwrap_or_default();
-
-            out.push_str(&format!(
-                "- {} {}{} — {}:{}-{}\n",
-                sym.kind, parent, sym.name, rel_path, sym.start_line, sym.end_line,
-            ));
-            if let Some(sig) = &sym.signature {
-                if !sig.is_empty() {
-                    out.push_str(&format!("  `{}`\n", truncate(sig, 120)));
+    let mut out = String::from("[ClaudeRLM] A command like this failed before:\n");
+    for f in failures {
+        let mut lines = f.error.content.lines();
+        let header = lines.next().unwrap_or("");
+        let symptom = lines
+            .find(|l| !l.trim().is_empty() && !l.starts_with("Exit code"))
+            .unwrap_or("");
+        out.push_str(&format!("- {} ({})\n", header, f.error.timestamp));
+        if !symptom.is_empty() {
+            out.push_str(&format!("  {}\n", truncate(symptom.trim(), 50)));
+        }
+
+        let failed = search::failed_command(&f.error.content).unwrap_or("").trim();
+        match &f.resolved_by {
+            Some(r) => {
+                let ran = r.content.lines().next().unwrap_or("").trim_start_matches("$ ").trim();
+                let files: Vec<String> = f
+                    .edited_files
+                    .iter()
+                    .take(5)
+                    .map(|p| make_relative(p, prefix))
+                    .collect();
+                if ran != failed {
+                    out.push_str(&format!("  Worked as: `{}`\n", ran));
                 }
-            }
-            if let Some(doc) = &sym.doc_comment {
-                if !doc.is_empty() {
-                    out.push_str(&format!("  {}\n", truncate(doc, 120)));
+                if !files.is_empty() {
+                    out.push_str(&format!("  Fixed after editing: {}\n", files.join(", ")));
+                } else if ran == failed {
+                    out.push_str("  Succeeded when re-run without changes (possibly flaky)\n");
                 }
             }
+            None => out.push_str("  No successful run recorded afterwards\n"),
         }
     }
-
-    if !knowledge.is_empty() {
-        out.push_str("\n### Relevant Knowledge\n");
-        for k in knowledge {
-            out.push_str(&format!(
-                "- [{}] **{}**: {}\n",
-                k.category,
-                k.subject,
-                truncate(&k.content, 200)
-            ));
-        }
-    }
-
     out
 }
 
-/// Call Haiku to synthesize a briefing from the raw data.
-fn synthesize_briefing(llm: &LlmConfig, prompt: &str, data: &str) -> Result<String> {
-    let system = "\
-You are a codebase analyst preparing a briefing for a code exploration agent.
-Given the agent's task and indexed project data, produce a concise report:
-1. Group related symbols and explain how they connect
-2. Note file locations using file:line format
-3. Include any relevant project knowledge or decisions
-4. Flag gaps — what the agent should still investigate via file reads
-
-Be concise and factual. No preamble. Use markdown formatting.";
-
-    let user_msg = format!(
-        "## Agent's Task\n{}\n\n## Indexed Data\n{}\n\nProduce a briefing report.",
-        prompt, data
-    );
-
-    llm.complete(system, &user_msg)
-}
+/// Check if pre-bash warnings are disabled via env var or config. The
+/// project's `.claude/claude-rlm.toml` takes priority over the global config.
+fn is_bash_warning_disabled(project_dir: &str) -> bool {
+    if std::env::var("CLAUDE_RLM_NO_BASH_WARNINGS").ok().as_deref() == Some("1") {
+        return true;
+    }
 
-/// Extract meaningful search keywords from a prompt.
-///
-/// Filters out English stop words and common code-exploration boilerplate
-/// (e.g., "find", "function", "file") to leave domain-specific terms.
-fn extract_keywords(prompt: &str) -> Vec<String> {
-    const STOP_WORDS: &[&str] = &[
-        // English
-        "the", "a", "an", "and", "or", "but", "in", "on", "at", "to", "for",
-        "of", "with", "by", "from", "as", "is", "was", "are", "be", "been",
-        "being", "have", "has", "had", "do", "does", "did", "will", "would",
-        "could", "should", "may", "might", "shall", "can", "need", "must",
-        "it", "its", "this", "that", "these", "those", "you", "we", "they",
-        "me", "him", "her", "them", "my", "your", "his", "our", "their",
-        "what", "which", "who", "when", "where", "how", "not", "no", "nor",
-        "if", "then", "else", "so", "than", "too", "very", "just", "about",
-        "up", "out", "all", "any", "each", "every", "both", "few", "more",
-        "most", "other", "some", "such", "only", "into", "also", "well",
-        // Code exploration boilerplate
-        "find", "search", "look", "show", "list", "get", "check", "see",
-        "file", "files", "code", "codebase", "project", "directory",
-        "function", "functions", "class", "classes", "method", "methods",
-        "struct", "structs", "implementation", "implementations",
-        "module", "modules", "related", "relevant", "existing", "current",
-        "using", "used", "understand", "understanding", "want", "need",
-        "return", "returns", "here", "there", "between", "within",
-        "across", "through", "whether", "including", "like", "use",
-    ];
-
-    let stop_set: std::collections::HashSet<&str> = STOP_WORDS.iter().copied().collect();
-
-    prompt
-        .split(|c: char| !c.is_alphanumeric() && c != '_')
-        .filter(|w| w.len() > 2)
-        .map(|w| w.to_lowercase())
-        .filter(|w| !stop_set.contains(w.as_str()))
-        .collect::<std::collections::HashSet<_>>()
-        .into_iter()
-        .collect()
+    // Check [warnings] section in config TOML
+    crate::llm::config_value(project_dir, "warnings", "bash")
+        .and_then(|v| v.as_bool())
+        .is_some_and(|enabled| !enabled)
 }
 
-fn make_relative(path: &str, prefix: &str) -> String {
+/// Strip the project directory from a path.
+pub fn make_relative(path: &str, prefix: &str) -> String {
     let normalized = path.replace('\\', "/");
     normalized
         .strip_prefix(prefix)
@@ -289,11 +404,43 @@ fn make_relative(path: &str, prefix: &str) -> String {
         .to_string()
 }
 
-fn truncate(s: &str, max: usize) -> String {
-    if s.len() <= max {
-        s.to_string()
-    } else {
-        let end = s.floor_char_boundary(max);
-        format!("{}...", &s[..end])
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::indexer::code;
+    use crate::treesitter::languages::Lang;
+
+    #[test]
+    fn test_edit_warnings() {
+        let db = Db::open_memory().unwrap();
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("header.rs");
+        std::fs::write(&path, "fn parse_header(s: &str) -> u32 {\n    s.len() as u32\n}\n\nfn other() {}\n").unwrap();
+        code::index_file(&db, &path, Lang::Rust).unwrap();
+        let file_path = path.to_string_lossy().to_string();
+
+        {
+            let conn = db.conn();
+            for (category, subject, content) in [
+                ("bug_fix", "header overflow", "parse_header overflowed on long input; check the length first."),
+                ("convention", "error style", "Use anyhow for errors."),
+                ("preference", "header naming", "parse_header should keep its name."),
+            ] {
+                conn.execute(
+                    "INSERT INTO knowledge (category, subject, content) VALUES (?1, ?2, ?3)",
+                    rusqlite::params![category, subject, content],
+                )
+                .unwrap();
+            }
+        }
+
+        let edit = json!({"file_path": file_path, "old_string": "s.len() as u32", "new_string": "0"});
+        let warnings = edit_warnings(&db, "Edit", &edit, &file_path).unwrap();
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].starts_with("- Past bug fix (`parse_header`)"));
+
+        // Outside any symbol the knowledge mentions: nothing
+        let edit = json!({"file_path": file_path, "old_string": "fn other() {}", "new_string": ""});
+        assert!(edit_warnings(&db, "Edit", &edit, &file_path).unwrap().is_empty());
     }
 }
@@ -1,10 +1,13 @@
 use anyhow::Result;
+use serde_json::json;
 
 use crate::db::Db;
 use crate::hooks::{self, HookInput};
 use crate::indexer::conversation;
+use crate::inject::recall;
 
-/// Handle UserPromptSubmit hook: index the user's prompt.
+/// Handle UserPromptSubmit hook: recall memory relevant to the prompt, then
+/// index it.
 pub fn handle(input: &HookInput) -> Result<()> {
     let project_dir = hooks::project_dir(input);
     let session_id = hooks::session_id(input);
@@ -20,15 +23,54 @@ pub fn handle(input: &HookInput) -> Result<()> {
     let detail: String = content.chars().take(100).collect();
     hooks::log_hook(&db, input, "UserPromptSubmit", &detail);
 
-    conversation::index_turn(
-        &db,
-        &session_id,
-        "user",
-        "request",
-        content,
-        None,
-        &[],
-    )?;
+    // Recall before indexing, so the prompt doesn't match itself
+    let recalled = if is_recall_disabled(&project_dir) || input.prompt.is_none() {
+        None
+    } else {
+        match recall::build_recall_context(&db, &session_id, content) {
+            Ok(context) => context,
+            Err(e) => {
+                eprintln!("[claude-rlm] Recall failed: {}", e);
+                None
+            }
+        }
+    };
+
+    index_prompt(&db, &session_id, content)?;
+
+    if let Some(context) = recalled {
+        hooks::log_hook(
+            &db,
+            input,
+            "UserPromptSubmit",
+            &format!("recall ({} bytes)", context.len()),
+        );
+        let output = json!({
+            "hookSpecificOutput": {
+                "hookEventName": "UserPromptSubmit",
+                "additionalContext": context
+            }
+        });
+        println!("{}", serde_json::to_string(&output)?);
+    }
 
     Ok(())
 }
+
+/// Index a user prompt as a `request` turn.
+pub fn index_prompt(db: &Db, session_id: &str, prompt: &str) -> Result<i64> {
+    conversation::index_turn(db, session_id, "user", "request", prompt, None, &[])
+}
+
+/// Check if proactive recall is disabled via env var or config. The
+/// project's `.claude/claude-rlm.toml` takes priority over the global config.
+fn is_recall_disabled(project_dir: &str) -> bool {
+    if std::env::var("CLAUDE_RLM_NO_RECALL").ok().as_deref() == Some("1") {
+        return true;
+    }
+
+    // Check [recall] section in config TOML
+    crate::llm::config_value(project_dir, "recall", "enabled")
+        .and_then(|v| v.as_bool())
+        .is_some_and(|enabled| !enabled)
+}
@@ -3,12 +3,16 @@ use serde_json::json;
 
 use crate::db::Db;
 use crate::hooks::{self, HookInput};
-use crate::indexer::{code, conversation, files, git, plans};
+use crate::indexer::{code, conversation, files, git, plans, subagents, transcript};
 use crate::inject;
 
 /// Handle SessionStart hook.
 /// - source="startup": inject project memory (recent sessions, knowledge)
 /// - source="compact": inject context relevant to current task
+/// - source="resume": reconstruct the resumed session's context, as after
+///   compaction
+/// - source="clear": inject light context that leaves out the conversation
+///   just cleared
 pub fn handle_start(input: &HookInput) -> Result<()> {
     let project_dir = hooks::project_dir(input);
     let session_id = hooks::session_id(input);
@@ -19,8 +23,23 @@ pub fn handle_start(input: &HookInput) -> Result<()> {
 
     hooks::log_hook(&db, input, "SessionStart", &format!("source: {}", source));
 
+    // Link resumed, forked and post-clear sessions to the session they continue
+    let parent = match source {
+        "resume" | "clear" => find_parent(&db, input, &session_id, source)?,
+        _ => None,
+    };
+    if source != "compact" {
+        conversation::record_start(&db, &session_id, source, parent.as_deref())?;
+        if let Some(branch) = git::current_branch(std::path::Path::new(&project_dir)) {
+            conversation::record_branch(&db, &session_id, &branch)?;
+        }
+    }
+    if source == "resume" {
+        conversation::reopen_session(&db, &session_id)?;
+    }
+
     // Catch up on git changes since last session
-    if source == "startup" {
+    if source == "startup" || source == "resume" {
         match git::catchup(&db, std::path::Path::new(&project_dir), &session_id) {
             Ok(stats) if stats.commits > 0 => {
                 eprintln!(
@@ -57,7 +76,18 @@ pub fn handle_start(input: &HookInput) -> Result<()> {
 
     let context = match source {
         "compact" => inject::build_compact_context(&db, &session_id)?,
-        _ => inject::build_startup_context(&db)?,
+        "resume" => {
+            // A forked session has no turns of its own yet: its history is
+            // the parent's
+            let own_turns = conversation::session_turn_count(&db, &session_id)?;
+            let resumed = match &parent {
+                Some(p) if own_turns == 0 => p.as_str(),
+                _ => session_id.as_str(),
+            };
+            inject::build_compact_context(&db, resumed)?
+        }
+        "clear" => inject::build_clear_context(&db, &session_id, parent.as_deref())?,
+        _ => inject::build_startup_context(&db, &session_id)?,
     };
 
     // Print startup banner with quick stats
@@ -106,6 +136,27 @@ pub fn handle_start(input: &HookInput) -> Result<()> {
     Ok(())
 }
 
+/// Find the session a resumed or cleared session continues: the session id
+/// its transcript starts with, else (after a clear, or a resume without a
+/// transcript) the most recently active other session that isn't live in
+/// another window.
+fn find_parent(db: &Db, input: &HookInput, session_id: &str, source: &str) -> Result<Option<String>> {
+    // A fresh session's transcript may not exist yet
+    if let Some(path) = input.transcript_path.as_deref() {
+        if let Ok(Some(parent)) = transcript::parent_session_id(path, session_id) {
+            return Ok(Some(parent));
+        }
+    }
+
+    // Resuming a session that already has turns continues it in place
+    let resumed_in_place =
+        source == "resume" && conversation::session_turn_count(db, session_id)? > 0;
+    if resumed_in_place {
+        return Ok(None);
+    }
+    conversation::previous_session(db, session_id)
+}
+
 /// Handle SessionEnd hook: signal the MCP server to exit, then queue
 /// deferred work. Returns instantly so Claude Code can proceed with shutdown.
 /// The MCP server picks up the shutdown signal within ~300ms and exits
@@ -118,6 +169,20 @@ pub fn handle_end(input: &HookInput) -> Result<()> {
 
     hooks::log_hook(&db, input, "SessionEnd", "");
 
+    // Final pass over the transcript: replies since the last Stop hook, and
+    // tool calls whose hooks were missed (timeouts, hooks disabled)
+    if let Some(path) = input.transcript_path.as_deref() {
+        conversation::ensure_session(&db, &session_id, &project_dir)?;
+        if let Err(e) = transcript::index_new_replies(&db, &session_id, path) {
+            eprintln!("[claude-rlm] Transcript indexing failed: {}", e);
+        }
+        match crate::import::reconcile_session(&db, &session_id, path) {
+            Ok(n) if n > 0 => eprintln!("[claude-rlm] Reconciled {} missed tool calls", n),
+            Err(e) => eprintln!("[claude-rlm] Transcript reconciliation failed: {}", e),
+            _ => {}
+        }
+    }
+
     // Signal the MCP server to exit gracefully via the task queue.
     // The server polls every 300ms, so it should exit before Claude Code
     // resorts to TerminateProcess.
@@ -134,9 +199,59 @@ pub fn handle_end(input: &HookInput) -> Result<()> {
     // Queue distillation for the next session's MCP server
     crate::db::tasks::enqueue_task(&db, "distill_session", &project_dir, Some(&session_id))?;
 
-    // Mark session as ended
+    // Mark session as ended, with any interrupted subagent runs
+    subagents::close_open_runs(&db, &session_id)?;
     conversation::end_session(&db, &session_id, None)?;
 
     Ok(())
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn start_input(session_id: &str, transcript_path: Option<&str>) -> HookInput {
+        serde_json::from_value(json!({
+            "session_id": session_id,
+            "transcript_path": transcript_path,
+        }))
+        .unwrap()
+    }
+
+    fn add_turn(db: &Db, session_id: &str) {
+        conversation::ensure_session(db, session_id, "/p").unwrap();
+        conversation::index_turn(db, session_id, "user", "request", "Fix the lexer", None, &[]).unwrap();
+    }
+
+    #[test]
+    fn test_find_parent() {
+        let db = Db::open_memory().unwrap();
+        let tmp = tempfile::tempdir().unwrap();
+        let line = |sid: &str| json!({"type": "user", "sessionId": sid}).to_string();
+
+        // Forked: the transcript starts with the parent's messages
+        let forked = tmp.path().join("forked.jsonl");
+        std::fs::write(&forked, format!("{}\n{}\n", line("old"), line("fork"))).unwrap();
+        let input = start_input("fork", Some(forked.to_str().unwrap()));
+        assert_eq!(find_parent(&db, &input, "fork", "resume").unwrap().as_deref(), Some("old"));
+
+        // Resumed in place: the session's own turns, no parent
+        add_turn(&db, "old");
+        let own = tmp.path().join("own.jsonl");
+        std::fs::write(&own, format!("{}\n", line("old"))).unwrap();
+        let input = start_input("old", Some(own.to_str().unwrap()));
+        assert_eq!(find_parent(&db, &input, "old", "resume").unwrap(), None);
+
+        // Cleared: the latest ended session, not one still live in another
+        // window even though it was active more recently
+        conversation::end_session(&db, "old", None).unwrap();
+        add_turn(&db, "other-window");
+        let input = start_input("new", None);
+        assert_eq!(find_parent(&db, &input, "new", "clear").unwrap().as_deref(), Some("old"));
+
+        // Only a live session to go by: no parent rather than the wrong one
+        let db = Db::open_memory().unwrap();
+        add_turn(&db, "other-window");
+        assert_eq!(find_parent(&db, &input, "new", "clear").unwrap(), None);
+    }
+}
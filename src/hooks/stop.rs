@@ -0,0 +1,28 @@
+use anyhow::Result;
+
+use crate::db::Db;
+use crate::hooks::{self, HookInput};
+use crate::indexer::{conversation, subagents, transcript};
+
+/// Handle Stop hook: index the assistant's replies from this response.
+///
+/// Hooks only see prompts and tool calls, so the assistant's own reasoning
+/// is read from the transcript, picking up where the previous Stop left off.
+pub fn handle(input: &HookInput) -> Result<()> {
+    let project_dir = hooks::project_dir(input);
+    let session_id = hooks::session_id(input);
+
+    let db = Db::open(std::path::Path::new(&project_dir))?;
+    conversation::ensure_session(&db, &session_id, &project_dir)?;
+
+    // The response is over, so any subagent run still open was interrupted
+    subagents::close_open_runs(&db, &session_id)?;
+
+    let Some(transcript_path) = input.transcript_path.as_deref() else {
+        return Ok(());
+    };
+    let indexed = transcript::index_new_replies(&db, &session_id, transcript_path)?;
+    hooks::log_hook(&db, input, "Stop", &format!("replies: {}", indexed));
+
+    Ok(())
+}
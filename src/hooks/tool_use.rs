@@ -3,15 +3,14 @@ use serde_json::Value;
 
 use crate::db::Db;
 use crate::hooks::{self, HookInput};
-use crate::indexer::{code, conversation, plans};
+use crate::indexer::{code, conversation, plans, subagents, todos};
 
-/// Handle PostToolUse for Edit/Write tools.
+/// Handle PostToolUse for Edit/Write/MultiEdit tools.
 pub fn handle_edit(input: &HookInput) -> Result<()> {
     let project_dir = hooks::project_dir(input);
-    let session_id = hooks::session_id(input);
 
     let db = Db::open(std::path::Path::new(&project_dir))?;
-    conversation::ensure_session(&db, &session_id, &project_dir)?;
+    let session_id = hooks::tool_session(&db, input, &project_dir)?;
 
     let tool_name = input.tool_name.as_deref().unwrap_or("unknown");
     let tool_input = &input.tool_input;
@@ -25,28 +24,15 @@ pub fn handle_edit(input: &HookInput) -> Result<()> {
 
     hooks::log_hook(&db, input, "PostToolUse", &format!("edit: {}", file_path));
 
-    // Build a concise content description
-    let content = if let Some(ti) = tool_input {
-        format_edit_content(tool_name, ti)
-    } else {
-        format!("{tool_name}: {file_path}")
-    };
+    let turn_id = index_edit(&db, &session_id, tool_name, tool_input.as_ref())?;
 
-    let action = match tool_name {
-        "Write" => "write",
-        "Edit" => "edit",
-        _ => "edit",
-    };
-
-    conversation::index_turn(
-        &db,
-        &session_id,
-        "assistant",
-        "code_edit",
-        &content,
-        tool_input.as_ref(),
-        &[(file_path.to_string(), action.to_string())],
-    )?;
+    // Record which lines the edit touched, for region-level history
+    if let Some(ti) = tool_input {
+        let regions = edited_regions(tool_name, ti, file_path);
+        if let Err(e) = conversation::record_edit_regions(&db, turn_id, file_path, &regions) {
+            tracing::warn!("Failed to record edit regions for {}: {}", file_path, e);
+        }
+    }
 
     // Re-index the changed file for tree-sitter symbols
     let path = std::path::Path::new(file_path);
@@ -84,13 +70,48 @@ pub fn handle_edit(input: &HookInput) -> Result<()> {
     Ok(())
 }
 
+/// Index an Edit/Write/MultiEdit tool call as a `code_edit` turn.
+pub fn index_edit(
+    db: &Db,
+    session_id: &str,
+    tool_name: &str,
+    tool_input: Option<&Value>,
+) -> Result<i64> {
+    let file_path = tool_input
+        .and_then(|v| v.get("file_path"))
+        .and_then(|v| v.as_str())
+        .unwrap_or("unknown");
+
+    // Build a concise content description
+    let content = if let Some(ti) = tool_input {
+        format_edit_content(tool_name, ti)
+    } else {
+        format!("{tool_name}: {file_path}")
+    };
+
+    let action = match tool_name {
+        "Write" => "write",
+        "Edit" => "edit",
+        _ => "edit",
+    };
+
+    conversation::index_turn(
+        db,
+        session_id,
+        "assistant",
+        "code_edit",
+        &content,
+        tool_input,
+        &[(file_path.to_string(), action.to_string())],
+    )
+}
+
 /// Handle PostToolUse for Read tool.
 pub fn handle_read(input: &HookInput) -> Result<()> {
     let project_dir = hooks::project_dir(input);
-    let session_id = hooks::session_id(input);
 
     let db = Db::open(std::path::Path::new(&project_dir))?;
-    conversation::ensure_session(&db, &session_id, &project_dir)?;
+    let session_id = hooks::tool_session(&db, input, &project_dir)?;
 
     let file_path = input
         .tool_input
@@ -101,28 +122,32 @@ pub fn handle_read(input: &HookInput) -> Result<()> {
 
     hooks::log_hook(&db, input, "PostToolUse", &format!("read: {}", file_path));
 
+    index_read(&db, &session_id, file_path)?;
+
+    Ok(())
+}
+
+/// Index a Read tool call as a `file_read` turn.
+pub fn index_read(db: &Db, session_id: &str, file_path: &str) -> Result<i64> {
     let content = format!("Read file: {file_path}");
 
     conversation::index_turn(
-        &db,
-        &session_id,
+        db,
+        session_id,
         "assistant",
         "file_read",
         &content,
         None,
         &[(file_path.to_string(), "read".to_string())],
-    )?;
-
-    Ok(())
+    )
 }
 
 /// Handle PostToolUse for Bash tool.
 pub fn handle_bash(input: &HookInput) -> Result<()> {
     let project_dir = hooks::project_dir(input);
-    let session_id = hooks::session_id(input);
 
     let db = Db::open(std::path::Path::new(&project_dir))?;
-    conversation::ensure_session(&db, &session_id, &project_dir)?;
+    let session_id = hooks::tool_session(&db, input, &project_dir)?;
 
     let command = input
         .tool_input
@@ -132,43 +157,540 @@ pub fn handle_bash(input: &HookInput) -> Result<()> {
         .unwrap_or("[unknown command]");
 
     let cmd_detail: String = command.chars().take(100).collect();
-    hooks::log_hook(&db, input, "PostToolUse", &format!("bash: {}", cmd_detail));
+    // PostToolUseFailure carries the failure message in `error`
+    let error = input.extra.get("error").and_then(|v| v.as_str());
+    let event = if error.is_some() { "PostToolUseFailure" } else { "PostToolUse" };
+    hooks::log_hook(&db, input, event, &format!("bash: {}", cmd_detail));
+
+    let mut outcome = BashOutcome::from_response(input.tool_response.as_ref(), error);
+    if outcome.duration_ms.is_none() {
+        outcome.duration_ms = input.extra.get("duration_ms").and_then(|v| v.as_i64());
+    }
+    index_bash(&db, &session_id, command, &outcome)?;
 
-    // Extract output: tool_response can be a string or {"stdout": "...", ...}
-    let output = input
-        .tool_response
-        .as_ref()
-        .and_then(|v| {
-            v.as_str()
-                .map(|s| s.to_string())
-                .or_else(|| v.get("stdout").and_then(|s| s.as_str()).map(|s| s.to_string()))
-        })
-        .unwrap_or_default();
-    let output = output.as_str();
+    Ok(())
+}
+
+/// What a Bash call produced, parsed from its `tool_response` (or the
+/// failure message when the call errored).
+#[derive(Debug, Default)]
+pub struct BashOutcome {
+    pub stdout: String,
+    pub stderr: String,
+    pub exit_code: Option<i64>,
+    pub interrupted: bool,
+    pub duration_ms: Option<i64>,
+}
+
+impl BashOutcome {
+    /// Parse a Bash `tool_response`: a plain string, or an object with
+    /// `stdout`/`stderr`/`interrupted` and, in some versions, an exit code.
+    /// `error` is the failure message of a failed call ("Exit code 1\n...").
+    pub fn from_response(tool_response: Option<&Value>, error: Option<&str>) -> Self {
+        let mut outcome = BashOutcome::default();
+        let field = |v: &Value, keys: &[&str]| keys.iter().find_map(|k| v.get(*k).cloned());
+
+        if let Some(v) = tool_response {
+            match v {
+                Value::String(s) => outcome.stdout = s.clone(),
+                _ => {
+                    let text = |keys: &[&str]| {
+                        field(v, keys)
+                            .and_then(|s| s.as_str().map(|s| s.to_string()))
+                            .unwrap_or_default()
+                    };
+                    outcome.stdout = text(&["stdout"]);
+                    outcome.stderr = text(&["stderr"]);
+                    outcome.interrupted = field(v, &["interrupted"])
+                        .and_then(|b| b.as_bool())
+                        .unwrap_or(false);
+                    outcome.exit_code = field(v, &["exit_code", "exitCode", "returnCode", "code"])
+                        .and_then(|c| c.as_i64());
+                    outcome.duration_ms = field(v, &["duration_ms", "durationMs"])
+                        .and_then(|d| d.as_i64());
+                }
+            }
+        }
+
+        if let Some(error) = error {
+            if outcome.stderr.is_empty() {
+                outcome.stderr = error.to_string();
+            }
+            outcome.exit_code = outcome
+                .exit_code
+                .or_else(|| parse_exit_code(error))
+                .or(Some(1));
+        }
+        outcome
+    }
+
+    pub fn failed(&self) -> bool {
+        self.interrupted || self.exit_code.is_some_and(|c| c != 0)
+    }
+}
+
+/// Extract N from "Exit code N" in a failure message.
+fn parse_exit_code(message: &str) -> Option<i64> {
+    let rest = &message[message.find("Exit code ")? + "Exit code ".len()..];
+    let sign = if rest.starts_with('-') { "-" } else { "" };
+    let digits: String = rest[sign.len()..].chars().take_while(|c| c.is_ascii_digit()).collect();
+    format!("{sign}{digits}").parse().ok()
+}
 
+/// Last `max` bytes of `s`, starting on a line boundary where possible.
+fn tail(s: &str, max: usize) -> &str {
+    if s.len() <= max {
+        return s;
+    }
+    let mut start = s.len() - max;
+    while !s.is_char_boundary(start) {
+        start += 1;
+    }
+    match s[start..].find('\n') {
+        Some(nl) if nl + 1 < s.len() - start => &s[start + nl + 1..],
+        _ => &s[start..],
+    }
+}
+
+/// Index a Bash tool call and its output as a `bash_cmd` turn. A failed or
+/// interrupted call also gets an `error` turn linked to the command turn.
+/// Returns the ids of the turns inserted, command turn first.
+pub fn index_bash(
+    db: &Db,
+    session_id: &str,
+    command: &str,
+    outcome: &BashOutcome,
+) -> Result<Vec<i64>> {
+    let output = outcome.stdout.as_str();
     let truncated_output = if output.len() > 2000 {
         &output[..output.floor_char_boundary(2000)]
     } else {
         output
     };
 
-    let content = format!("$ {command}\n{truncated_output}");
+    let mut content = format!("$ {command}\n{truncated_output}");
+    let stderr = tail(outcome.stderr.trim_end(), 1000);
+    if !stderr.is_empty() {
+        content.push_str(&format!("\n[stderr]\n{stderr}"));
+    }
+    if outcome.interrupted {
+        content.push_str("\n[interrupted]");
+    } else if let Some(code) = outcome.exit_code.filter(|c| *c != 0) {
+        content.push_str(&format!("\n[exit code {code}]"));
+    }
 
-    conversation::index_turn(
-        &db,
-        &session_id,
+    let metadata = serde_json::json!({
+        "exit_code": outcome.exit_code,
+        "interrupted": outcome.interrupted,
+        "duration_ms": outcome.duration_ms,
+    });
+
+    let turn_id = conversation::index_turn(
+        db,
+        session_id,
         "assistant",
         "bash_cmd",
         &content,
-        None,
+        Some(&metadata),
         &[],
     )?;
 
+    if outcome.failed() {
+        let first_line = command.lines().next().unwrap_or(command);
+        let header = if outcome.interrupted {
+            format!("Command interrupted: {first_line}")
+        } else {
+            format!(
+                "Command failed (exit {}): {first_line}",
+                outcome.exit_code.unwrap_or(1)
+            )
+        };
+        let detail = if stderr.is_empty() { tail(output.trim_end(), 1000) } else { stderr };
+        let error_metadata = serde_json::json!({
+            "command_turn_id": turn_id,
+            "exit_code": outcome.exit_code,
+            "interrupted": outcome.interrupted,
+        });
+        let error_id = conversation::index_turn(
+            db,
+            session_id,
+            "assistant",
+            "error",
+            &format!("{header}\n{detail}"),
+            Some(&error_metadata),
+            &[],
+        )?;
+        return Ok(vec![turn_id, error_id]);
+    }
+
+    Ok(vec![turn_id])
+}
+
+/// Handle PostToolUse for Grep/Glob/WebSearch tools.
+pub fn handle_search(input: &HookInput) -> Result<()> {
+    let project_dir = hooks::project_dir(input);
+
+    let db = Db::open(std::path::Path::new(&project_dir))?;
+    let session_id = hooks::tool_session(&db, input, &project_dir)?;
+
+    let tool_name = input.tool_name.as_deref().unwrap_or("unknown");
+    let null = Value::Null;
+    let tool_input = input.tool_input.as_ref().unwrap_or(&null);
+    let query = search_query(tool_name, tool_input);
+    hooks::log_hook(&db, input, "PostToolUse", &format!("search: {}", truncate_str(&query, 100)));
+
+    index_search(&db, &session_id, tool_name, tool_input, input.tool_response.as_ref())?;
+
+    Ok(())
+}
+
+/// The pattern or query a search tool ran.
+fn search_query(tool_name: &str, tool_input: &Value) -> String {
+    let key = if tool_name == "WebSearch" { "query" } else { "pattern" };
+    tool_input
+        .get(key)
+        .and_then(|v| v.as_str())
+        .unwrap_or("")
+        .to_string()
+}
+
+/// First line of a `search` turn: the tool, query and any path/filter.
+pub fn format_search_header(tool_name: &str, tool_input: &Value) -> String {
+    let query = search_query(tool_name, tool_input);
+    let input_str = |k: &str| tool_input.get(k).and_then(|v| v.as_str());
+
+    let mut header = format!("{tool_name} `{query}`");
+    if let Some(path) = input_str("path") {
+        header.push_str(&format!(" in {path}"));
+    }
+    if let Some(filter) = input_str("glob").or_else(|| input_str("type")) {
+        header.push_str(&format!(" ({filter})"));
+    }
+    header
+}
+
+/// Maximum matched files referenced by a search turn.
+const MAX_SEARCH_FILES: usize = 10;
+
+/// Index a Grep/Glob/WebSearch tool call as a `search` turn. Code searches
+/// reference the files they matched.
+pub fn index_search(
+    db: &Db,
+    session_id: &str,
+    tool_name: &str,
+    tool_input: &Value,
+    tool_response: Option<&Value>,
+) -> Result<i64> {
+    let mut content = format_search_header(tool_name, tool_input);
+
+    let filenames: Vec<String> = tool_response
+        .and_then(|r| r.get("filenames"))
+        .and_then(|f| f.as_array())
+        .map(|a| a.iter().filter_map(|f| f.as_str().map(|s| s.to_string())).collect())
+        .unwrap_or_default();
+
+    if tool_name == "WebSearch" {
+        // Results are link lists; keep titles/URLs where the response has them
+        let links: Vec<String> = tool_response
+            .and_then(|r| r.get("results"))
+            .and_then(|r| r.as_array())
+            .into_iter()
+            .flatten()
+            .flat_map(|r| r.get("content").and_then(|c| c.as_array()).cloned().unwrap_or_default())
+            .filter_map(|l| {
+                let title = l.get("title")?.as_str()?;
+                let url = l.get("url")?.as_str()?;
+                Some(format!("{title} <{url}>"))
+            })
+            .take(5)
+            .collect();
+        for link in links {
+            content.push_str(&format!("\n- {link}"));
+        }
+    } else if let Some(output) = tool_response.and_then(|r| r.get("content")).and_then(|c| c.as_str()) {
+        // Grep content mode: matching lines
+        content.push_str(&format!("\n{}", truncate_str(output, 1000)));
+    } else if !filenames.is_empty() {
+        content.push_str(&format!("\n{} files:", filenames.len()));
+        for f in filenames.iter().take(MAX_SEARCH_FILES) {
+            content.push_str(&format!("\n- {f}"));
+        }
+    }
+
+    let files: Vec<(String, String)> = filenames
+        .into_iter()
+        .take(MAX_SEARCH_FILES)
+        .map(|f| (f, "search".to_string()))
+        .collect();
+
+    conversation::index_turn(db, session_id, "assistant", "search", &content, None, &files)
+}
+
+/// Handle PostToolUse for WebFetch.
+pub fn handle_web_fetch(input: &HookInput) -> Result<()> {
+    let project_dir = hooks::project_dir(input);
+
+    let db = Db::open(std::path::Path::new(&project_dir))?;
+    let session_id = hooks::tool_session(&db, input, &project_dir)?;
+
+    let null = Value::Null;
+    let tool_input = input.tool_input.as_ref().unwrap_or(&null);
+    let url = tool_input.get("url").and_then(|v| v.as_str()).unwrap_or("unknown");
+    hooks::log_hook(&db, input, "PostToolUse", &format!("fetch: {}", url));
+
+    index_web_fetch(&db, &session_id, tool_input, input.tool_response.as_ref())?;
+
+    Ok(())
+}
+
+/// Index a WebFetch tool call as a `web_fetch` turn: the URL, what was asked
+/// of it, and the start of the answer.
+pub fn index_web_fetch(
+    db: &Db,
+    session_id: &str,
+    tool_input: &Value,
+    tool_response: Option<&Value>,
+) -> Result<i64> {
+    let input_str = |k: &str| tool_input.get(k).and_then(|v| v.as_str()).unwrap_or("");
+    let mut content = format!("Fetched {}", input_str("url"));
+    let prompt = input_str("prompt");
+    if !prompt.is_empty() {
+        content.push_str(&format!("\nPrompt: {}", truncate_str(prompt, 300)));
+    }
+
+    let result = tool_response.and_then(|r| {
+        r.as_str()
+            .or_else(|| r.get("result").and_then(|s| s.as_str()))
+    });
+    if let Some(result) = result {
+        content.push_str(&format!("\n{}", truncate_str(result, 1500)));
+    }
+
+    conversation::index_turn(db, session_id, "assistant", "web_fetch", &content, None, &[])
+}
+
+/// Handle PostToolUse for Task: close the subagent run and index its final
+/// report as a summary turn on this session.
+pub fn handle_task(input: &HookInput) -> Result<()> {
+    let project_dir = hooks::project_dir(input);
+    let session_id = hooks::session_id(input);
+
+    let db = Db::open(std::path::Path::new(&project_dir))?;
+    conversation::ensure_session(&db, &session_id, &project_dir)?;
+
+    let null = Value::Null;
+    let tool_input = input.tool_input.as_ref().unwrap_or(&null);
+    let input_str = |k: &str| tool_input.get(k).and_then(|v| v.as_str()).unwrap_or("");
+    let agent_type = match input_str("subagent_type") {
+        "" => "unknown",
+        t => t,
+    };
+    // PostToolUseFailure (interrupted or failed Task) carries `error`
+    // instead of a report; the run is closed either way
+    let error = input.extra.get("error").and_then(|v| v.as_str());
+    let event = if error.is_some() { "PostToolUseFailure" } else { "PostToolUse" };
+    hooks::log_hook(&db, input, event, &format!("task: {}", agent_type));
+
+    let report = match error {
+        Some(error) => format!("Failed: {}", error),
+        None => input
+            .tool_response
+            .as_ref()
+            .map(task_report)
+            .unwrap_or_default(),
+    };
+    let key = input.extra.get("tool_use_id").and_then(|v| v.as_str());
+    subagents::finish(
+        &db,
+        &session_id,
+        key,
+        agent_type,
+        input_str("description"),
+        &truncate_str(&report, 3000),
+    )?;
+
+    Ok(())
+}
+
+/// The text of a subagent's final report: a plain string, or the text
+/// blocks of a `content` array.
+fn task_report(response: &Value) -> String {
+    if let Some(s) = response.as_str() {
+        return s.to_string();
+    }
+    let blocks = response
+        .get("content")
+        .and_then(|c| c.as_array())
+        .or_else(|| response.as_array());
+    blocks
+        .map(|blocks| {
+            blocks
+                .iter()
+                .filter_map(|b| b.get("text").and_then(|t| t.as_str()))
+                .collect::<Vec<_>>()
+                .join("\n")
+        })
+        .unwrap_or_default()
+}
+
+/// Handle PostToolUse for NotebookEdit.
+pub fn handle_notebook_edit(input: &HookInput) -> Result<()> {
+    let project_dir = hooks::project_dir(input);
+
+    let db = Db::open(std::path::Path::new(&project_dir))?;
+    let session_id = hooks::tool_session(&db, input, &project_dir)?;
+
+    let null = Value::Null;
+    let tool_input = input.tool_input.as_ref().unwrap_or(&null);
+    let path = tool_input
+        .get("notebook_path")
+        .and_then(|v| v.as_str())
+        .unwrap_or("unknown");
+    hooks::log_hook(&db, input, "PostToolUse", &format!("notebook: {}", path));
+
+    index_notebook_edit(&db, &session_id, tool_input)?;
+
+    if let Err(e) = plans::record_progress(&db, &session_id, path) {
+        tracing::warn!("Failed to record plan progress: {}", e);
+    }
+
     Ok(())
 }
 
+/// Index a NotebookEdit tool call as a `notebook_edit` turn.
+pub fn index_notebook_edit(db: &Db, session_id: &str, tool_input: &Value) -> Result<i64> {
+    let path = tool_input
+        .get("notebook_path")
+        .and_then(|v| v.as_str())
+        .unwrap_or("unknown");
+
+    conversation::index_turn(
+        db,
+        session_id,
+        "assistant",
+        "notebook_edit",
+        &format_notebook_content(tool_input),
+        None,
+        &[(path.to_string(), "edit".to_string())],
+    )
+}
+
+/// Format notebook edit content: the cell edited and its new source.
+pub fn format_notebook_content(tool_input: &Value) -> String {
+    let input_str = |k: &str| tool_input.get(k).and_then(|v| v.as_str());
+    let path = input_str("notebook_path").unwrap_or("unknown");
+    let mode = input_str("edit_mode").unwrap_or("replace");
+
+    let mut content = format!("NotebookEdit {path} ({mode}");
+    if let Some(cell) = input_str("cell_id") {
+        content.push_str(&format!(", cell {cell}"));
+    }
+    if let Some(kind) = input_str("cell_type") {
+        content.push_str(&format!(", {kind}"));
+    }
+    content.push_str("):");
+    if let Some(source) = input_str("new_source") {
+        content.push_str(&format!("\n{}", truncate_str(source, 500)));
+    }
+    content
+}
+
+/// Handle PostToolUse for TodoWrite: store the new version of the list.
+pub fn handle_todo_write(input: &HookInput) -> Result<()> {
+    let project_dir = hooks::project_dir(input);
+
+    let db = Db::open(std::path::Path::new(&project_dir))?;
+    let session_id = hooks::tool_session(&db, input, &project_dir)?;
+
+    let items = input
+        .tool_input
+        .as_ref()
+        .map(todos::parse_todos)
+        .unwrap_or_default();
+    let version = todos::record_list(&db, &session_id, &items)?;
+
+    let detail = match version {
+        Some(v) => format!("todos: v{} ({} items)", v, items.len()),
+        None => "todos: unchanged".to_string(),
+    };
+    hooks::log_hook(&db, input, "PostToolUse", &detail);
+
+    Ok(())
+}
+
+/// Compute the post-edit line ranges touched by an Edit/Write/MultiEdit.
+///
+/// Write covers the whole file. Edit (and each MultiEdit edit) locates `new_string` in the file on disk
+/// (already written by Claude Code); pure deletions can't be located and
+/// yield no regions.
+fn edited_regions(tool_name: &str, tool_input: &Value, file_path: &str) -> Vec<(i64, i64)> {
+    match tool_name {
+        "Write" => {
+            let content = tool_input
+                .get("content")
+                .and_then(|v| v.as_str())
+                .unwrap_or("");
+            let lines = content.lines().count().max(1) as i64;
+            vec![(1, lines)]
+        }
+        "Edit" => {
+            let new = tool_input
+                .get("new_string")
+                .and_then(|v| v.as_str())
+                .unwrap_or("");
+            let replace_all = tool_input
+                .get("replace_all")
+                .and_then(|v| v.as_bool())
+                .unwrap_or(false);
+            match std::fs::read_to_string(file_path) {
+                Ok(source) => line_ranges_of(&source, new, if replace_all { 20 } else { 1 }),
+                Err(_) => Vec::new(),
+            }
+        }
+        "MultiEdit" => {
+            let Ok(source) = std::fs::read_to_string(file_path) else {
+                return Vec::new();
+            };
+            let edits = tool_input
+                .get("edits")
+                .and_then(|v| v.as_array())
+                .map(|a| a.as_slice())
+                .unwrap_or_default();
+            edits
+                .iter()
+                .flat_map(|edit| {
+                    let new = edit.get("new_string").and_then(|v| v.as_str()).unwrap_or("");
+                    let replace_all = edit
+                        .get("replace_all")
+                        .and_then(|v| v.as_bool())
+                        .unwrap_or(false);
+                    line_ranges_of(&source, new, if replace_all { 20 } else { 1 })
+                })
+                .collect()
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// Find up to `max` occurrences of `needle` in `haystack`, as 1-indexed
+/// inclusive line ranges.
+pub fn line_ranges_of(haystack: &str, needle: &str, max: usize) -> Vec<(i64, i64)> {
+    if needle.is_empty() {
+        return Vec::new();
+    }
+    let span = needle.trim_end_matches('\n').matches('\n').count() as i64;
+    haystack
+        .match_indices(needle)
+        .take(max)
+        .map(|(offset, _)| {
+            let start = haystack[..offset].matches('\n').count() as i64 + 1;
+            (start, start + span)
+        })
+        .collect()
+}
+
 /// Format edit content concisely.
-fn format_edit_content(tool_name: &str, tool_input: &Value) -> String {
+pub fn format_edit_content(tool_name: &str, tool_input: &Value) -> String {
     let file_path = tool_input
         .get("file_path")
         .and_then(|v| v.as_str())
@@ -199,6 +721,23 @@ fn format_edit_content(tool_name: &str, tool_input: &Value) -> String {
             let preview = truncate_str(content, 500);
             format!("Write {file_path}: {preview}")
         }
+        "MultiEdit" => {
+            let edits = tool_input
+                .get("edits")
+                .and_then(|v| v.as_array())
+                .map(|a| a.as_slice())
+                .unwrap_or_default();
+            let mut content = format!("MultiEdit {file_path} ({} edits):", edits.len());
+            for edit in edits.iter().take(5) {
+                let field = |k: &str| edit.get(k).and_then(|v| v.as_str()).unwrap_or("");
+                content.push_str(&format!(
+                    "\n- {}\n+ {}",
+                    truncate_str(field("old_string"), 200),
+                    truncate_str(field("new_string"), 200)
+                ));
+            }
+            content
+        }
         _ => format!("{tool_name} {file_path}"),
     }
 }
@@ -211,3 +750,189 @@ fn truncate_str(s: &str, max: usize) -> String {
         format!("{}...[truncated]", &s[..end])
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// A turn's type, content and referenced files (with their actions).
+    fn turn(db: &Db, turn_id: i64) -> (String, String, Vec<(String, String)>) {
+        let conn = db.conn();
+        let (turn_type, content) = conn
+            .query_row(
+                "SELECT turn_type, content FROM turns WHERE id = ?1",
+                [turn_id],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        let mut stmt = conn
+            .prepare("SELECT file_path, action FROM turn_files WHERE turn_id = ?1 ORDER BY file_path")
+            .unwrap();
+        let files = stmt
+            .query_map([turn_id], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        (turn_type, content, files)
+    }
+
+    fn db() -> Db {
+        let db = Db::open_memory().unwrap();
+        conversation::ensure_session(&db, "s1", "/nonexistent").unwrap();
+        db
+    }
+
+    #[test]
+    fn test_bash_outcome_from_response() {
+        // Older versions report a plain string
+        let outcome = BashOutcome::from_response(Some(&json!("Compiling foo\nFinished")), None);
+        assert_eq!(outcome.stdout, "Compiling foo\nFinished");
+        assert_eq!(outcome.exit_code, None);
+        assert!(!outcome.failed());
+
+        let response = json!({"stdout": "running 3 tests", "stderr": "warning: unused", "interrupted": false});
+        let outcome = BashOutcome::from_response(Some(&response), None);
+        assert_eq!(outcome.stdout, "running 3 tests");
+        assert_eq!(outcome.stderr, "warning: unused");
+        assert!(!outcome.failed());
+
+        let response = json!({"stdout": "", "stderr": "", "interrupted": true});
+        assert!(BashOutcome::from_response(Some(&response), None).failed());
+
+        // PostToolUseFailure: no response, the exit code is in the message
+        let outcome = BashOutcome::from_response(None, Some("Exit code 101\nerror[E0308]: mismatched types"));
+        assert_eq!(outcome.exit_code, Some(101));
+        assert_eq!(outcome.stderr, "Exit code 101\nerror[E0308]: mismatched types");
+        let outcome = BashOutcome::from_response(None, Some("Exit code -1\nkilled"));
+        assert_eq!(outcome.exit_code, Some(-1));
+        assert_eq!(BashOutcome::from_response(None, Some("Command timed out")).exit_code, Some(1));
+    }
+
+    #[test]
+    fn test_index_search() {
+        let db = db();
+
+        // Grep listing files: header plus the files, each referenced
+        let id = index_search(
+            &db,
+            "s1",
+            "Grep",
+            &json!({"pattern": "fn lex", "path": "src", "glob": "*.rs"}),
+            Some(&json!({"mode": "files_with_matches", "filenames": ["src/a.rs", "src/b.rs"]})),
+        )
+        .unwrap();
+        let (turn_type, content, files) = turn(&db, id);
+        assert_eq!(turn_type, "search");
+        assert_eq!(content, "Grep `fn lex` in src (*.rs)\n2 files:\n- src/a.rs\n- src/b.rs");
+        assert_eq!(
+            files,
+            [("src/a.rs".into(), "search".into()), ("src/b.rs".into(), "search".into())]
+        );
+
+        // Grep content mode keeps the matching lines
+        let id = index_search(
+            &db,
+            "s1",
+            "Grep",
+            &json!({"pattern": "TODO"}),
+            Some(&json!({"mode": "content", "content": "src/a.rs:3: // TODO: tabs"})),
+        )
+        .unwrap();
+        let (_, content, files) = turn(&db, id);
+        assert_eq!(content, "Grep `TODO`\nsrc/a.rs:3: // TODO: tabs");
+        assert!(files.is_empty());
+
+        // Glob
+        let id = index_search(
+            &db,
+            "s1",
+            "Glob",
+            &json!({"pattern": "**/*.toml"}),
+            Some(&json!({"filenames": ["Cargo.toml"]})),
+        )
+        .unwrap();
+        let (_, content, files) = turn(&db, id);
+        assert_eq!(content, "Glob `**/*.toml`\n1 files:\n- Cargo.toml");
+        assert_eq!(files, [("Cargo.toml".into(), "search".into())]);
+
+        // WebSearch keeps result titles and links, with no file references
+        let id = index_search(
+            &db,
+            "s1",
+            "WebSearch",
+            &json!({"query": "sqlite fts5 rank"}),
+            Some(&json!({"results": [{"content": [
+                {"title": "FTS5", "url": "https://sqlite.org/fts5.html"}
+            ]}]})),
+        )
+        .unwrap();
+        let (turn_type, content, files) = turn(&db, id);
+        assert_eq!(turn_type, "search");
+        assert_eq!(content, "WebSearch `sqlite fts5 rank`\n- FTS5 <https://sqlite.org/fts5.html>");
+        assert!(files.is_empty());
+    }
+
+    #[test]
+    fn test_index_web_fetch() {
+        let db = db();
+        let input = json!({"url": "https://docs.rs/rusqlite", "prompt": "How do I bind params?"});
+        let id = index_web_fetch(&db, "s1", &input, Some(&json!({"result": "Use params![]."}))).unwrap();
+        let (turn_type, content, files) = turn(&db, id);
+        assert_eq!(turn_type, "web_fetch");
+        assert_eq!(
+            content,
+            "Fetched https://docs.rs/rusqlite\nPrompt: How do I bind params?\nUse params![]."
+        );
+        assert!(files.is_empty());
+
+        // A plain string response
+        let id = index_web_fetch(&db, "s1", &input, Some(&json!("Use params![]."))).unwrap();
+        assert!(turn(&db, id).1.ends_with("\nUse params![]."));
+    }
+
+    #[test]
+    fn test_index_notebook_edit() {
+        let db = db();
+        let input = json!({
+            "notebook_path": "analysis.ipynb",
+            "cell_id": "c3",
+            "cell_type": "code",
+            "edit_mode": "insert",
+            "new_source": "df.describe()"
+        });
+        let id = index_notebook_edit(&db, "s1", &input).unwrap();
+        let (turn_type, content, files) = turn(&db, id);
+        assert_eq!(turn_type, "notebook_edit");
+        assert_eq!(content, "NotebookEdit analysis.ipynb (insert, cell c3, code):\ndf.describe()");
+        assert_eq!(files, [("analysis.ipynb".into(), "edit".into())]);
+    }
+
+    #[test]
+    fn test_multi_edit() {
+        let tmp = tempfile::tempdir().unwrap();
+        let file = tmp.path().join("lexer.rs");
+        let file_path = file.to_string_lossy().to_string();
+        // The file as it is after the edits were applied
+        std::fs::write(&file, "fn lex() {\n    crlf();\n}\n\nfn tabs() {}\nfn tabs() {}\n").unwrap();
+        let input = json!({
+            "file_path": file_path,
+            "edits": [
+                {"old_string": "    todo!();", "new_string": "    crlf();"},
+                {"old_string": "fn spaces() {}", "new_string": "fn tabs() {}", "replace_all": true},
+                {"old_string": "// gone", "new_string": ""}
+            ]
+        });
+
+        let db = db();
+        let id = index_edit(&db, "s1", "MultiEdit", Some(&input)).unwrap();
+        let (turn_type, content, files) = turn(&db, id);
+        assert_eq!(turn_type, "code_edit");
+        assert!(content.starts_with(&format!("MultiEdit {} (3 edits):", file_path)), "{}", content);
+        assert!(content.contains("\n-     todo!();\n+     crlf();"), "{}", content);
+        assert_eq!(files, [(file_path.clone(), "edit".into())]);
+
+        // Each edit is located on its own; the deletion has no region
+        assert_eq!(edited_regions("MultiEdit", &input, &file_path), [(2, 2), (5, 5), (6, 6)]);
+    }
+}
@@ -0,0 +1,482 @@
+//! Backfill project memory from existing Claude Code transcripts.
+//!
+//! Claude Code keeps every session as JSONL under
+//! `~/.claude/projects/<encoded project path>/<session id>.jsonl`. Sessions
+//! from before ClaudeRLM was installed are replayed through the same
+//! indexing functions the hooks use, with the original session ids and
+//! timestamps, then queued for distillation.
+//!
+//! The same replay fills gaps in live sessions: at SessionEnd, tool calls in
+//! the transcript whose hooks timed out or were disabled are indexed and
+//! marked `reconciled`.
+
+use anyhow::{Context, Result};
+use rusqlite::params;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::db::{tasks, Db};
+use crate::hooks::{prompt, tool_use};
+use crate::indexer::{conversation, transcript};
+
+/// Result of importing one transcript file.
+enum Imported {
+    /// Session imported with this many turns
+    Session(usize),
+    /// Session already in the database (imported before, or indexed live)
+    AlreadyIndexed,
+    /// Transcript had nothing to index
+    Empty,
+}
+
+/// Claude Code's transcript directory for a project: every character of the
+/// absolute path that isn't alphanumeric becomes `-`.
+fn transcripts_dir(project_dir: &Path) -> PathBuf {
+    let home = std::env::var("HOME")
+        .or_else(|_| std::env::var("USERPROFILE"))
+        .unwrap_or_else(|_| ".".to_string());
+    let encoded: String = project_dir
+        .to_string_lossy()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect();
+    Path::new(&home).join(".claude").join("projects").join(encoded)
+}
+
+/// Import all transcripts for a project (default: the current directory).
+pub fn run(project: Option<PathBuf>) -> Result<()> {
+    let project_dir = match project {
+        Some(p) => p,
+        None => std::env::current_dir()?,
+    };
+    let project_dir = std::fs::canonicalize(&project_dir)
+        .with_context(|| format!("Project directory not found: {}", project_dir.display()))?;
+    let project_str = project_dir.to_string_lossy().to_string();
+
+    let dir = transcripts_dir(&project_dir);
+    let mut files: Vec<PathBuf> = std::fs::read_dir(&dir)
+        .with_context(|| format!("No Claude Code transcripts found at {}", dir.display()))?
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.extension().is_some_and(|ext| ext == "jsonl"))
+        .collect();
+    files.sort_by_key(|p| std::fs::metadata(p).and_then(|m| m.modified()).ok());
+
+    let db = Db::open(&project_dir)?;
+    let (mut imported, mut turns, mut skipped) = (0, 0, 0);
+
+    for file in &files {
+        match import_transcript(&db, &project_str, file) {
+            Ok(Imported::Session(n)) => {
+                imported += 1;
+                turns += n;
+            }
+            Ok(Imported::AlreadyIndexed) => skipped += 1,
+            Ok(Imported::Empty) => {}
+            Err(e) => eprintln!("Skipping {}: {}", file.display(), e),
+        }
+    }
+
+    println!(
+        "Imported {} sessions ({} turns) from {}; {} already indexed.",
+        imported,
+        turns,
+        dir.display(),
+        skipped
+    );
+    if imported > 0 {
+        println!("Distillation is queued and runs when the MCP server next starts.");
+    }
+    Ok(())
+}
+
+/// Replay one transcript into the database.
+fn import_transcript(db: &Db, project_dir: &str, path: &Path) -> Result<Imported> {
+    let path_str = path.to_string_lossy().to_string();
+    let (lines, end_offset) = transcript::read_new_lines(&path_str, 0)?;
+    let entries: Vec<Value> = lines
+        .iter()
+        .filter_map(|l| serde_json::from_str(l).ok())
+        .collect();
+
+    if !entries.iter().any(|e| transcript::user_prompt(e).is_some()) {
+        return Ok(Imported::Empty);
+    }
+
+    let session_id = entries
+        .iter()
+        .find_map(|e| e.get("sessionId").and_then(|v| v.as_str()))
+        .map(|s| s.to_string())
+        .or_else(|| path.file_stem().map(|s| s.to_string_lossy().to_string()))
+        .context("Transcript has no session id")?;
+
+    let existing_turns: i64 = {
+        let conn = db.conn();
+        conn.query_row(
+            "SELECT COUNT(*) FROM turns WHERE session_id = ?1",
+            params![session_id],
+            |row| row.get(0),
+        )?
+    };
+    if existing_turns > 0 {
+        return Ok(Imported::AlreadyIndexed);
+    }
+
+    conversation::ensure_session(db, &session_id, project_dir)?;
+    let results = transcript::tool_results(&entries);
+    let mut count = 0;
+
+    for entry in &entries {
+        let timestamp = transcript::db_timestamp(entry);
+
+        if let Some(text) = transcript::user_prompt(entry) {
+            let turn_id = prompt::index_prompt(db, &session_id, &text)?;
+            stamp(db, &[turn_id], timestamp.as_deref())?;
+            count += 1;
+            continue;
+        }
+
+        if let Some(text) = transcript::assistant_text(entry) {
+            if transcript::index_reply(db, &session_id, &text, timestamp.as_deref())? {
+                count += 1;
+            }
+        }
+
+        for call in transcript::tool_calls(entry) {
+            let Some(result) = results.get(&call.id) else {
+                continue;
+            };
+            let turn_ids = index_tool_call(db, &session_id, &call, result)?;
+            if !turn_ids.is_empty() {
+                stamp(db, &turn_ids, timestamp.as_deref())?;
+                count += 1;
+            }
+        }
+    }
+
+    // Session bounds and branch come from the transcript, not from now
+    let first = entries.iter().filter_map(transcript::db_timestamp).min();
+    let last = entries.iter().filter_map(transcript::db_timestamp).max();
+    let branch = entries
+        .iter()
+        .find_map(|e| e.get("gitBranch").and_then(|v| v.as_str()))
+        .filter(|b| !b.is_empty());
+    let conn = db.conn();
+    conn.execute(
+        "UPDATE sessions SET
+            started_at = COALESCE(?2, started_at),
+            ended_at = COALESCE(?3, datetime('now')),
+            git_branch = ?4,
+            transcript_offset = ?5
+         WHERE id = ?1",
+        params![session_id, first, last, branch, end_offset as i64],
+    )?;
+    drop(conn);
+
+    tasks::enqueue_task(db, "distill_session", project_dir, Some(&session_id))?;
+    Ok(Imported::Session(count))
+}
+
+/// Index tool calls from a session's transcript that have no matching turn,
+/// e.g. because the hook timed out or hooks were disabled. Returns the
+/// number of turns recovered.
+pub fn reconcile_session(db: &Db, session_id: &str, transcript_path: &str) -> Result<usize> {
+    let (lines, _) = transcript::read_new_lines(transcript_path, 0)?;
+    let entries: Vec<Value> = lines
+        .iter()
+        .filter_map(|l| serde_json::from_str(l).ok())
+        .collect();
+    let results = transcript::tool_results(&entries);
+
+    // Indexed turns, counted by key, so repeated identical calls match
+    // one-to-one. Calls made while a subagent ran may have been attributed
+    // to its run.
+    let mut indexed: HashMap<(String, String), usize> = HashMap::new();
+    {
+        let conn = db.conn();
+        let mut stmt = conn.prepare(
+            "SELECT turn_type, content FROM turns
+             WHERE (session_id = ?1 OR session_id IN (
+                       SELECT id FROM sessions
+                       WHERE parent_session_id = ?1 AND start_source = 'subagent'))
+               AND turn_type IN ('code_edit', 'file_read', 'bash_cmd', 'search', 'web_fetch', 'notebook_edit')",
+        )?;
+        let rows = stmt.query_map(params![session_id], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })?;
+        for row in rows {
+            let (turn_type, content) = row?;
+            *indexed.entry((turn_type, first_line(&content))).or_default() += 1;
+        }
+    }
+
+    let mut recovered = 0;
+    for entry in &entries {
+        for call in transcript::tool_calls(entry) {
+            let Some(result) = results.get(&call.id) else {
+                continue;
+            };
+            if result.is_error && call.name != "Bash" {
+                continue;
+            }
+            let Some(key) = turn_key(&call) else {
+                continue;
+            };
+            match indexed.get_mut(&key) {
+                Some(n) if *n > 0 => *n -= 1,
+                _ => {
+                    let turn_ids = index_tool_call(db, session_id, &call, result)?;
+                    if !turn_ids.is_empty() {
+                        stamp(db, &turn_ids, transcript::db_timestamp(entry).as_deref())?;
+                        conversation::mark_reconciled(db, &turn_ids)?;
+                        recovered += 1;
+                    }
+                }
+            }
+        }
+    }
+    Ok(recovered)
+}
+
+fn first_line(content: &str) -> String {
+    content.lines().next().unwrap_or("").to_string()
+}
+
+/// The (turn type, first content line) a tool call's hook would index, used
+/// to match transcript calls against existing turns.
+fn turn_key(call: &transcript::ToolCall) -> Option<(String, String)> {
+    let input_str = |key: &str| call.input.get(key).and_then(|v| v.as_str()).unwrap_or("");
+    let (turn_type, content) = match call.name.as_str() {
+        "Edit" | "Write" | "MultiEdit" => (
+            "code_edit",
+            tool_use::format_edit_content(&call.name, &call.input),
+        ),
+        "Grep" | "Glob" | "WebSearch" => (
+            "search",
+            tool_use::format_search_header(&call.name, &call.input),
+        ),
+        "WebFetch" => ("web_fetch", format!("Fetched {}", input_str("url"))),
+        "NotebookEdit" => ("notebook_edit", tool_use::format_notebook_content(&call.input)),
+        "Read" => ("file_read", format!("Read file: {}", input_str("file_path"))),
+        "Bash" => ("bash_cmd", format!("$ {}", input_str("command"))),
+        _ => return None,
+    };
+    Some((turn_type.to_string(), first_line(&content)))
+}
+
+/// Index a tool call the way its PostToolUse hook would, returning the ids
+/// of the turns inserted (none when the call isn't indexed). Only the turns
+/// are recorded: the files on disk have moved on since, so edit regions,
+/// symbol re-indexing and plan tracking are left to live hooks.
+fn index_tool_call(
+    db: &Db,
+    session_id: &str,
+    call: &transcript::ToolCall,
+    result: &transcript::ToolResult,
+) -> Result<Vec<i64>> {
+    // Only Bash failures are indexed (as error turns); other failed calls
+    // changed nothing
+    if result.is_error && call.name != "Bash" {
+        return Ok(Vec::new());
+    }
+    let turn_id = match call.name.as_str() {
+        "Edit" | "Write" | "MultiEdit" => {
+            tool_use::index_edit(db, session_id, &call.name, Some(&call.input))?
+        }
+        "Grep" | "Glob" | "WebSearch" => tool_use::index_search(
+            db,
+            session_id,
+            &call.name,
+            &call.input,
+            Some(&result.response),
+        )?,
+        "WebFetch" => tool_use::index_web_fetch(db, session_id, &call.input, Some(&result.response))?,
+        "NotebookEdit" => tool_use::index_notebook_edit(db, session_id, &call.input)?,
+        "Read" => {
+            let file_path = call
+                .input
+                .get("file_path")
+                .and_then(|v| v.as_str())
+                .unwrap_or("unknown");
+            tool_use::index_read(db, session_id, file_path)?
+        }
+        "Bash" => {
+            let command = call
+                .input
+                .get("command")
+                .and_then(|v| v.as_str())
+                .unwrap_or("[unknown command]");
+            let outcome = if result.is_error {
+                tool_use::BashOutcome::from_response(None, Some(&result.text()))
+            } else {
+                tool_use::BashOutcome::from_response(Some(&result.response), None)
+            };
+            return tool_use::index_bash(db, session_id, command, &outcome);
+        }
+        _ => return Ok(Vec::new()),
+    };
+    Ok(vec![turn_id])
+}
+
+/// Give replayed turns the time recorded in the transcript.
+fn stamp(db: &Db, turn_ids: &[i64], timestamp: Option<&str>) -> Result<()> {
+    let Some(ts) = timestamp else {
+        return Ok(());
+    };
+    for turn_id in turn_ids {
+        conversation::set_turn_timestamp(db, *turn_id, ts)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bash_entries(id: &str, command: &str) -> String {
+        let call = serde_json::json!({
+            "type": "assistant",
+            "timestamp": "2025-01-02T03:04:05.000Z",
+            "message": {"role": "assistant", "content": [
+                {"type": "tool_use", "id": id, "name": "Bash", "input": {"command": command}}
+            ]}
+        });
+        let result = serde_json::json!({
+            "type": "user",
+            "message": {"role": "user", "content": [
+                {"type": "tool_result", "tool_use_id": id, "content": "ok"}
+            ]},
+            "toolUseResult": {"stdout": "ok", "stderr": ""}
+        });
+        format!("{}\n{}\n", call, result)
+    }
+
+    #[test]
+    fn test_reconcile_indexes_only_missed_calls() {
+        let db = Db::open_memory().unwrap();
+        conversation::ensure_session(&db, "s1", "/nonexistent").unwrap();
+        // The hook for the first `cargo build` fired; the second one timed out
+        let outcome = tool_use::BashOutcome::from_response(None, None);
+        tool_use::index_bash(&db, "s1", "cargo build", &outcome).unwrap();
+
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("reconcile.jsonl");
+        let transcript = [
+            bash_entries("t1", "cargo build"),
+            bash_entries("t2", "cargo build"),
+            bash_entries("t3", "cargo test"),
+        ]
+        .concat();
+        std::fs::write(&path, transcript).unwrap();
+        let path_str = path.to_string_lossy().to_string();
+
+        assert_eq!(reconcile_session(&db, "s1", &path_str).unwrap(), 2);
+        assert_eq!(reconcile_session(&db, "s1", &path_str).unwrap(), 0);
+
+        let reconciled: i64 = db
+            .conn()
+            .query_row("SELECT COUNT(*) FROM turns WHERE reconciled = 1", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(reconciled, 2);
+    }
+
+    fn prompt_entries(session_id: &str, branch: Option<&str>) -> String {
+        let mut prompt = serde_json::json!({
+            "type": "user",
+            "sessionId": session_id,
+            "timestamp": "2025-01-02T03:00:00.000Z",
+            "message": {"role": "user", "content": "Add a lexer for the config format"}
+        });
+        if let Some(branch) = branch {
+            prompt["gitBranch"] = branch.into();
+        }
+        let reply = serde_json::json!({
+            "type": "assistant",
+            "sessionId": session_id,
+            "timestamp": "2025-01-02T03:01:00.000Z",
+            "message": {"role": "assistant", "content": [
+                {"type": "text", "text": "I'll write the lexer first, then hook it into the parser so each key keeps its source span."}
+            ]}
+        });
+        format!("{}\n{}\n", prompt, reply)
+    }
+
+    #[test]
+    fn test_import_replays_transcript() {
+        // The project is checked out on a branch none of the sessions used
+        let tmp = tempfile::tempdir().unwrap();
+        let git = |args: &[&str]| {
+            let status = std::process::Command::new("git")
+                .args(["-c", "user.name=t", "-c", "user.email=t@t"])
+                .args(args)
+                .current_dir(tmp.path())
+                .stdout(std::process::Stdio::null())
+                .status()
+                .unwrap();
+            assert!(status.success());
+        };
+        git(&["init", "-q"]);
+        git(&["checkout", "-q", "-b", "today"]);
+        git(&["commit", "-q", "--allow-empty", "-m", "init"]);
+        let project_dir = tmp.path().to_string_lossy().to_string();
+
+        let path = tmp.path().join("hist-1.jsonl");
+        let transcript = prompt_entries("hist-1", Some("old-branch")) + &bash_entries("t1", "cargo build");
+        std::fs::write(&path, transcript).unwrap();
+        let unbranched = tmp.path().join("hist-2.jsonl");
+        std::fs::write(&unbranched, prompt_entries("hist-2", None)).unwrap();
+
+        let db = Db::open_memory().unwrap();
+        assert!(matches!(import_transcript(&db, &project_dir, &path).unwrap(), Imported::Session(3)));
+        assert!(matches!(import_transcript(&db, &project_dir, &path).unwrap(), Imported::AlreadyIndexed));
+        assert!(matches!(import_transcript(&db, &project_dir, &unbranched).unwrap(), Imported::Session(2)));
+
+        let conn = db.conn();
+        let mut stmt = conn
+            .prepare("SELECT turn_type, timestamp FROM turns WHERE session_id = 'hist-1' ORDER BY id")
+            .unwrap();
+        let turns: Vec<(String, String)> = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        assert_eq!(
+            turns,
+            [
+                ("request".to_string(), "2025-01-02 03:00:00".to_string()),
+                ("explanation".to_string(), "2025-01-02 03:01:00".to_string()),
+                ("bash_cmd".to_string(), "2025-01-02 03:04:05".to_string()),
+            ]
+        );
+
+        let session = |id: &str| -> (String, String, Option<String>) {
+            conn.query_row(
+                "SELECT started_at, ended_at, git_branch FROM sessions WHERE id = ?1",
+                [id],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .unwrap()
+        };
+        assert_eq!(
+            session("hist-1"),
+            (
+                "2025-01-02 03:00:00".to_string(),
+                "2025-01-02 03:04:05".to_string(),
+                Some("old-branch".to_string())
+            )
+        );
+        // No branch in the transcript: unknown, not today's checkout
+        assert_eq!(session("hist-2").2, None);
+
+        let mut stmt = conn
+            .prepare("SELECT payload FROM background_tasks WHERE task_type = 'distill_session' ORDER BY id")
+            .unwrap();
+        let queued: Vec<String> = stmt
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap();
+        assert_eq!(queued, ["hist-1", "hist-2"]);
+    }
+}
@@ -77,14 +77,12 @@ pub fn index_file(db: &Db, file_path: &Path, lang: Lang) -> Result<usize> {
     let file_path_str = file_path.to_string_lossy();
 
     let extracted = symbols::extract_symbols(lang, &source)?;
+    let calls = symbols::extract_calls(lang, &source)?;
 
     let conn = db.conn();
 
-    // Delete existing symbols for this file
-    conn.execute(
-        "DELETE FROM symbols WHERE file_path = ?1",
-        params![file_path_str.as_ref()],
-    )?;
+    // Delete existing symbols and call sites for this file
+    remove_file(&conn, &file_path_str)?;
 
     // Delete existing refs from symbols in this file
     conn.execute(
@@ -98,9 +96,25 @@ pub fn index_file(db: &Db, file_path: &Path, lang: Lang) -> Result<usize> {
         insert_symbol(&conn, &file_path_str, sym)?;
     }
 
+    // Insert call sites
+    let mut stmt = conn.prepare(
+        "INSERT INTO symbol_calls (file_path, line, callee) VALUES (?1, ?2, ?3)",
+    )?;
+    for call in &calls {
+        stmt.execute(params![file_path_str.as_ref(), call.line as i64, call.callee])?;
+    }
+
     Ok(extracted.len())
 }
 
+/// Remove all indexed symbols and call sites for a file
+/// (on deletion, or before re-indexing it).
+pub fn remove_file(conn: &rusqlite::Connection, file_path: &str) -> Result<()> {
+    conn.execute("DELETE FROM symbols WHERE file_path = ?1", params![file_path])?;
+    conn.execute("DELETE FROM symbol_calls WHERE file_path = ?1", params![file_path])?;
+    Ok(())
+}
+
 /// Re-index a single file (used by hooks and file watcher).
 pub fn reindex_file(db: &Db, file_path: &Path) -> Result<usize> {
     let ext = match file_path.extension().and_then(|e| e.to_str()) {
@@ -123,6 +137,14 @@ pub fn has_index(db: &Db) -> Result<bool> {
     Ok(count > 0)
 }
 
+/// Check if call sites have been indexed. Databases created before call
+/// indexing have symbols but no calls and need a full re-scan.
+pub fn has_call_index(db: &Db) -> Result<bool> {
+    let conn = db.conn();
+    let count: i64 = conn.query_row("SELECT COUNT(*) FROM symbol_calls", [], |row| row.get(0))?;
+    Ok(count > 0)
+}
+
 fn insert_symbol(
     conn: &rusqlite::Connection,
     file_path: &str,
@@ -14,6 +14,17 @@ pub fn ensure_session(db: &Db, session_id: &str, project_dir: &str) -> Result<()
     Ok(())
 }
 
+/// Record the git branch a session started on. A resumed session keeps the
+/// branch it was first started on.
+pub fn record_branch(db: &Db, session_id: &str, branch: &str) -> Result<()> {
+    let conn = db.conn();
+    conn.execute(
+        "UPDATE sessions SET git_branch = COALESCE(git_branch, ?2) WHERE id = ?1",
+        params![session_id, branch],
+    )?;
+    Ok(())
+}
+
 /// Get the next turn number for a session.
 fn next_turn_number(db: &Db, session_id: &str) -> Result<i64> {
     let conn = db.conn();
@@ -59,6 +70,146 @@ pub fn index_turn(
     Ok(turn_id)
 }
 
+/// Override a turn's timestamp (e.g. with the time recorded in a transcript).
+pub fn set_turn_timestamp(db: &Db, turn_id: i64, timestamp: &str) -> Result<()> {
+    let conn = db.conn();
+    conn.execute(
+        "UPDATE turns SET timestamp = ?2 WHERE id = ?1",
+        params![turn_id, timestamp],
+    )?;
+    Ok(())
+}
+
+/// Mark turns as recovered from the transcript.
+pub fn mark_reconciled(db: &Db, turn_ids: &[i64]) -> Result<()> {
+    let conn = db.conn();
+    for turn_id in turn_ids {
+        conn.execute("UPDATE turns SET reconciled = 1 WHERE id = ?1", params![turn_id])?;
+    }
+    Ok(())
+}
+
+/// Record the line ranges a `code_edit` turn touched in a file.
+pub fn record_edit_regions(
+    db: &Db,
+    turn_id: i64,
+    file_path: &str,
+    regions: &[(i64, i64)], // (start_line, end_line), 1-indexed inclusive
+) -> Result<()> {
+    let conn = db.conn();
+    for (start, end) in regions {
+        conn.execute(
+            "INSERT INTO edit_regions (turn_id, file_path, start_line, end_line)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![turn_id, file_path, start, end],
+        )?;
+    }
+    Ok(())
+}
+
+/// Record how a session started (`startup`, `resume`, `clear`) and the
+/// session it continues, if any. The first recorded source and parent stick:
+/// later compactions don't overwrite them.
+pub fn record_start(
+    db: &Db,
+    session_id: &str,
+    source: &str,
+    parent_session_id: Option<&str>,
+) -> Result<()> {
+    let parent = parent_session_id.filter(|p| *p != session_id);
+    let conn = db.conn();
+    conn.execute(
+        "UPDATE sessions
+         SET start_source = COALESCE(start_source, ?2),
+             parent_session_id = COALESCE(parent_session_id, ?3)
+         WHERE id = ?1",
+        params![session_id, source, parent],
+    )?;
+    Ok(())
+}
+
+/// Clear a session's end marker when it's resumed.
+pub fn reopen_session(db: &Db, session_id: &str) -> Result<()> {
+    let conn = db.conn();
+    conn.execute(
+        "UPDATE sessions SET ended_at = NULL WHERE id = ?1",
+        params![session_id],
+    )?;
+    Ok(())
+}
+
+/// The most recently active session other than `session_id`, skipping
+/// subagent runs and sessions still live in another window (see
+/// `live_sessions`), which `session_id` can't be continuing.
+pub fn previous_session(db: &Db, session_id: &str) -> Result<Option<String>> {
+    let live = live_sessions(db, session_id)?;
+    let conn = db.conn();
+    let mut stmt = conn.prepare(
+        "SELECT t.session_id FROM turns t
+         JOIN sessions s ON s.id = t.session_id
+         WHERE t.session_id != ?1 AND COALESCE(s.start_source, '') != 'subagent'
+         GROUP BY t.session_id
+         ORDER BY MAX(t.id) DESC",
+    )?;
+    for row in stmt.query_map(params![session_id], |row| row.get::<_, String>(0))? {
+        let id = row?;
+        if !live.contains(&id) {
+            return Ok(Some(id));
+        }
+    }
+    Ok(None)
+}
+
+/// Minutes without activity after which an unended session (one whose
+/// SessionEnd hook never ran) stops counting as live.
+pub const LIVE_SESSION_MINUTES: i64 = 30;
+
+/// `session_id` followed by the sessions it continues: the parents it was
+/// resumed, forked or cleared from, or the session a subagent ran in.
+pub fn lineage(db: &Db, session_id: &str) -> Result<Vec<String>> {
+    let conn = db.conn();
+    let mut chain = vec![session_id.to_string()];
+    while chain.len() < 20 {
+        let parent: Option<String> = conn
+            .query_row(
+                "SELECT parent_session_id FROM sessions WHERE id = ?1",
+                params![chain[chain.len() - 1]],
+                |row| row.get(0),
+            )
+            .ok()
+            .flatten();
+        match parent {
+            Some(p) if !chain.contains(&p) => chain.push(p),
+            _ => break,
+        }
+    }
+    Ok(chain)
+}
+
+/// Other sessions still open on this project, e.g. in a second Claude
+/// window: not ended, active in the last LIVE_SESSION_MINUTES, and not
+/// `session_id`'s own lineage or a subagent run.
+pub fn live_sessions(db: &Db, session_id: &str) -> Result<Vec<String>> {
+    let own = lineage(db, session_id)?;
+    let conn = db.conn();
+    let mut stmt = conn.prepare(
+        "SELECT s.id FROM sessions s
+         WHERE s.ended_at IS NULL
+           AND COALESCE(s.start_source, '') != 'subagent'
+           AND (s.started_at >= datetime('now', ?1)
+                OR EXISTS (
+                    SELECT 1 FROM turns t
+                    WHERE (t.session_id = s.id OR t.session_id IN (
+                              SELECT c.id FROM sessions c WHERE c.parent_session_id = s.id))
+                      AND t.timestamp >= datetime('now', ?1)))",
+    )?;
+    let window = format!("-{} minutes", LIVE_SESSION_MINUTES);
+    let live: Vec<String> = stmt
+        .query_map(params![window], |row| row.get::<_, String>(0))?
+        .collect::<rusqlite::Result<_>>()?;
+    Ok(live.into_iter().filter(|id| !own.contains(id)).collect())
+}
+
 /// Mark a session as ended.
 pub fn end_session(db: &Db, session_id: &str, summary: Option<&str>) -> Result<()> {
     let conn = db.conn();
@@ -70,7 +221,6 @@ pub fn end_session(db: &Db, session_id: &str, summary: Option<&str>) -> Result<(
 }
 
 /// Get the total number of turns in a session.
-#[allow(dead_code)]
 pub fn session_turn_count(db: &Db, session_id: &str) -> Result<i64> {
     let conn = db.conn();
     let count: i64 = conn.query_row(
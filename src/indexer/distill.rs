@@ -4,6 +4,7 @@ use serde::Deserialize;
 
 use crate::db::Db;
 use crate::db::search;
+use crate::indexer::subagents;
 use crate::llm::LlmConfig;
 
 /// Distill knowledge from a completed session using LLM if available,
@@ -28,9 +29,7 @@ pub fn distill_session_smart(db: &Db, session_id: &str) -> Result<DistillStats>
 
 /// Distill knowledge using an LLM for high-quality extraction.
 fn distill_session_llm(db: &Db, session_id: &str, llm: &LlmConfig) -> Result<DistillStats> {
-    let conn = db.conn();
-    let turns = search::session_turns(&conn, session_id)?;
-    drop(conn);
+    let turns = distill_turns(db, session_id)?;
 
     if turns.is_empty() {
         return Ok(DistillStats::default());
@@ -139,11 +138,21 @@ struct LlmKnowledgeEntry {
     confidence: f64,
 }
 
+/// A session's turns to distill: with its subagents' activity, unless
+/// `[subagents] distill = false`.
+fn distill_turns(db: &Db, session_id: &str) -> Result<Vec<search::TurnSearchResult>> {
+    let with_subagents = subagents::include_activity(&db.project_dir(), "distill");
+    let conn = db.conn();
+    if with_subagents {
+        search::session_turns_with_subagents(&conn, session_id)
+    } else {
+        search::session_turns(&conn, session_id)
+    }
+}
+
 /// Distill knowledge from a completed session using heuristic pattern matching.
 pub fn distill_session(db: &Db, session_id: &str) -> Result<DistillStats> {
-    let conn = db.conn();
-    let turns = search::session_turns(&conn, session_id)?;
-    drop(conn);
+    let turns = distill_turns(db, session_id)?;
 
     if turns.is_empty() {
         return Ok(DistillStats::default());
@@ -339,6 +348,47 @@ pub fn distill_session(db: &Db, session_id: &str) -> Result<DistillStats> {
         }
     }
 
+    // 6. Command failures and what fixed them
+    //    Pattern: an error turn, edits, then the same command succeeding
+    for (idx, turn) in turns.iter().enumerate() {
+        if turn.turn_type != "error" {
+            continue;
+        }
+        let (Some(command), Some(fix)) = (
+            search::failed_command(&turn.content),
+            search::error_resolution(&db.conn(), &turns, idx)?,
+        ) else {
+            continue;
+        };
+
+        let mut fixed_files: Vec<String> = turns
+            .iter()
+            .filter(|t| {
+                t.turn_type == "code_edit"
+                    && t.turn_number > turn.turn_number
+                    && t.turn_number < fix.turn_number
+            })
+            .flat_map(|t| t.files.iter().map(|f| short_path(f)))
+            .collect();
+        fixed_files.sort();
+        fixed_files.dedup();
+        if fixed_files.is_empty() {
+            continue;
+        }
+
+        let symptom = turn.content.lines().nth(1).unwrap_or("").trim();
+        let subject = format!("`{}` failure", truncate(command, 60));
+        let content = format!(
+            "`{}` failed ({}); fixed by editing {}",
+            truncate(command, 100),
+            truncate(symptom, 150),
+            fixed_files.join(", ")
+        );
+        stats.extracted += upsert_knowledge(
+            db, session_id, "bug_fix", &subject, &content, 0.7,
+        )?;
+    }
+
     Ok(stats)
 }
 
@@ -452,7 +502,7 @@ fn extract_preference_subject(text: &str) -> String {
         if let Some(pos) = text.find(keyword) {
             let after = &text[pos + keyword.len()..];
             let end = after
-                .find(|c: char| c == '.' || c == ',' || c == '!' || c == '\n')
+                .find(['.', ',', '!', '\n'])
                 .unwrap_or_else(|| after.floor_char_boundary(after.len().min(50)));
             let phrase = after[..end].trim();
             if !phrase.is_empty() {
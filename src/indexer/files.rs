@@ -138,10 +138,7 @@ pub fn catchup(db: &Db, project_dir: &Path, session_id: &str) -> Result<CatchupS
                 "DELETE FROM file_hashes WHERE project_dir = ?1 AND file_path = ?2",
                 params![&dir_str, file_name],
             )?;
-            conn.execute(
-                "DELETE FROM symbols WHERE file_path = ?1",
-                params![&full_path],
-            )?;
+            code::remove_file(&conn, &full_path)?;
         }
     }
 
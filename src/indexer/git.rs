@@ -120,6 +120,55 @@ pub fn catchup(db: &Db, project_dir: &Path, session_id: &str) -> Result<CatchupS
     })
 }
 
+/// A commit that touched a line range, from `git log -L`.
+#[derive(Debug)]
+pub struct LineCommit {
+    pub hash: String,
+    /// Commit time as UTC `YYYY-MM-DD HH:MM:SS` (same format as turn timestamps)
+    pub timestamp: String,
+    pub subject: String,
+}
+
+/// List commits that touched lines `start..=end` of `file_path`, newest first.
+/// Returns an empty list outside a git repo or for untracked files.
+pub fn line_history(
+    dir: &Path,
+    file_path: &str,
+    start: i64,
+    end: i64,
+    limit: usize,
+) -> Vec<LineCommit> {
+    let range = format!("-L{},{}:{}", start, end, file_path);
+    let limit = format!("-n{}", limit);
+    let output = match Command::new("git")
+        .args(["log", "-s", "--format=%h%x09%at%x09%s", &limit, &range])
+        .current_dir(dir)
+        .stderr(std::process::Stdio::null())
+        .output()
+    {
+        Ok(o) if o.status.success() => o,
+        _ => return Vec::new(),
+    };
+
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.splitn(3, '\t');
+            let hash = parts.next()?.to_string();
+            let secs: i64 = parts.next()?.parse().ok()?;
+            let subject = parts.next().unwrap_or("").to_string();
+            let timestamp = chrono::DateTime::from_timestamp(secs, 0)?
+                .format("%Y-%m-%d %H:%M:%S")
+                .to_string();
+            Some(LineCommit {
+                hash,
+                timestamp,
+                subject,
+            })
+        })
+        .collect()
+}
+
 // --- Git CLI helpers ---
 
 pub fn is_git_repo(dir: &Path) -> bool {
@@ -133,6 +182,26 @@ pub fn is_git_repo(dir: &Path) -> bool {
         .unwrap_or(false)
 }
 
+/// Name of the currently checked-out branch, or `None` outside a repo or on a
+/// detached HEAD.
+pub fn current_branch(dir: &Path) -> Option<String> {
+    let output = Command::new("git")
+        .args(["rev-parse", "--abbrev-ref", "HEAD"])
+        .current_dir(dir)
+        .stderr(std::process::Stdio::null())
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let branch = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if branch.is_empty() || branch == "HEAD" {
+        None
+    } else {
+        Some(branch)
+    }
+}
+
 fn git_rev_parse_head(dir: &Path) -> Result<String> {
     let output = Command::new("git")
         .args(["rev-parse", "HEAD"])
@@ -4,3 +4,6 @@ pub mod distill;
 pub mod files;
 pub mod git;
 pub mod plans;
+pub mod subagents;
+pub mod todos;
+pub mod transcript;
@@ -1,6 +1,7 @@
 use anyhow::Result;
 
 use crate::db::Db;
+use crate::indexer::conversation;
 
 /// Info about an active plan, returned by `active_plan()`.
 pub struct PlanInfo {
@@ -99,9 +100,20 @@ fn is_file_path(s: &str, extensions: &[&str]) -> bool {
     extensions.iter().any(|ext| s.ends_with(ext))
 }
 
+/// Sessions whose plans `session_id` may see and take over, as JSON arrays
+/// for `json_each`: its own lineage, and other live sessions (whose plans it
+/// must leave alone).
+fn plan_scope(db: &Db, session_id: &str) -> Result<(String, String)> {
+    let own = conversation::lineage(db, session_id)?;
+    let live = conversation::live_sessions(db, session_id)?;
+    Ok((serde_json::to_string(&own)?, serde_json::to_string(&live)?))
+}
+
 /// Create or update a plan in the database.
-/// When creating a new plan, supersedes any other active plans.
+/// When creating a new plan, supersedes the other active plans, except
+/// those of other live sessions (another window working on its own plan).
 pub fn upsert_plan(db: &Db, session_id: &str, path: &str, content: &str) -> Result<i64> {
+    let (_, live) = plan_scope(db, session_id)?;
     let conn = db.conn();
     let title = extract_title(content, path);
     let target_files = extract_target_files(content);
@@ -128,9 +140,11 @@ pub fn upsert_plan(db: &Db, session_id: &str, path: &str, content: &str) -> Resu
         // Supersede any other active plans
         let active_ids: Vec<i64> = {
             let mut stmt = conn.prepare(
-                "SELECT id FROM plans WHERE status IN ('created', 'in_progress') AND plan_file_path != ?1",
+                "SELECT id FROM plans
+                 WHERE status IN ('created', 'in_progress') AND plan_file_path != ?1
+                   AND session_id NOT IN (SELECT value FROM json_each(?2))",
             )?;
-            let rows = stmt.query_map([path], |row| row.get(0))?;
+            let rows = stmt.query_map(rusqlite::params![path, live], |row| row.get(0))?;
             rows.filter_map(|r| r.ok()).collect()
         };
 
@@ -157,24 +171,35 @@ pub fn upsert_plan(db: &Db, session_id: &str, path: &str, content: &str) -> Resu
 
 /// Record a source file edit as progress on the active plan.
 /// Transitions plan from `created` → `in_progress` on first source edit.
+/// A plan left behind by an earlier session is taken over by the session
+/// that makes progress on it.
 pub fn record_progress(db: &Db, session_id: &str, file_path: &str) -> Result<()> {
+    let (own, live) = plan_scope(db, session_id)?;
     let conn = db.conn();
 
-    // Find active plan for this session (or any active plan)
-    let plan_id: Option<i64> = conn
+    // Find this session's active plan, else one no live session owns
+    let plan: Option<(i64, String)> = conn
         .query_row(
-            "SELECT id FROM plans
+            "SELECT id, session_id FROM plans
              WHERE status IN ('created', 'in_progress')
-             ORDER BY CASE WHEN session_id = ?1 THEN 0 ELSE 1 END, updated_at DESC
+               AND session_id NOT IN (SELECT value FROM json_each(?2))
+             ORDER BY CASE WHEN session_id IN (SELECT value FROM json_each(?1)) THEN 0 ELSE 1 END,
+                      updated_at DESC
              LIMIT 1",
-            [session_id],
-            |row| row.get(0),
+            rusqlite::params![own, live],
+            |row| Ok((row.get(0)?, row.get(1)?)),
         )
         .ok();
 
-    let Some(plan_id) = plan_id else {
+    let Some((plan_id, owner)) = plan else {
         return Ok(());
     };
+    if owner != session_id {
+        conn.execute(
+            "UPDATE plans SET session_id = ?1 WHERE id = ?2",
+            rusqlite::params![session_id, plan_id],
+        )?;
+    }
 
     // Upsert into plan_progress
     conn.execute(
@@ -196,18 +221,28 @@ pub fn record_progress(db: &Db, session_id: &str, file_path: &str) -> Result<()>
     Ok(())
 }
 
-/// Get the most recent active plan (created or in_progress).
-pub fn active_plan(db: &Db) -> Result<Option<PlanInfo>> {
+/// Raw `plans` row: id, session_id, plan_file_path, title, content, status,
+/// target_files, created_at, updated_at.
+type PlanRow = (i64, String, String, Option<String>, String, String, Option<String>, String, String);
+
+/// Get the active plan (created or in_progress) for a session: its own
+/// (or its lineage's) most recent, else the most recent one left by a
+/// session that's no longer live. Plans of other live sessions are never
+/// returned.
+pub fn active_plan(db: &Db, session_id: &str) -> Result<Option<PlanInfo>> {
+    let (own, live) = plan_scope(db, session_id)?;
     let conn = db.conn();
 
-    let row: Option<(i64, String, String, Option<String>, String, String, Option<String>, String, String)> = conn
+    let row: Option<PlanRow> = conn
         .query_row(
             "SELECT id, session_id, plan_file_path, title, content, status, target_files, created_at, updated_at
              FROM plans
              WHERE status IN ('created', 'in_progress')
-             ORDER BY updated_at DESC
+               AND session_id NOT IN (SELECT value FROM json_each(?2))
+             ORDER BY CASE WHEN session_id IN (SELECT value FROM json_each(?1)) THEN 0 ELSE 1 END,
+                      updated_at DESC
              LIMIT 1",
-            [],
+            rusqlite::params![own, live],
             |row| Ok((
                 row.get(0)?,
                 row.get(1)?,
@@ -328,3 +363,32 @@ pub fn abandon_stale_plans(db: &Db, max_age_days: i64) -> Result<()> {
     )?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plans_scoped_to_live_sessions() {
+        let db = Db::open_memory().unwrap();
+        conversation::ensure_session(&db, "a", "/p").unwrap();
+        conversation::ensure_session(&db, "b", "/p").unwrap();
+        let plan = "# Plan A\n\n- src/a.rs\n";
+        upsert_plan(&db, "a", "/home/.claude/plans/a.md", plan).unwrap();
+        upsert_plan(&db, "b", "/home/.claude/plans/b.md", "# Plan B\n").unwrap();
+
+        // Each window sees its own plan; neither superseded the other's
+        assert_eq!(active_plan(&db, "a").unwrap().unwrap().title.as_deref(), Some("Plan A"));
+        assert_eq!(active_plan(&db, "b").unwrap().unwrap().title.as_deref(), Some("Plan B"));
+
+        // Edits in b don't count as progress on a's plan
+        record_progress(&db, "b", "/p/src/a.rs").unwrap();
+        assert!(active_plan(&db, "a").unwrap().unwrap().progress.is_empty());
+
+        // Once a has ended, its plan is left for the next session to take over
+        conversation::end_session(&db, "a", None).unwrap();
+        conversation::end_session(&db, "b", None).unwrap();
+        conversation::ensure_session(&db, "c", "/p").unwrap();
+        assert!(active_plan(&db, "c").unwrap().is_some());
+    }
+}
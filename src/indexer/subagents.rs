@@ -0,0 +1,241 @@
+use anyhow::Result;
+use rusqlite::{params, OptionalExtension};
+use serde_json::json;
+
+use crate::db::Db;
+use crate::indexer::conversation;
+
+/// The session recording one subagent run, keyed by the Task call's
+/// tool_use_id.
+fn run_session_id(parent_session_id: &str, key: &str) -> String {
+    format!("{}/agent-{}", parent_session_id, key)
+}
+
+/// Open a session for a subagent run started by the Task tool. Returns the
+/// run's session id.
+pub fn start(
+    db: &Db,
+    parent_session_id: &str,
+    project_dir: &str,
+    key: &str,
+    agent_type: &str,
+) -> Result<String> {
+    let run_id = run_session_id(parent_session_id, key);
+    conversation::ensure_session(db, parent_session_id, project_dir)?;
+    conversation::ensure_session(db, &run_id, project_dir)?;
+    conversation::record_start(db, &run_id, "subagent", Some(parent_session_id))?;
+    let conn = db.conn();
+    conn.execute(
+        "UPDATE sessions SET agent_type = COALESCE(agent_type, ?2) WHERE id = ?1",
+        params![run_id, agent_type],
+    )?;
+    Ok(run_id)
+}
+
+/// Close a subagent run and index its final report as a `summary` turn on
+/// the parent session, listing the files the subagent edited. Without a
+/// key, the newest open run of `agent_type` is closed. Returns the summary
+/// turn's id.
+pub fn finish(
+    db: &Db,
+    parent_session_id: &str,
+    key: Option<&str>,
+    agent_type: &str,
+    description: &str,
+    report: &str,
+) -> Result<i64> {
+    let run_id = match key {
+        Some(k) => Some(run_session_id(parent_session_id, k)),
+        None => newest_open_run(db, parent_session_id, agent_type)?,
+    };
+
+    let mut files = Vec::new();
+    if let Some(run_id) = &run_id {
+        files = edited_files(db, run_id)?
+            .into_iter()
+            .map(|f| (f, "edit".to_string()))
+            .collect();
+        let first_line = report.lines().find(|l| !l.trim().is_empty()).unwrap_or("");
+        conversation::end_session(db, run_id, Some(first_line))?;
+    }
+
+    let header = if description.is_empty() {
+        format!("Subagent ({}) report:", agent_type)
+    } else {
+        format!("Subagent ({}) report: {}", agent_type, description)
+    };
+    let content = format!("{}\n{}", header, report.trim());
+    let metadata = json!({
+        "agent_type": agent_type,
+        "subagent_session": run_id,
+        "description": description,
+    });
+    conversation::index_turn(
+        db,
+        parent_session_id,
+        "assistant",
+        "summary",
+        &content,
+        Some(&metadata),
+        &files,
+    )
+}
+
+/// Close every subagent run still open under `parent_session_id`. A run is
+/// normally closed by the Task call's PostToolUse hook, which doesn't fire
+/// when the Task is interrupted. Returns the number of runs closed.
+pub fn close_open_runs(db: &Db, parent_session_id: &str) -> Result<usize> {
+    let conn = db.conn();
+    let closed = conn.execute(
+        "UPDATE sessions SET ended_at = datetime('now')
+         WHERE parent_session_id = ?1 AND start_source = 'subagent'
+           AND ended_at IS NULL",
+        params![parent_session_id],
+    )?;
+    Ok(closed)
+}
+
+/// The session a tool call belongs to. Hooks fired inside a subagent carry
+/// the parent's session id plus the subagent's `agent_id`, so calls with an
+/// `agent_id` are attributed to the run it claimed, else to the only open
+/// unclaimed run. Calls without one, or with several runs open and no way to
+/// tell them apart, stay on the parent session.
+pub fn attribute(
+    db: &Db,
+    session_id: &str,
+    agent_id: Option<&str>,
+    agent_type: Option<&str>,
+) -> Result<String> {
+    let Some(agent_id) = agent_id else {
+        return Ok(session_id.to_string());
+    };
+    let conn = db.conn();
+    let claimed: Option<String> = conn
+        .query_row(
+            "SELECT id FROM sessions WHERE parent_session_id = ?1 AND agent_id = ?2",
+            params![session_id, agent_id],
+            |row| row.get(0),
+        )
+        .optional()?;
+    if let Some(run_id) = claimed {
+        return Ok(run_id);
+    }
+
+    let mut stmt = conn.prepare(
+        "SELECT id, agent_type FROM sessions
+         WHERE parent_session_id = ?1 AND start_source = 'subagent'
+           AND ended_at IS NULL AND agent_id IS NULL",
+    )?;
+    let open: Vec<(String, Option<String>)> = stmt
+        .query_map(params![session_id], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<rusqlite::Result<_>>()?;
+
+    // Narrow by agent type when several runs are open
+    let candidates: Vec<&String> = match agent_type {
+        Some(t) if open.len() > 1 => open
+            .iter()
+            .filter(|(_, ty)| ty.as_deref() == Some(t))
+            .map(|(id, _)| id)
+            .collect(),
+        _ => open.iter().map(|(id, _)| id).collect(),
+    };
+    let [run_id] = candidates.as_slice() else {
+        return Ok(session_id.to_string());
+    };
+
+    conn.execute(
+        "UPDATE sessions SET agent_id = ?2 WHERE id = ?1",
+        params![run_id, agent_id],
+    )?;
+    Ok(run_id.to_string())
+}
+
+/// Whether subagent activity is included in `purpose` (`compact` or
+/// `distill`), per `[subagents]` in the project or global config. Compact
+/// injection leaves it out by default, since the reports on the parent
+/// summarize it; distillation includes it.
+pub fn include_activity(project_dir: &str, purpose: &str) -> bool {
+    crate::llm::config_value(project_dir, "subagents", purpose)
+        .and_then(|v| v.as_bool())
+        .unwrap_or(purpose == "distill")
+}
+
+fn newest_open_run(db: &Db, parent_session_id: &str, agent_type: &str) -> Result<Option<String>> {
+    let conn = db.conn();
+    let run_id = conn
+        .query_row(
+            "SELECT id FROM sessions
+             WHERE parent_session_id = ?1 AND start_source = 'subagent'
+               AND ended_at IS NULL AND agent_type = ?2
+             ORDER BY started_at DESC, id DESC LIMIT 1",
+            params![parent_session_id, agent_type],
+            |row| row.get(0),
+        )
+        .optional()?;
+    Ok(run_id)
+}
+
+fn edited_files(db: &Db, run_id: &str) -> Result<Vec<String>> {
+    let conn = db.conn();
+    let mut stmt = conn.prepare(
+        "SELECT DISTINCT tf.file_path FROM turn_files tf
+         JOIN turns t ON t.id = tf.turn_id
+         WHERE t.session_id = ?1 AND t.turn_type IN ('code_edit', 'notebook_edit')
+         ORDER BY tf.file_path",
+    )?;
+    let files = stmt
+        .query_map(params![run_id], |row| row.get(0))?
+        .collect::<rusqlite::Result<_>>()?;
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_subagent_attribution() {
+        let db = Db::open_memory().unwrap();
+        conversation::ensure_session(&db, "main", "/p").unwrap();
+
+        // No run open: calls stay on the parent
+        assert_eq!(attribute(&db, "main", Some("a0"), None).unwrap(), "main");
+
+        let run = start(&db, "main", "/p", "toolu_1", "general-purpose").unwrap();
+        assert_eq!(run, "main/agent-toolu_1");
+        // Without an agent_id the call is the parent's own
+        assert_eq!(attribute(&db, "main", None, None).unwrap(), "main");
+        assert_eq!(attribute(&db, "main", Some("a0"), None).unwrap(), run);
+        conversation::index_turn(
+            &db,
+            &run,
+            "assistant",
+            "code_edit",
+            "Edit src/lib.rs",
+            None,
+            &[("/p/src/lib.rs".to_string(), "edit".to_string())],
+        )
+        .unwrap();
+
+        // Two runs open: the new agent can only be the unclaimed one, and a
+        // claimed run keeps its agent
+        let explore = start(&db, "main", "/p", "toolu_2", "Explore").unwrap();
+        assert_eq!(attribute(&db, "main", Some("a1"), Some("Explore")).unwrap(), explore);
+        assert_eq!(attribute(&db, "main", Some("a1"), None).unwrap(), explore);
+        assert_eq!(attribute(&db, "main", Some("a0"), None).unwrap(), run);
+
+        finish(&db, "main", Some("toolu_1"), "general-purpose", "Fix lib", "Fixed the bug.").unwrap();
+        let turns = crate::db::search::session_turns(&db.conn(), "main").unwrap();
+        let summary = turns.iter().find(|t| t.turn_type == "summary").unwrap();
+        assert!(summary.content.contains("Fixed the bug."));
+        assert_eq!(summary.files, vec!["/p/src/lib.rs".to_string()]);
+        let children = crate::db::search::child_sessions(&db.conn(), "main").unwrap();
+        assert_eq!(children, vec![run, explore.clone()]);
+
+        // An interrupted run is closed when the session stops, and later
+        // unclaimed agents no longer land in it
+        start(&db, "main", "/p", "toolu_3", "Explore").unwrap();
+        assert_eq!(close_open_runs(&db, "main").unwrap(), 2);
+        assert_eq!(attribute(&db, "main", Some("a3"), None).unwrap(), "main");
+    }
+}
@@ -0,0 +1,160 @@
+use anyhow::Result;
+use rusqlite::params;
+use serde_json::Value;
+
+use crate::db::Db;
+
+/// One item of a TodoWrite list.
+#[derive(Debug)]
+pub struct TodoItem {
+    pub content: String,
+    pub status: String,
+    pub active_form: Option<String>,
+    /// Status in the previous version of the list (`None` if newly added)
+    pub previous_status: Option<String>,
+}
+
+/// Parse the `todos` array of a TodoWrite tool input.
+pub fn parse_todos(tool_input: &Value) -> Vec<TodoItem> {
+    tool_input
+        .get("todos")
+        .and_then(|t| t.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|t| {
+            Some(TodoItem {
+                content: t.get("content")?.as_str()?.to_string(),
+                status: t
+                    .get("status")
+                    .and_then(|s| s.as_str())
+                    .unwrap_or("pending")
+                    .to_string(),
+                active_form: t
+                    .get("activeForm")
+                    .and_then(|s| s.as_str())
+                    .map(|s| s.to_string()),
+                previous_status: None,
+            })
+        })
+        .collect()
+}
+
+/// Position of the row that stands for an empty version of a list, so that
+/// clearing the list replaces the previous version.
+const CLEARED_POSITION: i64 = -1;
+
+/// Store a new version of a session's todo list, recording each item's
+/// status in the previous version so transitions can be followed.
+/// Returns the version number, or `None` if the list is unchanged.
+pub fn record_list(db: &Db, session_id: &str, items: &[TodoItem]) -> Result<Option<i64>> {
+    let previous = current_list(db, session_id)?;
+    let unchanged = previous.len() == items.len()
+        && previous
+            .iter()
+            .zip(items)
+            .all(|(a, b)| a.content == b.content && a.status == b.status);
+    if unchanged {
+        return Ok(None);
+    }
+
+    let conn = db.conn();
+    let version: i64 = conn.query_row(
+        "SELECT COALESCE(MAX(version), 0) + 1 FROM todos WHERE session_id = ?1",
+        params![session_id],
+        |row| row.get(0),
+    )?;
+
+    for (position, item) in items.iter().enumerate() {
+        let previous_status = previous
+            .iter()
+            .find(|p| p.content == item.content)
+            .map(|p| p.status.as_str());
+        conn.execute(
+            "INSERT INTO todos (session_id, version, position, content, status, active_form, previous_status)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                session_id,
+                version,
+                position as i64,
+                item.content,
+                item.status,
+                item.active_form,
+                previous_status,
+            ],
+        )?;
+    }
+    if items.is_empty() {
+        conn.execute(
+            "INSERT INTO todos (session_id, version, position, content, status)
+             VALUES (?1, ?2, ?3, '', 'cleared')",
+            params![session_id, version, CLEARED_POSITION],
+        )?;
+    }
+    Ok(Some(version))
+}
+
+/// The latest version of a session's todo list, in list order. Empty when
+/// the list was cleared.
+pub fn current_list(db: &Db, session_id: &str) -> Result<Vec<TodoItem>> {
+    let conn = db.conn();
+    let mut stmt = conn.prepare(
+        "SELECT content, status, active_form, previous_status FROM todos
+         WHERE session_id = ?1
+           AND version = (SELECT MAX(version) FROM todos WHERE session_id = ?1)
+           AND position != ?2
+         ORDER BY position",
+    )?;
+    let rows = stmt.query_map(params![session_id, CLEARED_POSITION], |row| {
+        Ok(TodoItem {
+            content: row.get(0)?,
+            status: row.get(1)?,
+            active_form: row.get(2)?,
+            previous_status: row.get(3)?,
+        })
+    })?;
+
+    let mut items = Vec::new();
+    for row in rows {
+        items.push(row?);
+    }
+    Ok(items)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::indexer::conversation;
+
+    fn list(items: &[(&str, &str)]) -> Vec<TodoItem> {
+        let todos: Vec<Value> = items
+            .iter()
+            .map(|(c, s)| serde_json::json!({"content": c, "status": s, "activeForm": c}))
+            .collect();
+        parse_todos(&serde_json::json!({ "todos": todos }))
+    }
+
+    #[test]
+    fn test_record_list_tracks_transitions() {
+        let db = Db::open_memory().unwrap();
+        conversation::ensure_session(&db, "s1", "/nonexistent").unwrap();
+
+        let v1 = list(&[("Add schema", "in_progress"), ("Wire hook", "pending")]);
+        assert_eq!(record_list(&db, "s1", &v1).unwrap(), Some(1));
+        // Same list again is not a new version
+        assert_eq!(record_list(&db, "s1", &v1).unwrap(), None);
+
+        let v2 = list(&[("Add schema", "completed"), ("Wire hook", "in_progress"), ("Docs", "pending")]);
+        assert_eq!(record_list(&db, "s1", &v2).unwrap(), Some(2));
+
+        let current = current_list(&db, "s1").unwrap();
+        assert_eq!(current.len(), 3);
+        assert_eq!(current[0].previous_status.as_deref(), Some("in_progress"));
+        assert_eq!(current[1].previous_status.as_deref(), Some("pending"));
+        assert_eq!(current[2].previous_status, None);
+
+        // Clearing the list is a version of its own, and sticks
+        assert_eq!(record_list(&db, "s1", &[]).unwrap(), Some(3));
+        assert!(current_list(&db, "s1").unwrap().is_empty());
+        assert_eq!(record_list(&db, "s1", &[]).unwrap(), None);
+    }
+}
@@ -0,0 +1,397 @@
+use anyhow::Result;
+use rusqlite::params;
+use serde_json::Value;
+use std::io::{Read, Seek, SeekFrom};
+
+use crate::db::Db;
+
+use super::conversation;
+
+/// Replies shorter than this are narration ("Let me check the file.") and
+/// aren't worth indexing.
+const MIN_REPLY_CHARS: usize = 80;
+
+/// Longest reply stored as a single turn.
+const MAX_REPLY_CHARS: usize = 4000;
+
+/// Phrases that mark a reply as a decision rather than an explanation.
+const DECISION_MARKERS: &[&str] = &[
+    "i'll use",
+    "i will use",
+    "i decided",
+    "decided to",
+    "going with",
+    "i'll go with",
+    "instead of",
+    "rather than",
+    "i chose",
+    "opted for",
+    "the approach",
+    "trade-off",
+    "tradeoff",
+    "better to",
+];
+
+/// Classify an assistant reply as a `decision` or an `explanation` turn.
+pub fn classify_reply(text: &str) -> &'static str {
+    let lower = text.to_lowercase();
+    if DECISION_MARKERS.iter().any(|m| lower.contains(m)) {
+        "decision"
+    } else {
+        "explanation"
+    }
+}
+
+/// Read the complete lines appended to a transcript since `offset`.
+///
+/// Returns the lines and the offset just past the last complete line, so a
+/// line still being written is picked up by the next pass.
+pub fn read_new_lines(path: &str, offset: u64) -> Result<(Vec<String>, u64)> {
+    let mut file = std::fs::File::open(path)?;
+    let len = file.metadata()?.len();
+    // Transcript was replaced or truncated; start over
+    let offset = if offset > len { 0 } else { offset };
+
+    file.seek(SeekFrom::Start(offset))?;
+    let mut buf = Vec::new();
+    file.read_to_end(&mut buf)?;
+
+    let Some(last_newline) = buf.iter().rposition(|&b| b == b'\n') else {
+        return Ok((Vec::new(), offset));
+    };
+    let complete = String::from_utf8_lossy(&buf[..last_newline]);
+    let lines = complete
+        .lines()
+        .filter(|l| !l.trim().is_empty())
+        .map(|l| l.to_string())
+        .collect();
+    Ok((lines, offset + last_newline as u64 + 1))
+}
+
+/// Convert a transcript timestamp (RFC 3339) to the database format.
+pub fn db_timestamp(entry: &Value) -> Option<String> {
+    let ts = entry.get("timestamp")?.as_str()?;
+    let parsed = chrono::DateTime::parse_from_rfc3339(ts).ok()?;
+    Some(
+        parsed
+            .with_timezone(&chrono::Utc)
+            .format("%Y-%m-%d %H:%M:%S")
+            .to_string(),
+    )
+}
+
+/// Extract the text of an assistant message entry, skipping subagent
+/// (sidechain) messages and synthetic API error replies.
+pub fn assistant_text(entry: &Value) -> Option<String> {
+    if entry.get("type")?.as_str()? != "assistant" {
+        return None;
+    }
+    let api_error = entry
+        .get("isApiErrorMessage")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false);
+    if is_sidechain(entry) || api_error {
+        return None;
+    }
+
+    let blocks = entry.get("message")?.get("content")?.as_array()?;
+    let text = blocks
+        .iter()
+        .filter(|b| b.get("type").and_then(|t| t.as_str()) == Some("text"))
+        .filter_map(|b| b.get("text").and_then(|t| t.as_str()))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let text = text.trim();
+    if text.is_empty() {
+        None
+    } else {
+        Some(text.to_string())
+    }
+}
+
+/// Whether an entry belongs to a subagent (sidechain) conversation.
+fn is_sidechain(entry: &Value) -> bool {
+    entry
+        .get("isSidechain")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false)
+}
+
+/// Extract the prompt text of a user entry typed by the user. Tool results,
+/// meta messages (caveats, injected context) and slash-command plumbing are
+/// skipped.
+pub fn user_prompt(entry: &Value) -> Option<String> {
+    if entry.get("type")?.as_str()? != "user" || is_sidechain(entry) {
+        return None;
+    }
+    let flag = |key: &str| entry.get(key).and_then(|v| v.as_bool()).unwrap_or(false);
+    if flag("isMeta") || flag("isCompactSummary") {
+        return None;
+    }
+
+    let content = entry.get("message")?.get("content")?;
+    let text = match content {
+        Value::String(s) => s.clone(),
+        Value::Array(blocks) => {
+            if blocks
+                .iter()
+                .any(|b| b.get("type").and_then(|t| t.as_str()) == Some("tool_result"))
+            {
+                return None;
+            }
+            blocks
+                .iter()
+                .filter(|b| b.get("type").and_then(|t| t.as_str()) == Some("text"))
+                .filter_map(|b| b.get("text").and_then(|t| t.as_str()))
+                .collect::<Vec<_>>()
+                .join("\n")
+        }
+        _ => return None,
+    };
+
+    let text = text.trim();
+    if text.is_empty() || text.starts_with("<command-") || text.starts_with("<local-command-") {
+        return None;
+    }
+    Some(text.to_string())
+}
+
+/// The first session id in a transcript other than `session_id`. A resumed
+/// or forked session's transcript starts with the parent's messages, which
+/// keep the parent's session id.
+pub fn parent_session_id(path: &str, session_id: &str) -> Result<Option<String>> {
+    let file = std::fs::File::open(path)?;
+    for line in std::io::BufRead::lines(std::io::BufReader::new(file)) {
+        let Ok(entry) = serde_json::from_str::<Value>(&line?) else {
+            continue;
+        };
+        match entry.get("sessionId").and_then(|v| v.as_str()) {
+            Some(id) if id != session_id => return Ok(Some(id.to_string())),
+            // Reached the session's own messages
+            Some(_) => return Ok(None),
+            None => {}
+        }
+    }
+    Ok(None)
+}
+
+/// A tool call made by the assistant, as recorded in a transcript.
+#[derive(Debug)]
+pub struct ToolCall {
+    pub id: String,
+    pub name: String,
+    pub input: Value,
+}
+
+/// Extract the tool calls of an assistant entry.
+pub fn tool_calls(entry: &Value) -> Vec<ToolCall> {
+    if entry.get("type").and_then(|t| t.as_str()) != Some("assistant") || is_sidechain(entry) {
+        return Vec::new();
+    }
+    let Some(blocks) = entry
+        .get("message")
+        .and_then(|m| m.get("content"))
+        .and_then(|c| c.as_array())
+    else {
+        return Vec::new();
+    };
+
+    blocks
+        .iter()
+        .filter(|b| b.get("type").and_then(|t| t.as_str()) == Some("tool_use"))
+        .filter_map(|b| {
+            Some(ToolCall {
+                id: b.get("id")?.as_str()?.to_string(),
+                name: b.get("name")?.as_str()?.to_string(),
+                input: b.get("input").cloned().unwrap_or(Value::Null),
+            })
+        })
+        .collect()
+}
+
+/// The outcome of a tool call, as recorded in a transcript.
+#[derive(Debug)]
+pub struct ToolResult {
+    /// What a PostToolUse hook receives as `tool_response`: the structured
+    /// `toolUseResult` when present, else the result block's content
+    pub response: Value,
+    /// The call failed (PostToolUseFailure rather than PostToolUse)
+    pub is_error: bool,
+}
+
+impl ToolResult {
+    /// The result as plain text (failure messages are strings or text blocks).
+    pub fn text(&self) -> String {
+        match &self.response {
+            Value::String(s) => s.clone(),
+            Value::Array(blocks) => blocks
+                .iter()
+                .filter_map(|b| b.get("text").and_then(|t| t.as_str()))
+                .collect::<Vec<_>>()
+                .join("\n"),
+            other => other.to_string(),
+        }
+    }
+}
+
+/// Collect tool results by tool-use id.
+pub fn tool_results(entries: &[Value]) -> std::collections::HashMap<String, ToolResult> {
+    let mut results = std::collections::HashMap::new();
+    for entry in entries {
+        if entry.get("type").and_then(|t| t.as_str()) != Some("user") {
+            continue;
+        }
+        let Some(blocks) = entry
+            .get("message")
+            .and_then(|m| m.get("content"))
+            .and_then(|c| c.as_array())
+        else {
+            continue;
+        };
+        for block in blocks {
+            if block.get("type").and_then(|t| t.as_str()) != Some("tool_result") {
+                continue;
+            }
+            let is_error = block.get("is_error").and_then(|v| v.as_bool()).unwrap_or(false);
+            let Some(id) = block.get("tool_use_id").and_then(|v| v.as_str()) else {
+                continue;
+            };
+            let response = entry
+                .get("toolUseResult")
+                .cloned()
+                .or_else(|| block.get("content").cloned())
+                .unwrap_or(Value::Null);
+            results.insert(id.to_string(), ToolResult { response, is_error });
+        }
+    }
+    results
+}
+
+/// Index a single assistant reply as an explanation or decision turn.
+/// Returns false if the reply was too short to keep.
+pub fn index_reply(
+    db: &Db,
+    session_id: &str,
+    text: &str,
+    timestamp: Option<&str>,
+) -> Result<bool> {
+    if text.chars().count() < MIN_REPLY_CHARS {
+        return Ok(false);
+    }
+    let content = if text.len() > MAX_REPLY_CHARS {
+        &text[..text.floor_char_boundary(MAX_REPLY_CHARS)]
+    } else {
+        text
+    };
+
+    let turn_id = conversation::index_turn(
+        db,
+        session_id,
+        "assistant",
+        classify_reply(content),
+        content,
+        None,
+        &[],
+    )?;
+    if let Some(ts) = timestamp {
+        conversation::set_turn_timestamp(db, turn_id, ts)?;
+    }
+    Ok(true)
+}
+
+/// Index assistant replies appended to a session's transcript since the
+/// last pass. The byte offset is stored per session so repeated Stop hooks
+/// never index a reply twice.
+pub fn index_new_replies(db: &Db, session_id: &str, transcript_path: &str) -> Result<usize> {
+    let offset: i64 = {
+        let conn = db.conn();
+        conn.query_row(
+            "SELECT COALESCE(transcript_offset, 0) FROM sessions WHERE id = ?1",
+            params![session_id],
+            |row| row.get(0),
+        )?
+    };
+
+    let (lines, new_offset) = read_new_lines(transcript_path, offset as u64)?;
+
+    let mut indexed = 0;
+    for line in &lines {
+        let Ok(entry) = serde_json::from_str::<Value>(line) else {
+            continue;
+        };
+        if let Some(text) = assistant_text(&entry) {
+            if index_reply(db, session_id, &text, db_timestamp(&entry).as_deref())? {
+                indexed += 1;
+            }
+        }
+    }
+
+    let conn = db.conn();
+    conn.execute(
+        "UPDATE sessions SET transcript_offset = ?2 WHERE id = ?1",
+        params![session_id, new_offset as i64],
+    )?;
+    Ok(indexed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assistant_line(text: &str) -> String {
+        serde_json::json!({
+            "type": "assistant",
+            "timestamp": "2025-01-02T03:04:05.000Z",
+            "message": {"role": "assistant", "content": [{"type": "text", "text": text}]}
+        })
+        .to_string()
+    }
+
+    #[test]
+    fn test_index_new_replies_is_incremental() {
+        let db = Db::open_memory().unwrap();
+        conversation::ensure_session(&db, "s1", "/nonexistent").unwrap();
+
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("transcript.jsonl");
+        let path_str = path.to_string_lossy().to_string();
+        let decision = "I'll use a byte offset per session instead of re-reading the whole transcript, since transcripts grow large.";
+        std::fs::write(&path, format!("{}\n{}\n", assistant_line(decision), assistant_line("Let me check."))).unwrap();
+
+        assert_eq!(index_new_replies(&db, "s1", &path_str).unwrap(), 1);
+        // Nothing new: nothing indexed
+        assert_eq!(index_new_replies(&db, "s1", &path_str).unwrap(), 0);
+
+        let explanation = "The parser walks the tree once and records every call expression with the line it appears on, keyed by name.";
+        let mut f = std::fs::OpenOptions::new().append(true).open(&path).unwrap();
+        std::io::Write::write_all(&mut f, format!("{}\n", assistant_line(explanation)).as_bytes()).unwrap();
+        assert_eq!(index_new_replies(&db, "s1", &path_str).unwrap(), 1);
+
+        let conn = db.conn();
+        let types: Vec<(String, String)> = conn
+            .prepare("SELECT turn_type, timestamp FROM turns ORDER BY id")
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .map(|r| r.unwrap())
+            .collect();
+        assert_eq!(types.len(), 2);
+        assert_eq!(types[0].0, "decision");
+        assert_eq!(types[1].0, "explanation");
+        assert_eq!(types[0].1, "2025-01-02 03:04:05");
+    }
+
+    #[test]
+    fn test_parent_session_id_from_copied_history() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("fork.jsonl");
+        let path_str = path.to_string_lossy().to_string();
+        let line = |sid: &str| serde_json::json!({"type": "user", "sessionId": sid}).to_string();
+        std::fs::write(&path, format!("{{\"type\":\"summary\"}}\n{}\n{}\n", line("parent"), line("child"))).unwrap();
+
+        assert_eq!(parent_session_id(&path_str, "child").unwrap().as_deref(), Some("parent"));
+        // Resumed in place: the transcript starts with the session's own messages
+        std::fs::write(&path, format!("{}\n", line("child"))).unwrap();
+        assert_eq!(parent_session_id(&path_str, "child").unwrap(), None);
+    }
+}
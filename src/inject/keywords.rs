@@ -0,0 +1,41 @@
+//! Keyword extraction shared by recall, ranking and subagent briefings.
+
+/// Extract meaningful search keywords from a prompt.
+///
+/// Filters out English stop words and common code-exploration boilerplate
+/// (e.g., "find", "function", "file") to leave domain-specific terms.
+pub fn extract_keywords(prompt: &str) -> Vec<String> {
+    const STOP_WORDS: &[&str] = &[
+        // English
+        "the", "a", "an", "and", "or", "but", "in", "on", "at", "to", "for",
+        "of", "with", "by", "from", "as", "is", "was", "are", "be", "been",
+        "being", "have", "has", "had", "do", "does", "did", "will", "would",
+        "could", "should", "may", "might", "shall", "can", "need", "must",
+        "it", "its", "this", "that", "these", "those", "you", "we", "they",
+        "me", "him", "her", "them", "my", "your", "his", "our", "their",
+        "what", "which", "who", "when", "where", "how", "not", "no", "nor",
+        "if", "then", "else", "so", "than", "too", "very", "just", "about",
+        "up", "out", "all", "any", "each", "every", "both", "few", "more",
+        "most", "other", "some", "such", "only", "into", "also", "well",
+        // Code exploration boilerplate
+        "find", "search", "look", "show", "list", "get", "check", "see",
+        "file", "files", "code", "codebase", "project", "directory",
+        "function", "functions", "class", "classes", "method", "methods",
+        "struct", "structs", "implementation", "implementations",
+        "module", "modules", "related", "relevant", "existing", "current",
+        "using", "used", "understand", "understanding", "want", "need",
+        "return", "returns", "here", "there", "between", "within",
+        "across", "through", "whether", "including", "like", "use",
+    ];
+
+    let stop_set: std::collections::HashSet<&str> = STOP_WORDS.iter().copied().collect();
+
+    prompt
+        .split(|c: char| !c.is_alphanumeric() && c != '_')
+        .filter(|w| w.len() > 2)
+        .map(|w| w.to_lowercase())
+        .filter(|w| !stop_set.contains(w.as_str()))
+        .collect::<std::collections::HashSet<_>>()
+        .into_iter()
+        .collect()
+}
@@ -1,15 +1,19 @@
+pub mod keywords;
 pub mod ranking;
+pub mod recall;
+pub mod threads;
+pub mod tokens;
 
 use anyhow::Result;
 
 use crate::db::Db;
 use crate::db::search;
-use crate::indexer::plans;
+use crate::indexer::{plans, subagents, todos};
 
-/// Maximum characters for injected context.
-/// ~4 chars per token, aim for ~4000 tokens = 16000 chars.
-const COMPACT_BUDGET: usize = 16_000;
-const STARTUP_BUDGET: usize = 8_000;
+use self::tokens::{estimate, truncate};
+
+/// Latest user requests that make up the relevance query after compaction.
+const QUERY_REQUESTS: usize = 3;
 
 const HEADER: &str = "\
 [ClaudeRLM] You have persistent project memory powered by ClaudeRLM. \
@@ -21,6 +25,10 @@ You have MCP tools to search your memory:\n\
 - memory_decisions: Recall why certain choices were made\n\
 - memory_files: See change history for specific files\n\
 - memory_symbols: Query code structure (functions, classes, structs)\n\
+- memory_symbol_context: One symbol's source, edit history, callers and callees\n\
+- memory_blame: Why a file region looks like it does (edits, requests, commits)\n\
+- memory_errors: How a similar error was resolved before\n\
+- memory_sessions: List past sessions or replay one as a timeline\n\
 \n\
 Use these proactively. Before starting a task, check if you've worked on \
 something similar before. When the user references past work, search your \
@@ -32,35 +40,70 @@ Briefly greet the user and let them know project memory is loaded. Mention \
 any notable context from the sections below (recent sessions, knowledge, \
 git changes) if present.\n\n";
 
+const CLEAR_HEADER: &str = "\
+[ClaudeRLM] The conversation was cleared. Project memory is still indexed \
+and searchable with the memory_* MCP tools (memory_search, memory_decisions, \
+memory_files, memory_sessions, ...). The cleared conversation is not repeated \
+here; search memory only if the user refers back to it.\n\n";
+
+/// An injection's sections, in order, for previewing what a builder emits.
+pub struct Preview {
+    /// The emitted context is these joined by newlines
+    pub sections: Vec<String>,
+    /// The turns considered for ranked selection and what became of each
+    /// (after compaction only)
+    pub explanations: Vec<ranking::Explanation>,
+}
+
+impl Preview {
+    pub fn context(&self) -> String {
+        self.sections.join("\n")
+    }
+}
+
 /// Build context to inject at session startup.
 /// Includes: project structure, recent session summaries, active knowledge.
 /// Also checks for a version-updated marker and injects a notice if present.
-pub fn build_startup_context(db: &Db) -> Result<String> {
+pub fn build_startup_context(db: &Db, session_id: &str) -> Result<String> {
+    let updated = crate::update::check_version_updated();
+    Ok(startup_sections(db, session_id, updated)?.context())
+}
+
+/// The startup context's sections, leaving the version-updated marker in
+/// place for the next real session start.
+pub fn preview_startup_context(db: &Db, session_id: &str) -> Result<Preview> {
+    startup_sections(db, session_id, crate::update::pending_version_update())
+}
+
+fn startup_sections(db: &Db, session_id: &str, updated_version: Option<String>) -> Result<Preview> {
+    // Lookups that lock the connection themselves go before `conn` is held
+    let active_plan = plans::active_plan(db, session_id);
     let conn = db.conn();
     let mut parts: Vec<String> = vec![HEADER.to_string()];
-    let mut budget_remaining = STARTUP_BUDGET - HEADER.len();
+    let mut budget_remaining =
+        tokens::budget(&db.project_dir(), "startup").saturating_sub(estimate(HEADER));
 
     // Check if the binary was updated in a previous session
-    if let Some(version) = crate::update::check_version_updated() {
+    if let Some(version) = updated_version {
         let notice = format!(
             "**ClaudeRLM has been updated to v{}. This new version takes effect this session. Please let the user know.**\n\n",
             version
         );
-        budget_remaining = budget_remaining.saturating_sub(notice.len());
+        budget_remaining = budget_remaining.saturating_sub(estimate(&notice));
         parts.push(notice);
     }
 
     // 0. Active plan (highest priority — crash recovery)
-    if let Ok(Some(plan)) = plans::active_plan(&db) {
+    if let Ok(Some(plan)) = active_plan {
         let section = format_plan_section(&plan, budget_remaining);
-        budget_remaining = budget_remaining.saturating_sub(section.len());
+        budget_remaining = budget_remaining.saturating_sub(estimate(&section));
         parts.push(section);
     }
 
     // 1. Codebase map (symbols grouped by file) or fallback to stats
     let map_section = format_codebase_map(&conn, &db.project_dir(), budget_remaining / 2)?;
     if !map_section.is_empty() {
-        budget_remaining = budget_remaining.saturating_sub(map_section.len());
+        budget_remaining = budget_remaining.saturating_sub(estimate(&map_section));
         parts.push(map_section);
     }
 
@@ -77,39 +120,115 @@ pub fn build_startup_context(db: &Db) -> Result<String> {
                 &s.id[..id_end],
                 s.started_at,
                 ended,
-                truncate(summary, 200)
+                truncate(summary, 50)
             );
-            if section.len() + entry.len() > budget_remaining / 2 {
+            if estimate(&section) + estimate(&entry) > budget_remaining / 2 {
                 break;
             }
             section.push_str(&entry);
         }
-        budget_remaining = budget_remaining.saturating_sub(section.len());
+        budget_remaining = budget_remaining.saturating_sub(estimate(&section));
         parts.push(section);
     }
 
-    // 3. Recent git/file catch-up (if from this session start, within last 30 seconds)
+    // 3. Git/file catch-up run by this session's start (not another
+    //    window's, which may have started moments ago)
     {
         let mut stmt = conn.prepare(
             "SELECT content FROM turns
              WHERE turn_type IN ('git_catchup', 'file_catchup')
+               AND session_id = ?1
                AND timestamp >= datetime('now', '-30 seconds')
              ORDER BY timestamp DESC
              LIMIT 1",
         )?;
 
         let catchup: Option<String> = stmt
-            .query_row([], |row| row.get(0))
+            .query_row([session_id], |row| row.get(0))
             .ok();
 
         if let Some(content) = catchup {
-            let section = format!("## Recent Git Changes\n{}\n", truncate(&content, 800));
-            budget_remaining = budget_remaining.saturating_sub(section.len());
+            let section = format!("## Recent Git Changes\n{}\n", truncate(&content, 200));
+            budget_remaining = budget_remaining.saturating_sub(estimate(&section));
             parts.push(section);
         }
     }
 
     // 4. Active knowledge (decisions, conventions, preferences)
+    let knowledge_section = format_knowledge_section(&conn, budget_remaining)?;
+    if !knowledge_section.is_empty() {
+        parts.push(knowledge_section);
+    }
+
+    Ok(Preview {
+        sections: parts,
+        explanations: Vec::new(),
+    })
+}
+
+/// Build context to inject after `/clear`.
+///
+/// The user just discarded the conversation on purpose, so nothing from the
+/// cleared session is repeated: no activity, no plan it created, and it's
+/// left out of the recent sessions. What remains is project knowledge and
+/// a pointer to the memory tools.
+pub fn build_clear_context(
+    db: &Db,
+    session_id: &str,
+    cleared_session: Option<&str>,
+) -> Result<String> {
+    // Lookups that lock the connection themselves go before `conn` is held
+    let active_plan = plans::active_plan(db, session_id);
+    let conn = db.conn();
+    let mut parts: Vec<String> = vec![CLEAR_HEADER.to_string()];
+    let mut budget_remaining =
+        tokens::budget(&db.project_dir(), "clear").saturating_sub(estimate(CLEAR_HEADER));
+
+    // Active plan, unless it belongs to the conversation just cleared
+    if let Ok(Some(plan)) = active_plan {
+        if Some(plan.session_id.as_str()) != cleared_session {
+            let section = format_plan_section(&plan, budget_remaining / 2);
+            budget_remaining = budget_remaining.saturating_sub(estimate(&section));
+            parts.push(section);
+        }
+    }
+
+    // Other recent sessions, one line each
+    let sessions: Vec<_> = search::recent_sessions(&conn, 5)?
+        .into_iter()
+        .filter(|s| s.id != session_id && Some(s.id.as_str()) != cleared_session)
+        .take(3)
+        .collect();
+    if !sessions.is_empty() {
+        let mut section = String::from("## Other Recent Sessions\n");
+        for s in &sessions {
+            let id_end = s.id.floor_char_boundary(8.min(s.id.len()));
+            let entry = format!(
+                "- {} ({}): {}\n",
+                &s.id[..id_end],
+                s.started_at,
+                truncate(s.summary.as_deref().unwrap_or("(no summary)"), 30)
+            );
+            if estimate(&section) + estimate(&entry) > budget_remaining / 3 {
+                break;
+            }
+            section.push_str(&entry);
+        }
+        budget_remaining = budget_remaining.saturating_sub(estimate(&section));
+        parts.push(section);
+    }
+
+    let knowledge_section = format_knowledge_section(&conn, budget_remaining)?;
+    if !knowledge_section.is_empty() {
+        parts.push(knowledge_section);
+    }
+
+    Ok(parts.join("\n"))
+}
+
+/// Format active knowledge (decisions, conventions, preferences, ...) by
+/// category within `budget` tokens. Empty if there is none.
+fn format_knowledge_section(conn: &rusqlite::Connection, budget: usize) -> Result<String> {
     let knowledge_categories = [
         "decision",
         "preference",
@@ -142,9 +261,9 @@ pub fn build_startup_context(db: &Db) -> Result<String> {
                     "- **{}** ({:.0}%): {}\n",
                     subject,
                     confidence * 100.0,
-                    truncate(content, 150)
+                    truncate(content, 40)
                 );
-                if knowledge_section.len() + entry.len() > budget_remaining {
+                if estimate(&knowledge_section) + estimate(&entry) > budget {
                     break;
                 }
                 knowledge_section.push_str(&entry);
@@ -152,11 +271,10 @@ pub fn build_startup_context(db: &Db) -> Result<String> {
         }
     }
 
-    if !knowledge_section.is_empty() {
-        parts.push(format!("## Project Knowledge\n{}", knowledge_section));
+    if knowledge_section.is_empty() {
+        return Ok(String::new());
     }
-
-    Ok(parts.join("\n"))
+    Ok(format!("## Project Knowledge\n{}", knowledge_section))
 }
 
 /// Build context to inject after compaction.
@@ -168,41 +286,102 @@ pub fn build_startup_context(db: &Db) -> Result<String> {
 /// 3. Key decisions made in this session
 /// 4. Recent code changes (what was done, not just file names)
 /// 5. Any checkpoint summaries from PreCompact
+/// 6. Open threads: failing commands, TODO/FIXME lines added by edits,
+///    untouched plan targets
+/// 7. The open items of the current todo list
 pub fn build_compact_context(db: &Db, session_id: &str) -> Result<String> {
+    Ok(preview_compact_context(db, session_id)?.context())
+}
+
+/// The post-compaction context's sections, with the score and fate of each
+/// turn considered for ranked selection.
+pub fn preview_compact_context(db: &Db, session_id: &str) -> Result<Preview> {
+    // Lookups that lock the connection themselves go before `conn` is held
+    let active_plan = plans::active_plan(db, session_id).ok().flatten();
+    let todo_items = todos::current_list(db, session_id)?;
+    let with_subagents = subagents::include_activity(&db.project_dir(), "compact");
+    let budget = tokens::budget(&db.project_dir(), "compact");
     let conn = db.conn();
     let mut parts: Vec<String> = vec![HEADER.to_string()];
 
     // Active plan (must survive compaction)
-    if let Ok(Some(plan)) = plans::active_plan(&db) {
-        parts.push(format_plan_section(&plan, COMPACT_BUDGET / 4));
+    if let Some(plan) = &active_plan {
+        parts.push(format_plan_section(plan, budget / 4));
+    }
+
+    // Current todo list: what's left, verbatim
+    let open_items: Vec<&todos::TodoItem> = todo_items
+        .iter()
+        .filter(|t| t.status != "completed")
+        .collect();
+    if !open_items.is_empty() {
+        let done = todo_items.len() - open_items.len();
+        let mut section = format!(
+            "## Current Tasks ({} of {} done)\n",
+            done,
+            todo_items.len()
+        );
+        for t in &open_items {
+            let marker = if t.status == "in_progress" { "[in progress]" } else { "[pending]" };
+            section.push_str(&format!("- {} {}\n", marker, t.content));
+        }
+        parts.push(section);
     }
 
     // Get the active file set for file-affinity scoring
     let active_files = search::active_files(&conn, session_id, 20)?;
 
-    // Get all turns from this session
-    let all_turns = search::session_turns(&conn, session_id)?;
+    // Get all turns from this session (and its subagents, if configured;
+    // their reports are always there as summary turns)
+    let all_turns = if with_subagents {
+        search::session_turns_with_subagents(&conn, session_id)?
+    } else {
+        search::session_turns(&conn, session_id)?
+    };
 
     if all_turns.is_empty() {
-        return Ok(String::new());
+        return Ok(Preview {
+            sections: Vec::new(),
+            explanations: Vec::new(),
+        });
     }
 
-    // 1. Checkpoint summaries (generated by PreCompact) — these are gold
-    let checkpoints: Vec<&search::TurnSearchResult> = all_turns
+    // 1. Checkpoint summaries (generated by PreCompact) — these are gold.
+    //    An LLM checkpoint builds on the ones before it, so earlier
+    //    checkpoints are left out once there is one.
+    let mut checkpoints: Vec<&search::TurnSearchResult> = all_turns
         .iter()
         .filter(|t| t.turn_type == "checkpoint")
         .collect();
+    if let Some(latest) = checkpoints
+        .iter()
+        .rposition(|cp| cp.content.starts_with(crate::hooks::compact::LLM_CHECKPOINT_HEADER))
+    {
+        checkpoints.drain(..latest);
+    }
 
     if !checkpoints.is_empty() {
         let mut section = String::from("## Session Checkpoint\n");
         for cp in &checkpoints {
-            section.push_str(&truncate(&cp.content, 2000));
+            section.push_str(&truncate(&cp.content, 750));
             section.push('\n');
         }
         parts.push(section);
     }
 
-    // 2. All user requests (these define the task — never skip these)
+    // 2. Open threads: what was left unfinished
+    let threads = threads::open_threads_section(
+        &conn,
+        &all_turns,
+        active_plan.as_ref(),
+        &db.project_dir(),
+        budget / 8,
+    )?;
+    if !threads.is_empty() {
+        parts.push(threads);
+    }
+
+    // 3. All user requests (these define the task — never skip these)
     let requests: Vec<&search::TurnSearchResult> = all_turns
         .iter()
         .filter(|t| t.turn_type == "request")
@@ -211,12 +390,23 @@ pub fn build_compact_context(db: &Db, session_id: &str) -> Result<String> {
     if !requests.is_empty() {
         let mut section = String::from("## User Requests\n");
         for r in &requests {
-            section.push_str(&format!("{}. {}\n", r.turn_number, truncate(&r.content, 300)));
+            section.push_str(&format!("{}. {}\n", r.turn_number, truncate(&r.content, 75)));
         }
         parts.push(section);
     }
 
-    // 3. Files being worked on with their change summaries
+    // What the session is about now, for relevance ranking below: the
+    // latest requests and the checkpoint
+    let query_texts: Vec<&str> = requests
+        .iter()
+        .rev()
+        .take(QUERY_REQUESTS)
+        .map(|r| r.content.as_str())
+        .chain(checkpoints.last().map(|cp| &cp.content[..cp.content.floor_char_boundary(2000)]))
+        .collect();
+    let query = ranking::relevance_query(&query_texts);
+
+    // 4. Files being worked on with their change summaries
     if !active_files.is_empty() {
         let mut section = String::from("## Active Files\n");
         for file in &active_files {
@@ -225,25 +415,30 @@ pub fn build_compact_context(db: &Db, session_id: &str) -> Result<String> {
         parts.push(section);
     }
 
-    // 4. Use ranked retrieval for the remaining budget
+    // 5. Use ranked retrieval for the remaining budget
     //    Filter out requests and checkpoints (already included above)
-    let current_size: usize = parts.iter().map(|p| p.len()).sum();
-    let remaining_budget = COMPACT_BUDGET.saturating_sub(current_size);
+    let current_size: usize = parts.iter().map(|p| estimate(p)).sum();
+    let remaining_budget = budget.saturating_sub(current_size);
 
     let rankable_turns: Vec<search::TurnSearchResult> = all_turns
         .into_iter()
         .filter(|t| t.turn_type != "request" && t.turn_type != "checkpoint")
         .collect();
 
-    if !rankable_turns.is_empty() && remaining_budget > 200 {
-        let ranked_context =
-            ranking::ranked_select(&rankable_turns, &active_files, remaining_budget);
+    let mut explanations = Vec::new();
+    if !rankable_turns.is_empty() && remaining_budget > 50 {
+        let (ranked_context, explained) =
+            ranking::explain_select(&rankable_turns, &active_files, &query, remaining_budget);
+        explanations = explained;
         if !ranked_context.is_empty() {
             parts.push(format!("## Session Activity\n{}", ranked_context));
         }
     }
 
-    Ok(parts.join("\n"))
+    Ok(Preview {
+        sections: parts,
+        explanations,
+    })
 }
 
 /// Format a codebase map showing symbols grouped by file.
@@ -319,7 +514,7 @@ fn format_codebase_map(
         }
         line.push('\n');
 
-        if section.len() + line.len() > budget {
+        if estimate(&section) + estimate(&line) > budget {
             break;
         }
         section.push_str(&line);
@@ -358,15 +553,6 @@ fn make_relative(path: &str, prefix: &str) -> String {
         .to_string()
 }
 
-fn truncate(s: &str, max: usize) -> String {
-    if s.len() <= max {
-        s.to_string()
-    } else {
-        let end = s.floor_char_boundary(max);
-        format!("{}...", &s[..end])
-    }
-}
-
 fn capitalize(s: &str) -> String {
     let mut chars = s.chars();
     match chars.next() {
@@ -375,8 +561,9 @@ fn capitalize(s: &str) -> String {
     }
 }
 
-/// Format an active plan for injection into startup/compact context.
-fn format_plan_section(plan: &plans::PlanInfo, budget: usize) -> String {
+/// Format an active plan for injection into startup/compact context, within
+/// about `budget` tokens.
+pub fn format_plan_section(plan: &plans::PlanInfo, budget: usize) -> String {
     let title = plan.title.as_deref().unwrap_or("Untitled Plan");
     let mut section = format!(
         "## Active Plan: {} [{}]\n\
@@ -413,8 +600,8 @@ fn format_plan_section(plan: &plans::PlanInfo, budget: usize) -> String {
     }
 
     // Plan content (truncated)
-    let content_budget = budget.saturating_sub(section.len()).min(2000);
-    if content_budget > 100 {
+    let content_budget = budget.saturating_sub(estimate(&section)).min(500);
+    if content_budget > 25 {
         section.push_str("\nPlan content:\n");
         section.push_str(&truncate(&plan.content, content_budget));
         section.push('\n');
@@ -422,3 +609,40 @@ fn format_plan_section(plan: &plans::PlanInfo, budget: usize) -> String {
 
     section
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::indexer::conversation;
+
+    #[test]
+    fn test_clear_context_leaves_out_cleared_session() {
+        let db = Db::open_memory().unwrap();
+        for (id, summary) in [("earlier-session", "Added the parser"), ("cleared-session", "Lexer rewrite")] {
+            conversation::ensure_session(&db, id, "/p").unwrap();
+            conversation::index_turn(&db, id, "user", "request", summary, None, &[]).unwrap();
+            conversation::end_session(&db, id, Some(summary)).unwrap();
+        }
+        plans::upsert_plan(&db, "cleared-session", "/p/.claude/plans/lexer.md", "# Lexer rewrite\n").unwrap();
+        db.conn()
+            .execute(
+                "INSERT INTO knowledge (category, subject, content) VALUES ('convention', 'errors', 'Use anyhow for errors.')",
+                [],
+            )
+            .unwrap();
+
+        conversation::ensure_session(&db, "new-session", "/p").unwrap();
+        conversation::record_start(&db, "new-session", "clear", Some("cleared-session")).unwrap();
+        let context = build_clear_context(&db, "new-session", Some("cleared-session")).unwrap();
+
+        assert!(context.starts_with(CLEAR_HEADER));
+        assert!(context.contains("Added the parser"), "{}", context);
+        assert!(context.contains("Use anyhow for errors."), "{}", context);
+        // Neither the cleared conversation nor its plan comes back
+        assert!(!context.contains("cleared-"), "{}", context);
+        assert!(!context.contains("Lexer rewrite"), "{}", context);
+
+        let unfiltered = build_clear_context(&db, "new-session", None).unwrap();
+        assert!(unfiltered.contains("## Active Plan: Lexer rewrite"), "{}", unfiltered);
+    }
+}
@@ -1,9 +1,117 @@
+//! Ranked selection of turns for context injection.
+//!
+//! Each turn is scored as
+//!
+//! ```text
+//! score = type_weight × recency × file_affinity × length_bonus
+//!         × (1 + RELEVANCE_WEIGHT × relevance)
+//! ```
+//!
+//! where `relevance` is the turn's BM25 score against a query (the latest
+//! user requests and the checkpoint, for compaction), divided by the best
+//! score among the candidates so it falls in [0, 1]. With no query, or no
+//! matching turn, the last factor is 1 and ranking falls back to type,
+//! recency and file affinity alone.
+
+use std::collections::{HashMap, HashSet};
+
 use crate::db::search::TurnSearchResult;
 
+use super::keywords::extract_keywords;
+use super::tokens::{estimate, truncate};
+
+/// How much query relevance can lift a turn: the best-matching turn scores
+/// up to `1 + RELEVANCE_WEIGHT` times what it would without a query.
+const RELEVANCE_WEIGHT: f64 = 1.5;
+
+/// BM25 term-frequency saturation.
+const BM25_K1: f64 = 1.2;
+
+/// BM25 document-length normalization.
+const BM25_B: f64 = 0.75;
+
+/// Trade-off between score and novelty when selecting turns: 1.0 is plain
+/// top-k by score, lower values favor turns unlike those already selected.
+const MMR_LAMBDA: f64 = 0.7;
+
+/// Repeats of the same read, edit or command collapse into one line from
+/// this many turns on.
+const MIN_RUN: usize = 3;
+
 /// A scored turn ready for injection.
 pub struct ScoredTurn {
     pub turn: TurnSearchResult,
     pub score: f64,
+    /// Line standing in for a collapsed run of repeated turns
+    /// ("edited ranking.rs ×12 between turns 40–71"); `turn` is the run's
+    /// latest.
+    pub collapsed: Option<String>,
+    /// Ids of the earlier turns of a collapsed run
+    pub folded: Vec<i64>,
+}
+
+/// The factors of a turn's score (see the module docs).
+#[derive(Debug, Clone, Copy)]
+pub struct ScoreParts {
+    pub type_weight: f64,
+    pub recency: f64,
+    pub affinity: f64,
+    pub length_bonus: f64,
+    /// Normalized BM25 score against the query, in [0, 1]
+    pub relevance: f64,
+}
+
+impl ScoreParts {
+    pub fn score(&self) -> f64 {
+        self.type_weight
+            * self.recency
+            * self.affinity
+            * self.length_bonus
+            * (1.0 + RELEVANCE_WEIGHT * self.relevance)
+    }
+}
+
+/// Why a candidate turn was or wasn't injected.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Outcome {
+    /// Selected; `pick` is its place in selection order, from 1
+    Included { pick: usize },
+    /// Selected, and cut short to fit the budget
+    Truncated { pick: usize },
+    /// Folded into the collapsed run ending at turn number `into`
+    Collapsed { into: i64 },
+    /// Next to be picked, but `tokens` didn't fit in the `left` remaining
+    NoRoom { tokens: usize, left: usize },
+    /// The budget ran out first; `similarity` is its highest similarity to
+    /// a selected turn, which held it back
+    NotReached { similarity: f64 },
+}
+
+impl std::fmt::Display for Outcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Outcome::Included { pick } => write!(f, "included (pick {})", pick),
+            Outcome::Truncated { pick } => write!(f, "included, truncated to fit (pick {})", pick),
+            Outcome::Collapsed { into } => write!(f, "collapsed into the run ending at turn {}", into),
+            Outcome::NoRoom { tokens, left } => {
+                write!(f, "cut: next pick, but {} tokens with {} left", tokens, left)
+            }
+            Outcome::NotReached { similarity } if *similarity > 0.0 => write!(
+                f,
+                "cut: budget spent before it was picked (similarity to selected {:.2})",
+                similarity
+            ),
+            Outcome::NotReached { .. } => write!(f, "cut: budget spent before it was picked"),
+        }
+    }
+}
+
+/// A candidate turn's score and what became of it.
+#[derive(Debug)]
+pub struct Explanation {
+    pub turn: TurnSearchResult,
+    pub parts: ScoreParts,
+    pub outcome: Outcome,
 }
 
 /// Weight multiplier for different turn types.
@@ -14,11 +122,15 @@ pub fn type_weight(turn_type: &str) -> f64 {
         "git_catchup" => 1.3,
         "file_catchup" => 1.3,
         "request" => 1.3,
+        "summary" => 1.3,
         "code_edit" => 1.2,
+        "notebook_edit" => 1.2,
         "explanation" => 1.0,
         "error" => 1.0,
         "plan" => 1.0,
+        "web_fetch" => 0.6,
         "file_read" => 0.5,
+        "search" => 0.4,
         "bash_cmd" => 0.3,
         _ => 0.5,
     }
@@ -45,119 +157,414 @@ pub fn file_affinity(turn_files: &[String], context_files: &[String]) -> f64 {
     1.0 + (overlap as f64 * 0.5)
 }
 
-/// Score a turn for ranked retrieval.
+/// Score a turn for ranked retrieval (see the module docs for the formula).
+/// `relevance` is the turn's normalized BM25 score against the query.
 /// Higher score = more relevant for injection.
-fn score_turn(turn: &TurnSearchResult, context_files: &[String], now: &str) -> f64 {
+fn score_parts(turn: &TurnSearchResult, context_files: &[String], relevance: f64, now: &str) -> ScoreParts {
     let age_hours = hours_between(&turn.timestamp, now);
-    let type_w = type_weight(&turn.turn_type);
-    let recency = recency_boost(age_hours);
-    let affinity = file_affinity(&turn.files, context_files);
-
-    // Base score from type weight and recency
-    let mut score = type_w * recency * affinity;
 
     // Bonus for turns with more substantive content
-    let content_len = turn.content.len();
-    if content_len > 100 {
-        score *= 1.1;
+    let content_tokens = estimate(&turn.content);
+    let mut length_bonus = 1.0;
+    if content_tokens > 25 {
+        length_bonus *= 1.1;
+    }
+    if content_tokens > 125 {
+        length_bonus *= 1.1;
+    }
+
+    ScoreParts {
+        type_weight: type_weight(&turn.turn_type),
+        recency: recency_boost(age_hours),
+        affinity: file_affinity(&turn.files, context_files),
+        length_bonus,
+        relevance,
+    }
+}
+
+/// Build a relevance query from texts that say what the session is about
+/// (the latest requests, the checkpoint).
+pub fn relevance_query(texts: &[&str]) -> Vec<String> {
+    let mut query = Vec::new();
+    for text in texts {
+        for term in extract_keywords(text) {
+            if !query.contains(&term) {
+                query.push(term);
+            }
+        }
+    }
+    query
+}
+
+/// Lowercased terms of a turn's content and files, split like
+/// `extract_keywords` so query terms line up.
+fn tokenize(turn: &TurnSearchResult) -> Vec<String> {
+    std::iter::once(turn.content.as_str())
+        .chain(turn.files.iter().map(|f| f.as_str()))
+        .flat_map(|text| text.split(|c: char| !c.is_alphanumeric() && c != '_'))
+        .filter(|w| w.len() > 2)
+        .map(|w| w.to_lowercase())
+        .collect()
+}
+
+/// BM25 score of each turn against `query`, with the turns themselves as
+/// the corpus, divided by the best score so the result is in [0, 1].
+pub fn relevance_scores(turns: &[TurnSearchResult], query: &[String]) -> Vec<f64> {
+    if query.is_empty() || turns.is_empty() {
+        return vec![0.0; turns.len()];
     }
-    if content_len > 500 {
-        score *= 1.1;
+    let query: HashSet<&str> = query.iter().map(|q| q.as_str()).collect();
+
+    // Term frequencies of query terms only, per turn
+    let mut lengths = Vec::with_capacity(turns.len());
+    let mut freqs: Vec<HashMap<&str, usize>> = Vec::with_capacity(turns.len());
+    let mut doc_freq: HashMap<&str, usize> = HashMap::new();
+    for turn in turns {
+        let tokens = tokenize(turn);
+        lengths.push(tokens.len() as f64);
+        let mut tf: HashMap<&str, usize> = HashMap::new();
+        for token in &tokens {
+            if let Some(term) = query.get(token.as_str()) {
+                *tf.entry(term).or_default() += 1;
+            }
+        }
+        for term in tf.keys() {
+            *doc_freq.entry(term).or_default() += 1;
+        }
+        freqs.push(tf);
     }
 
-    score
+    let n = turns.len() as f64;
+    let avg_len = (lengths.iter().sum::<f64>() / n).max(1.0);
+    let scores: Vec<f64> = freqs
+        .iter()
+        .zip(&lengths)
+        .map(|(tf, len)| {
+            tf.iter()
+                .map(|(term, &f)| {
+                    let df = doc_freq[term] as f64;
+                    let idf = (1.0 + (n - df + 0.5) / (df + 0.5)).ln();
+                    let f = f as f64;
+                    idf * f * (BM25_K1 + 1.0)
+                        / (f + BM25_K1 * (1.0 - BM25_B + BM25_B * len / avg_len))
+                })
+                .sum()
+        })
+        .collect();
+
+    let best = scores.iter().cloned().fold(0.0, f64::max);
+    if best <= 0.0 {
+        return vec![0.0; turns.len()];
+    }
+    scores.into_iter().map(|s| s / best).collect()
 }
 
-/// Rank and select turns for context injection within a character budget.
+/// Rank and select turns for context injection.
 ///
-/// Returns a formatted context string that fits within `budget_chars`.
-/// Turns are selected by score, then re-ordered chronologically for coherence.
+/// Returns a formatted context string that fits within `budget_tokens`.
+/// Repeated reads, edits and commands are first collapsed into one line per
+/// target. Turns are then picked by maximal marginal relevance: each pick
+/// maximizes `MMR_LAMBDA × score − (1 − MMR_LAMBDA) × similarity` to the
+/// turns already picked (scores normalized to [0, 1]), so near-duplicates
+/// don't crowd out the rest of the session. Selected turns are re-ordered
+/// chronologically for coherence.
+/// `query` holds the terms the session is currently about (may be empty).
 pub fn ranked_select(
     turns: &[TurnSearchResult],
     context_files: &[String],
-    budget_chars: usize,
+    query: &[String],
+    budget_tokens: usize,
 ) -> String {
+    explain_select(turns, context_files, query, budget_tokens).0
+}
+
+/// `ranked_select`, also returning each turn's score components and why it
+/// was included or cut, in the order of `turns`.
+pub fn explain_select(
+    turns: &[TurnSearchResult],
+    context_files: &[String],
+    query: &[String],
+    budget_tokens: usize,
+) -> (String, Vec<Explanation>) {
     let now = chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
+    let relevance = relevance_scores(turns, query);
 
     // Score all turns
-    let mut scored: Vec<ScoredTurn> = turns
+    let parts: Vec<ScoreParts> = turns
+        .iter()
+        .zip(&relevance)
+        .map(|(t, &r)| score_parts(t, context_files, r, &now))
+        .collect();
+    let scored: Vec<ScoredTurn> = turns
         .iter()
-        .map(|t| ScoredTurn {
-            score: score_turn(t, context_files, &now),
-            turn: TurnSearchResult {
-                turn_id: t.turn_id,
-                session_id: t.session_id.clone(),
-                turn_number: t.turn_number,
-                timestamp: t.timestamp.clone(),
-                role: t.role.clone(),
-                turn_type: t.turn_type.clone(),
-                content: t.content.clone(),
-                content_summary: t.content_summary.clone(),
-                rank: t.rank,
-                files: t.files.clone(),
-            },
+        .zip(&parts)
+        .map(|(t, p)| ScoredTurn {
+            score: p.score(),
+            turn: t.clone(),
+            collapsed: None,
+            folded: Vec::new(),
         })
         .collect();
+    let candidates = collapse_runs(scored);
+    let selection = mmr_select(&candidates, budget_tokens);
 
-    // Sort by score descending (best first)
-    scored.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(std::cmp::Ordering::Equal));
-
-    // Select turns within budget
-    let mut selected: Vec<&ScoredTurn> = Vec::new();
-    let mut used_chars = 0;
-
-    for st in &scored {
-        let entry_size = format_turn_for_injection(&st.turn).len();
-        if used_chars + entry_size > budget_chars {
-            // Try truncating this turn's content to fit
-            let remaining = budget_chars.saturating_sub(used_chars);
-            if remaining > 100 {
-                // Worth including a truncated version
-                selected.push(st);
-                used_chars += remaining;
-            }
-            break;
+    let mut outcomes: HashMap<i64, Outcome> = HashMap::new();
+    for (i, st) in candidates.iter().enumerate() {
+        for &id in &st.folded {
+            outcomes.insert(id, Outcome::Collapsed { into: st.turn.turn_number });
         }
-        selected.push(st);
-        used_chars += entry_size;
+        let outcome = match selection.no_room {
+            Some((j, tokens, left)) if j == i => Outcome::NoRoom { tokens, left },
+            _ => Outcome::NotReached { similarity: selection.similarity[i] },
+        };
+        outcomes.insert(st.turn.turn_id, outcome);
     }
 
-    // Re-sort selected turns chronologically for coherent reading
-    selected.sort_by_key(|st| st.turn.turn_number);
+    // Re-sort selected turns chronologically for coherent reading (by id:
+    // turns may come from several sessions, e.g. subagent runs)
+    let mut selected: Vec<(usize, &ScoredTurn)> = selection
+        .picked
+        .iter()
+        .enumerate()
+        .map(|(pick, &i)| (pick + 1, &candidates[i]))
+        .collect();
+    selected.sort_by_key(|(_, st)| st.turn.turn_id);
 
     // Format the output
     let mut output = String::new();
-    for (i, st) in selected.iter().enumerate() {
-        let formatted = format_turn_for_injection(&st.turn);
+    let mut used_tokens = 0;
+    for (i, &(pick, st)) in selected.iter().enumerate() {
+        let formatted = format_scored(st);
+        let entry_tokens = estimate(&formatted);
         // Truncate the last entry if it exceeds the budget
-        if i == selected.len() - 1 && output.len() + formatted.len() > budget_chars {
-            let remaining = budget_chars.saturating_sub(output.len());
-            if remaining > 50 {
-                output.push_str(&formatted[..remaining.min(formatted.len())]);
+        let outcome = if i == selected.len() - 1 && used_tokens + entry_tokens > budget_tokens {
+            let remaining = budget_tokens.saturating_sub(used_tokens);
+            if remaining > 12 {
+                output.push_str(&truncate(&formatted, remaining));
+                output.push('\n');
+                Outcome::Truncated { pick }
+            } else {
+                Outcome::NoRoom { tokens: entry_tokens, left: remaining }
             }
         } else {
             output.push_str(&formatted);
+            used_tokens += entry_tokens;
+            Outcome::Included { pick }
+        };
+        outcomes.insert(st.turn.turn_id, outcome);
+    }
+
+    let explanations = turns
+        .iter()
+        .zip(parts)
+        .map(|(t, parts)| Explanation {
+            outcome: outcomes
+                .remove(&t.turn_id)
+                .unwrap_or(Outcome::NotReached { similarity: 0.0 }),
+            turn: t.clone(),
+            parts,
+        })
+        .collect();
+    (output, explanations)
+}
+
+/// What a repeated turn repeats: the same files read or edited, or the same
+/// command run. None for turns that never collapse.
+fn run_key(turn: &TurnSearchResult) -> Option<String> {
+    match turn.turn_type.as_str() {
+        "file_read" | "code_edit" | "notebook_edit" if !turn.files.is_empty() => {
+            let mut files = turn.files.clone();
+            files.sort();
+            files.dedup();
+            Some(format!("{}:{}", turn.turn_type, files.join("\n")))
+        }
+        "bash_cmd" => Some(format!("bash_cmd:{}", turn.content.lines().next().unwrap_or(""))),
+        _ => None,
+    }
+}
+
+/// Collapse every group of at least MIN_RUN turns with the same run key
+/// into one entry, scored as its best member and placed at its latest.
+fn collapse_runs(scored: Vec<ScoredTurn>) -> Vec<ScoredTurn> {
+    let mut groups: HashMap<String, Vec<ScoredTurn>> = HashMap::new();
+    let mut out = Vec::new();
+    for st in scored {
+        match run_key(&st.turn) {
+            Some(key) => groups.entry(key).or_default().push(st),
+            None => out.push(st),
+        }
+    }
+
+    for (_, mut group) in groups {
+        if group.len() < MIN_RUN {
+            out.extend(group);
+            continue;
         }
+        group.sort_by_key(|st| st.turn.turn_id);
+        let best = group.iter().map(|st| st.score).fold(0.0, f64::max);
+        let first = group[0].turn.turn_number;
+        let count = group.len();
+        let latest = group.remove(count - 1).turn;
+        let line = run_line(&latest, count, first);
+        out.push(ScoredTurn {
+            turn: latest,
+            score: best,
+            collapsed: Some(line),
+            folded: group.iter().map(|st| st.turn.turn_id).collect(),
+        });
     }
+    // Chronological, so ties in selection break the same way every time
+    out.sort_by_key(|st| st.turn.turn_id);
+    out
+}
 
-    output
+/// The aggregated line for a run: "edited ranking.rs ×12 between turns
+/// 40–71", with the latest edit's description.
+fn run_line(latest: &TurnSearchResult, count: usize, first_turn: i64) -> String {
+    let names: Vec<&str> = latest
+        .files
+        .iter()
+        .map(|f| f.rsplit(['/', '\\']).next().unwrap_or(f))
+        .collect();
+    let first_line = latest.content.lines().next().unwrap_or("");
+    let (verb, target) = match latest.turn_type.as_str() {
+        "file_read" => ("read", names.join(", ")),
+        "bash_cmd" => ("ran", format!("`{}`", first_line.trim_start_matches("$ "))),
+        _ => ("edited", names.join(", ")),
+    };
+    let mut line = format!(
+        "{} {} ×{} between turns {}–{}",
+        verb, target, count, first_turn, latest.turn_number
+    );
+    if verb == "edited" {
+        line.push_str(&format!(" (latest: {})", truncate(first_line, 40)));
+    }
+    line
 }
 
-/// Format a turn for injection into context.
-fn format_turn_for_injection(turn: &TurnSearchResult) -> String {
-    let type_label = match turn.turn_type.as_str() {
+/// Candidates picked by `mmr_select`, by index.
+struct Selection {
+    /// In pick order; the last may be truncated to fit
+    picked: Vec<usize>,
+    /// The pick that didn't fit: its index, tokens and the tokens left
+    no_room: Option<(usize, usize, usize)>,
+    /// Each candidate's highest similarity to a picked one
+    similarity: Vec<f64>,
+}
+
+/// Pick candidates by maximal marginal relevance (see `ranked_select`)
+/// until the budget is spent.
+fn mmr_select(candidates: &[ScoredTurn], budget_tokens: usize) -> Selection {
+    let best = candidates.iter().map(|st| st.score).fold(0.0, f64::max);
+    let norm: Vec<f64> = candidates
+        .iter()
+        .map(|st| if best > 0.0 { st.score / best } else { 0.0 })
+        .collect();
+    let features: Vec<(HashSet<String>, HashSet<&str>)> = candidates
+        .iter()
+        .map(|st| {
+            let text = st.collapsed.as_deref().unwrap_or(&st.turn.content);
+            let terms = text
+                .split(|c: char| !c.is_alphanumeric() && c != '_')
+                .filter(|w| w.len() > 2)
+                .map(|w| w.to_lowercase())
+                .collect();
+            let files = st.turn.files.iter().map(|f| f.as_str()).collect();
+            (terms, files)
+        })
+        .collect();
+
+    // Highest similarity of each candidate to anything selected so far,
+    // updated against each new pick
+    let mut max_sim = vec![0.0f64; candidates.len()];
+    let mut remaining: Vec<usize> = (0..candidates.len()).collect();
+    let mut selected = Vec::new();
+    let mut no_room = None;
+    let mut used_tokens = 0;
+    while !remaining.is_empty() {
+        let (pos, _) = remaining
+            .iter()
+            .enumerate()
+            .map(|(pos, &i)| (pos, MMR_LAMBDA * norm[i] - (1.0 - MMR_LAMBDA) * max_sim[i]))
+            .fold((0, f64::NEG_INFINITY), |acc, cur| if cur.1 > acc.1 { cur } else { acc });
+        let picked = remaining.remove(pos);
+
+        let entry_tokens = estimate(&format_scored(&candidates[picked]));
+        if used_tokens + entry_tokens > budget_tokens {
+            // Worth including a truncated version?
+            let left = budget_tokens.saturating_sub(used_tokens);
+            if left > 25 {
+                selected.push(picked);
+            } else {
+                no_room = Some((picked, entry_tokens, left));
+            }
+            break;
+        }
+        selected.push(picked);
+        used_tokens += entry_tokens;
+
+        for &i in &remaining {
+            let sim = similarity(&features[i], &features[picked]);
+            max_sim[i] = max_sim[i].max(sim);
+        }
+    }
+    Selection {
+        picked: selected,
+        no_room,
+        similarity: max_sim,
+    }
+}
+
+/// Similarity of two turns: the mean of their content-term and file-set
+/// Jaccard similarities (content alone when neither has files).
+fn similarity(a: &(HashSet<String>, HashSet<&str>), b: &(HashSet<String>, HashSet<&str>)) -> f64 {
+    let content = jaccard(&a.0, &b.0);
+    if a.1.is_empty() && b.1.is_empty() {
+        return content;
+    }
+    (content + jaccard(&a.1, &b.1)) / 2.0
+}
+
+fn jaccard<T: Eq + std::hash::Hash>(a: &HashSet<T>, b: &HashSet<T>) -> f64 {
+    let union = a.union(b).count();
+    if union == 0 {
+        return 0.0;
+    }
+    a.intersection(b).count() as f64 / union as f64
+}
+
+/// Format a candidate: its aggregated line if it's a collapsed run.
+fn format_scored(st: &ScoredTurn) -> String {
+    match &st.collapsed {
+        Some(line) => format!("- **{}**: {}\n", type_label(&st.turn.turn_type), line),
+        None => format_turn_for_injection(&st.turn),
+    }
+}
+
+/// Short label for a turn type in injected context.
+pub fn type_label(turn_type: &str) -> &str {
+    match turn_type {
         "git_catchup" => "Git",
         "file_catchup" => "Files",
         "request" => "User",
         "code_edit" => "Edit",
         "file_read" => "Read",
         "bash_cmd" => "Cmd",
+        "search" => "Search",
+        "web_fetch" => "Fetch",
+        "notebook_edit" => "Notebook",
         "decision" => "Decision",
         "checkpoint" => "Checkpoint",
         "plan" => "Plan",
+        "summary" => "Subagent",
         "error" => "Error",
-        _ => &turn.turn_type,
-    };
+        other => other,
+    }
+}
+
+/// Format a turn for injection into context.
+fn format_turn_for_injection(turn: &TurnSearchResult) -> String {
+    let type_label = type_label(&turn.turn_type);
 
     let files_str = if !turn.files.is_empty() {
         format!(" [{}]", turn.files.join(", "))
@@ -166,12 +573,7 @@ fn format_turn_for_injection(turn: &TurnSearchResult) -> String {
     };
 
     // Truncate content for injection (individual turns shouldn't dominate)
-    let content = if turn.content.len() > 800 {
-        let end = turn.content.floor_char_boundary(800);
-        format!("{}...", &turn.content[..end])
-    } else {
-        turn.content.clone()
-    };
+    let content = truncate(&turn.content, 200);
 
     format!("- **{}**{}: {}\n", type_label, files_str, content)
 }
@@ -190,3 +592,138 @@ fn hours_between(earlier: &str, later: &str) -> f64 {
         _ => 1.0, // Default to 1 hour if parsing fails
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn turn(id: i64, turn_type: &str, content: &str, hours_ago: i64) -> TurnSearchResult {
+        let timestamp = (chrono::Utc::now() - chrono::Duration::hours(hours_ago))
+            .format("%Y-%m-%d %H:%M:%S")
+            .to_string();
+        TurnSearchResult {
+            turn_id: id,
+            session_id: "s".to_string(),
+            turn_number: id,
+            timestamp,
+            role: "assistant".to_string(),
+            turn_type: turn_type.to_string(),
+            content: content.to_string(),
+            content_summary: None,
+            rank: 0.0,
+            files: Vec::new(),
+        }
+    }
+
+    fn query(terms: &[&str]) -> Vec<String> {
+        terms.iter().map(|t| t.to_string()).collect()
+    }
+
+    #[test]
+    fn test_relevance_scores() {
+        let turns = vec![
+            turn(1, "code_edit", "Edit src/lexer.rs: tokenizer handles tokenizer errors", 1),
+            turn(2, "code_edit", "Edit src/lexer.rs: rename tokenizer state", 1),
+            turn(3, "bash_cmd", "$ cargo build", 1),
+        ];
+        let scores = relevance_scores(&turns, &query(&["tokenizer", "errors"]));
+        assert_eq!(scores[0], 1.0);
+        assert!(scores[1] > 0.0 && scores[1] < scores[0]);
+        assert_eq!(scores[2], 0.0);
+
+        // No query, or nothing matching it: no turn gets a relevance boost
+        assert!(relevance_scores(&turns, &[]).iter().all(|&s| s == 0.0));
+        assert!(relevance_scores(&turns, &query(&["unrelated"])).iter().all(|&s| s == 0.0));
+    }
+
+    #[test]
+    fn test_query_lifts_relevant_turns() {
+        let turns = vec![
+            turn(1, "code_edit", "Edit src/lexer.rs: handle tokenizer errors", 5),
+            turn(2, "code_edit", "Edit src/render.rs: adjust colours", 1),
+        ];
+        // Budget (in tokens) for a single entry
+        let budget = 18;
+
+        // Without a query the more recent edit wins
+        let selected = ranked_select(&turns, &[], &[], budget);
+        assert!(selected.contains("render.rs"), "{}", selected);
+
+        // Asking about the tokenizer brings the older, matching edit forward
+        let selected = ranked_select(&turns, &[], &query(&["tokenizer"]), budget);
+        assert!(selected.contains("lexer.rs"), "{}", selected);
+
+        // Relevance multiplies the other factors rather than replacing them:
+        // a matching command still ranks below a matching decision
+        let turns = vec![
+            turn(1, "bash_cmd", "$ cargo test tokenizer", 1),
+            turn(2, "decision", "Keep the tokenizer hand-written", 1),
+        ];
+        let selected = ranked_select(&turns, &[], &query(&["tokenizer"]), budget);
+        assert!(selected.contains("hand-written"), "{}", selected);
+    }
+
+    #[test]
+    fn test_repeated_edits_collapse() {
+        let mut turns: Vec<TurnSearchResult> = (1..=12)
+            .map(|i| {
+                let mut t = turn(i, "code_edit", &format!("Edit ranking.rs: change {}", i), 1);
+                t.files = vec!["/p/src/inject/ranking.rs".to_string()];
+                t
+            })
+            .collect();
+        turns.push(turn(13, "decision", "Use MMR for selection", 1));
+
+        let selected = ranked_select(&turns, &[], &[], 4_000);
+        assert!(
+            selected.contains("edited ranking.rs ×12 between turns 1–12 (latest: Edit ranking.rs: change 12)"),
+            "{}",
+            selected
+        );
+        assert_eq!(selected.lines().count(), 2, "{}", selected);
+    }
+
+    #[test]
+    fn test_explain_select() {
+        let mut turns: Vec<TurnSearchResult> = (1..=3)
+            .map(|i| {
+                let mut t = turn(i, "code_edit", &format!("Edit lexer.rs: change {}", i), 1);
+                t.files = vec!["/p/src/lexer.rs".to_string()];
+                t
+            })
+            .collect();
+        turns.push(turn(4, "decision", "Keep the lexer hand-written", 1));
+        turns.push(turn(5, "bash_cmd", "$ cargo fmt", 48));
+
+        // Room for the run and the decision, not the old command
+        let (output, explained) = explain_select(&turns, &[], &[], 45);
+        assert_eq!(output, ranked_select(&turns, &[], &[], 45));
+        let outcomes: Vec<&Outcome> = explained.iter().map(|e| &e.outcome).collect();
+        assert_eq!(outcomes[0], &Outcome::Collapsed { into: 3 });
+        assert_eq!(outcomes[1], &Outcome::Collapsed { into: 3 });
+        assert!(matches!(outcomes[2], Outcome::Included { .. }), "{:?}", outcomes);
+        assert!(matches!(outcomes[3], Outcome::Included { .. }));
+        // The command is the next pick, and doesn't fit in what's left
+        let Outcome::NoRoom { tokens, left } = outcomes[4] else {
+            panic!("{:?}", outcomes);
+        };
+        assert!(tokens > left);
+        assert_eq!(explained[3].parts.type_weight, 1.5);
+        assert!(explained[4].parts.recency < explained[3].parts.recency);
+    }
+
+    #[test]
+    fn test_near_duplicates_give_way() {
+        let error = "cargo build failed: unresolved import mmr";
+        let turns = vec![
+            turn(1, "error", error, 1),
+            turn(2, "error", error, 1),
+            turn(3, "web_fetch", "Fetched https://docs.rs/rusqlite", 1),
+        ];
+        // Room for two entries: the second error repeats the first, so the
+        // lower-scored but different fetch is picked instead
+        let selected = ranked_select(&turns, &[], &[], 30);
+        assert_eq!(selected.matches(error).count(), 1, "{}", selected);
+        assert!(selected.contains("docs.rs"), "{}", selected);
+    }
+}
@@ -0,0 +1,287 @@
+use anyhow::Result;
+use rusqlite::Connection;
+use std::time::{Duration, Instant};
+
+use crate::db::Db;
+use crate::db::search;
+
+use super::keywords::extract_keywords;
+use super::tokens::{self, estimate, truncate};
+
+/// Minimum relevance score (share of prompt keywords a match covers) for a
+/// match to be injected.
+const RECALL_THRESHOLD: f64 = 0.5;
+
+/// A match must share at least this many keywords with the prompt.
+const MIN_MATCHED_TERMS: usize = 2;
+
+/// Coverage is measured against at most this many prompt keywords, so long
+/// prompts aren't penalized for detail.
+const MAX_SCORED_TERMS: usize = 4;
+
+/// Hard limit on time spent querying. Recall runs before every prompt, so
+/// it gives up rather than delay one.
+const RECALL_TIME_LIMIT: Duration = Duration::from_millis(250);
+
+/// Words common in prompts that say nothing about the topic
+/// ("do it the same way as last time").
+const PROMPT_STOP_WORDS: &[&str] = &[
+    "same", "way", "last", "time", "again", "before", "previous", "previously",
+    "please", "let", "make", "now", "add", "fix", "change", "update", "work",
+    "thing", "things", "done", "did", "doing", "sure", "okay", "thanks",
+];
+
+/// Build the context to inject alongside a prompt: knowledge, past requests
+/// from other sessions, and history of files the prompt mentions. Returns
+/// None when nothing clears the relevance threshold.
+///
+/// Must run before the prompt itself is indexed, or the prompt would match
+/// itself.
+pub fn build_recall_context(db: &Db, session_id: &str, prompt: &str) -> Result<Option<String>> {
+    let deadline = Instant::now() + RECALL_TIME_LIMIT;
+
+    let keywords: Vec<String> = extract_keywords(prompt)
+        .into_iter()
+        .filter(|k| !PROMPT_STOP_WORDS.contains(&k.as_str()))
+        .collect();
+    let mentioned = mentioned_files(prompt);
+    if keywords.len() < MIN_MATCHED_TERMS && mentioned.is_empty() {
+        return Ok(None);
+    }
+
+    let budget = tokens::budget(&db.project_dir(), "recall");
+    let conn = db.conn();
+    // Interrupt any query still running at the deadline
+    conn.progress_handler(1_000, Some(move || Instant::now() > deadline));
+    let mut sections = Vec::new();
+    let gathered = gather(&conn, session_id, &keywords, &mentioned, deadline, budget, &mut sections);
+    conn.progress_handler(0, None::<fn() -> bool>);
+    drop(conn);
+
+    // Out of time: keep the sections gathered so far
+    if let Err(e) = gathered {
+        eprintln!("[claude-rlm] Recall cut short: {}", e);
+    }
+
+    if sections.is_empty() {
+        return Ok(None);
+    }
+    let mut out = String::from(
+        "[ClaudeRLM] Memory related to this prompt (recalled automatically; \
+         use memory_search for more):\n\n",
+    );
+    for section in sections {
+        out.push_str(&section);
+    }
+    Ok(Some(out))
+}
+
+/// Query each source in priority order, pushing one section per source with
+/// matches. Stops at the first error (an interrupted query) or once the
+/// budget (in tokens) or deadline is spent.
+fn gather(
+    conn: &Connection,
+    session_id: &str,
+    keywords: &[String],
+    mentioned: &[String],
+    deadline: Instant,
+    budget: usize,
+    sections: &mut Vec<String>,
+) -> Result<()> {
+    let mut budget_remaining = budget;
+
+    // 1. History of files the prompt names (an exact match, no scoring)
+    let mut lines = Vec::new();
+    for name in mentioned {
+        for path in search::files_matching(conn, name, 2)? {
+            let edits: Vec<_> = search::file_history(conn, &path, 10)?
+                .into_iter()
+                .filter(|t| matches!(t.turn_type.as_str(), "code_edit" | "notebook_edit"))
+                .take(3)
+                .collect();
+            for t in edits {
+                let summary = t.content_summary.as_deref().unwrap_or(&t.content);
+                lines.push(format!(
+                    "- {} ({}): {}\n",
+                    path,
+                    t.timestamp,
+                    truncate(summary.lines().next().unwrap_or(""), 40)
+                ));
+            }
+        }
+    }
+    push_section(sections, "## File History\n", lines, &mut budget_remaining);
+    if Instant::now() > deadline || keywords.len() < MIN_MATCHED_TERMS {
+        return Ok(());
+    }
+
+    // 2. Knowledge
+    let mut scored: Vec<(f64, String)> = search::search_knowledge_any(conn, keywords, 10)?
+        .into_iter()
+        .filter_map(|k| {
+            let score = coverage(&format!("{} {}", k.subject, k.content), keywords);
+            (score >= RECALL_THRESHOLD).then(|| {
+                let line = format!(
+                    "- **{}** [{}]: {}\n",
+                    k.subject,
+                    k.category,
+                    truncate(&k.content, 75)
+                );
+                (score, line)
+            })
+        })
+        .collect();
+    scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+    let lines = scored.into_iter().take(4).map(|(_, line)| line).collect();
+    push_section(sections, "## Knowledge\n", lines, &mut budget_remaining);
+    if Instant::now() > deadline {
+        return Ok(());
+    }
+
+    // 3. Similar requests from past sessions, with the files they led to
+    let mut scored: Vec<(f64, search::TurnSearchResult)> =
+        search::past_requests(conn, keywords, session_id, 10)?
+            .into_iter()
+            .map(|r| (coverage(&r.content, keywords), r))
+            .filter(|(score, _)| *score >= RECALL_THRESHOLD)
+            .collect();
+    scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+    let mut lines = Vec::new();
+    for (_, r) in scored.into_iter().take(3) {
+        let files = search::request_followup_files(conn, &r.session_id, r.turn_number, 5)?;
+        let id_end = r.session_id.floor_char_boundary(8.min(r.session_id.len()));
+        let mut line = format!(
+            "- {} (session {}): \"{}\"\n",
+            r.timestamp,
+            &r.session_id[..id_end],
+            truncate(&r.content.replace('\n', " "), 50)
+        );
+        if !files.is_empty() {
+            line.push_str(&format!("  Edited: {}\n", files.join(", ")));
+        }
+        lines.push(line);
+    }
+    push_section(sections, "## Similar Past Requests\n", lines, &mut budget_remaining);
+
+    Ok(())
+}
+
+/// Append a section with as many lines as fit in the budget.
+fn push_section(
+    sections: &mut Vec<String>,
+    heading: &str,
+    lines: Vec<String>,
+    budget_remaining: &mut usize,
+) {
+    let mut section = String::from(heading);
+    let mut used = estimate(heading);
+    for line in lines {
+        let line_tokens = estimate(&line);
+        if used + line_tokens > *budget_remaining {
+            break;
+        }
+        section.push_str(&line);
+        used += line_tokens;
+    }
+    if section.len() > heading.len() {
+        *budget_remaining = budget_remaining.saturating_sub(used + 1);
+        section.push('\n');
+        sections.push(section);
+    }
+}
+
+/// Relevance of a match: the share of prompt keywords it contains, measured
+/// against at most MAX_SCORED_TERMS keywords. Zero below MIN_MATCHED_TERMS.
+fn coverage(text: &str, keywords: &[String]) -> f64 {
+    let lower = text.to_lowercase();
+    let matched = keywords.iter().filter(|k| lower.contains(k.as_str())).count();
+    if matched < MIN_MATCHED_TERMS {
+        return 0.0;
+    }
+    let scored = keywords.len().min(MAX_SCORED_TERMS);
+    (matched as f64 / scored as f64).min(1.0)
+}
+
+/// Paths and file names mentioned in a prompt (`src/main.rs`, `lib.rs:42`).
+fn mentioned_files(prompt: &str) -> Vec<String> {
+    let mut files = Vec::new();
+    for word in prompt.split_whitespace() {
+        let word = word.trim_matches(|c: char| "`'\"()[]<>,;!?".contains(c));
+        let word = word.trim_end_matches('.');
+        // Drop a trailing :line or :line:col
+        let word = word
+            .split(':')
+            .next()
+            .unwrap_or("");
+        if word.contains("//") {
+            continue;
+        }
+        let Some((stem, ext)) = word.rsplit_once('.') else {
+            continue;
+        };
+        let stem_name = stem.rsplit('/').next().unwrap_or(stem);
+        let is_ext = !ext.is_empty()
+            && ext.len() <= 5
+            && ext.chars().all(|c| c.is_ascii_alphanumeric())
+            && ext.chars().any(|c| c.is_ascii_alphabetic());
+        if stem_name.len() >= 2 && is_ext && !files.iter().any(|f| f == word) {
+            files.push(word.to_string());
+        }
+    }
+    files.truncate(5);
+    files
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::indexer::conversation;
+
+    #[test]
+    fn test_recall_threshold() {
+        let db = Db::open_memory().unwrap();
+        conversation::ensure_session(&db, "old", "/nonexistent").unwrap();
+        conversation::index_turn(
+            &db,
+            "old",
+            "user",
+            "request",
+            "Add a memory_blame MCP tool that explains a file region",
+            None,
+            &[],
+        )
+        .unwrap();
+        conversation::index_turn(
+            &db,
+            "old",
+            "assistant",
+            "code_edit",
+            "Edit src/server.rs",
+            None,
+            &[("/p/src/server.rs".to_string(), "edit".to_string())],
+        )
+        .unwrap();
+        conversation::ensure_session(&db, "new", "/nonexistent").unwrap();
+
+        let context = build_recall_context(&db, "new", "Add another MCP tool the same way as memory_blame")
+            .unwrap()
+            .unwrap();
+        assert!(context.contains("## Similar Past Requests"));
+        assert!(context.contains("Edited: /p/src/server.rs"));
+
+        // Mentioned file: its history, whatever the keywords
+        let context = build_recall_context(&db, "new", "what changed in server.rs?")
+            .unwrap()
+            .unwrap();
+        assert!(context.contains("## File History"));
+
+        // Weak overlap stays silent
+        assert!(build_recall_context(&db, "new", "write a haiku about a region of france")
+            .unwrap()
+            .is_none());
+        // Requests from the current session are not recalled
+        assert!(build_recall_context(&db, "old", "Add another MCP tool like memory_blame")
+            .unwrap()
+            .is_none());
+    }
+}
@@ -0,0 +1,238 @@
+//! Open threads: what a session left unfinished, for compaction recovery.
+//!
+//! Computed from turns already indexed and the active plan: commands whose
+//! last run failed, TODO/FIXME lines added by edits and still in the file,
+//! and plan targets not edited yet.
+
+use anyhow::Result;
+use rusqlite::Connection;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::db::search::{self, TurnSearchResult};
+use crate::hooks::pre_tool_use::make_relative;
+use crate::indexer::plans::PlanInfo;
+
+use super::tokens::{estimate, truncate};
+
+/// Markers of unfinished work in added lines.
+const MARKERS: &[&str] = &["TODO", "FIXME"];
+
+/// Maximum marker lines listed; the newest are kept.
+const MAX_MARKERS: usize = 8;
+
+/// The "Open Threads" section for a session's turns, within `budget`
+/// tokens. Empty when nothing is left open.
+pub fn open_threads_section(
+    conn: &Connection,
+    turns: &[TurnSearchResult],
+    plan: Option<&PlanInfo>,
+    project_dir: &str,
+    budget: usize,
+) -> Result<String> {
+    let prefix = project_dir.replace('\\', "/");
+    let prefix = prefix.trim_end_matches('/');
+    let mut items = Vec::new();
+
+    // Commands still failing, most recent last
+    for error in search::unresolved_errors(conn, turns)? {
+        items.push(format!(
+            "- Failing: {}\n",
+            truncate(&error.content, 60).replace('\n', "\n  ")
+        ));
+    }
+
+    for (file, line) in open_markers(conn, turns)? {
+        items.push(format!(
+            "- {} in {}: `{}`\n",
+            marker_in(&line).unwrap_or("TODO"),
+            make_relative(&file, prefix),
+            truncate(&line, 30)
+        ));
+    }
+
+    if let Some(plan) = plan {
+        for target in untouched_targets(plan) {
+            items.push(format!("- Plan target not edited yet: {}\n", target));
+        }
+    }
+
+    let heading = "## Open Threads\n";
+    let mut section = String::from(heading);
+    let mut used = estimate(heading);
+    for item in items {
+        let tokens = estimate(&item);
+        if used + tokens > budget {
+            break;
+        }
+        section.push_str(&item);
+        used += tokens;
+    }
+    if section.len() == heading.len() {
+        return Ok(String::new());
+    }
+    Ok(section)
+}
+
+/// TODO/FIXME lines added by the session's edits that are still in the
+/// file, as (file, trimmed line), oldest first.
+fn open_markers(conn: &Connection, turns: &[TurnSearchResult]) -> Result<Vec<(String, String)>> {
+    let mut stmt = conn.prepare("SELECT metadata FROM turns WHERE id = ?1")?;
+    let mut markers: Vec<(String, String)> = Vec::new();
+    for turn in turns.iter().filter(|t| t.turn_type == "code_edit") {
+        let metadata: Option<String> = stmt.query_row([turn.turn_id], |row| row.get(0))?;
+        let Some(tool_input) = metadata.and_then(|m| serde_json::from_str::<Value>(&m).ok()) else {
+            continue;
+        };
+        let Some(file) = tool_input.get("file_path").and_then(|v| v.as_str()) else {
+            continue;
+        };
+        for line in added_markers(&tool_input) {
+            let key = (file.to_string(), line);
+            markers.retain(|m| *m != key);
+            markers.push(key);
+        }
+    }
+
+    // A marker removed by a later edit (or by hand) is done
+    let mut contents: HashMap<String, Option<String>> = HashMap::new();
+    markers.retain(|(file, line)| {
+        contents
+            .entry(file.clone())
+            .or_insert_with(|| std::fs::read_to_string(file).ok())
+            .as_deref()
+            .is_some_and(|c| c.lines().any(|l| l.trim() == line))
+    });
+
+    let skip = markers.len().saturating_sub(MAX_MARKERS);
+    Ok(markers.split_off(skip))
+}
+
+/// Marker lines in the new text of an Edit, MultiEdit or Write call that
+/// weren't in the text it replaced.
+fn added_markers(tool_input: &Value) -> Vec<String> {
+    let text = |v: &Value, key: &str| v.get(key).and_then(|s| s.as_str()).unwrap_or("").to_string();
+    let edits = tool_input.get("edits").and_then(|v| v.as_array());
+    let replacements: Vec<(String, String)> = if let Some(edits) = edits {
+        edits
+            .iter()
+            .map(|e| (text(e, "old_string"), text(e, "new_string")))
+            .collect()
+    } else if tool_input.get("new_string").is_some() {
+        vec![(text(tool_input, "old_string"), text(tool_input, "new_string"))]
+    } else {
+        vec![(String::new(), text(tool_input, "content"))]
+    };
+
+    let mut added = Vec::new();
+    for (old, new) in &replacements {
+        for line in new.lines().map(str::trim) {
+            let is_new = !old.lines().any(|l| l.trim() == line);
+            if marker_in(line).is_some() && is_new && !added.iter().any(|a| a == line) {
+                added.push(line.to_string());
+            }
+        }
+    }
+    added
+}
+
+/// The marker a line carries, matched as a whole word (`TODO:`, `FIXME(x)`,
+/// not `TODOS` or `toDoList`).
+fn marker_in(line: &str) -> Option<&'static str> {
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+    MARKERS.iter().copied().find(|marker| {
+        line.match_indices(marker).any(|(i, _)| {
+            let before = line[..i].chars().next_back();
+            let after = line[i + marker.len()..].chars().next();
+            !before.is_some_and(is_word) && !after.is_some_and(is_word)
+        })
+    })
+}
+
+/// The plan's target files with no recorded edit. Paths match on whole
+/// components, so an edit to `foo_lexer.rs` doesn't tick off `lexer.rs`.
+fn untouched_targets(plan: &PlanInfo) -> Vec<&str> {
+    plan.target_files
+        .iter()
+        .filter(|t| {
+            !plan.progress.iter().any(|p| {
+                let (edited, target) = (Path::new(&p.file_path), Path::new(t.as_str()));
+                edited.ends_with(target) || target.ends_with(edited)
+            })
+        })
+        .map(|t| t.as_str())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::Db;
+    use crate::hooks::tool_use::{index_bash, index_edit, BashOutcome};
+    use crate::indexer::conversation;
+    use serde_json::json;
+
+    #[test]
+    fn test_open_threads() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path();
+        let project_dir = dir.to_string_lossy().to_string();
+        let file = dir.join("lexer.rs");
+        let file_path = file.to_string_lossy().to_string();
+        std::fs::write(&file, "fn lex() {\n    // TODO: handle CRLF\n}\n").unwrap();
+
+        let db = Db::open_memory().unwrap();
+        conversation::ensure_session(&db, "s1", &project_dir).unwrap();
+        let edit = |old: &str, new: &str| {
+            let input = json!({"file_path": file_path, "old_string": old, "new_string": new});
+            index_edit(&db, "s1", "Edit", Some(&input)).unwrap();
+        };
+        // Added, still in the file: open. Added then removed: done.
+        edit("fn lex() {}", "fn lex() {\n    // TODO: handle CRLF\n}");
+        edit("", "// FIXME: tabs");
+        edit("// FIXME: tabs", "");
+
+        // Failing until a later run succeeds
+        let ran = |code| BashOutcome { exit_code: Some(code), ..Default::default() };
+        index_bash(&db, "s1", "cargo test", &ran(101)).unwrap();
+        index_bash(&db, "s1", "cargo build", &ran(1)).unwrap();
+        index_bash(&db, "s1", "cargo build", &ran(0)).unwrap();
+
+        let plan = PlanInfo {
+            id: 1,
+            session_id: "s1".to_string(),
+            plan_file_path: "plan.md".to_string(),
+            title: None,
+            content: String::new(),
+            status: "active".to_string(),
+            target_files: vec!["lexer.rs".to_string(), "parser.rs".to_string(), "ast.rs".to_string()],
+            created_at: String::new(),
+            updated_at: String::new(),
+            progress: vec![
+                crate::indexer::plans::ProgressEntry {
+                    file_path: file_path.clone(),
+                    edit_count: 3,
+                },
+                crate::indexer::plans::ProgressEntry {
+                    file_path: dir.join("my_ast.rs").to_string_lossy().to_string(),
+                    edit_count: 1,
+                },
+            ],
+        };
+
+        let conn = db.conn();
+        let turns = search::session_turns(&conn, "s1").unwrap();
+        let section = open_threads_section(&conn, &turns, Some(&plan), &project_dir, 500).unwrap();
+        assert!(section.contains("- Failing: Command failed (exit 101): cargo test"), "{}", section);
+        assert!(!section.contains("cargo build"), "{}", section);
+        assert!(section.contains("- TODO in lexer.rs: `// TODO: handle CRLF`"), "{}", section);
+        assert!(!section.contains("FIXME"), "{}", section);
+        assert!(section.contains("- Plan target not edited yet: parser.rs"), "{}", section);
+        assert!(!section.contains("yet: lexer.rs"), "{}", section);
+        // A file whose name merely ends the same way isn't the target
+        assert!(section.contains("- Plan target not edited yet: ast.rs"), "{}", section);
+
+        assert_eq!(marker_in("let todos = TODOS;"), None);
+    }
+}
@@ -0,0 +1,209 @@
+//! Token estimation for injected context.
+//!
+//! Budgets are in tokens, since that's what context costs. Counting bytes
+//! assumes ~4 per token, which holds for English prose but not for code
+//! (punctuation and short identifiers, ~2-3 bytes per token) or non-ASCII
+//! text (a CJK character is 3 bytes and about one token). The estimator
+//! approximates a BPE tokenizer without its vocabulary: text is split the
+//! way BPE pre-tokenizes it (words, number runs, punctuation runs,
+//! whitespace), and each piece is costed by its class and length.
+
+/// Default budgets, in tokens, per injection source.
+const DEFAULT_BUDGETS: &[(&str, usize)] = &[
+    ("startup", 2_000),
+    ("compact", 4_000),
+    ("clear", 1_000),
+    ("recall", 500),
+];
+
+/// Letters of an ASCII word that typically fit in one token; longer words
+/// split into pieces of about `WORD_PIECE` letters.
+const WORD_TOKEN_LEN: usize = 8;
+const WORD_PIECE: usize = 4;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Class {
+    Word,
+    Digits,
+    Space,
+    Newline,
+    Punct,
+    /// Chinese, Japanese and Korean characters: about one token each
+    Cjk,
+    /// Other non-ASCII symbols (emoji, dashes, ...)
+    Symbol,
+}
+
+fn classify(c: char) -> Class {
+    match c {
+        '\n' | '\r' => Class::Newline,
+        ' ' | '\t' => Class::Space,
+        '0'..='9' => Class::Digits,
+        '_' => Class::Word,
+        c if c.is_ascii_alphabetic() => Class::Word,
+        c if c.is_ascii() => Class::Punct,
+        c if is_cjk(c) => Class::Cjk,
+        c if c.is_alphabetic() => Class::Word,
+        c if c.is_whitespace() => Class::Space,
+        _ => Class::Symbol,
+    }
+}
+
+fn is_cjk(c: char) -> bool {
+    matches!(c as u32,
+        0x3040..=0x30FF     // Hiragana, Katakana
+        | 0x3400..=0x4DBF   // CJK Extension A
+        | 0x4E00..=0x9FFF   // CJK Unified Ideographs
+        | 0xAC00..=0xD7AF   // Hangul syllables
+        | 0xF900..=0xFAFF   // CJK Compatibility Ideographs
+        | 0xFF00..=0xFFEF)  // Full-width forms
+}
+
+/// Split `text` into pre-tokens, returning each piece's end byte offset and
+/// estimated token count.
+fn pieces(text: &str) -> Vec<(usize, usize)> {
+    let mut out = Vec::new();
+    let mut chars = text.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        let class = classify(c);
+        let mut end = start + c.len_utf8();
+        // CJK and symbols are costed per character
+        if !matches!(class, Class::Cjk | Class::Symbol) {
+            while let Some(&(i, next)) = chars.peek() {
+                if classify(next) != class {
+                    break;
+                }
+                end = i + next.len_utf8();
+                chars.next();
+            }
+        }
+        let piece = &text[start..end];
+        let tokens = match class {
+            Class::Word => word_tokens(piece),
+            Class::Digits => piece.len().div_ceil(3),
+            // A single space merges into the following token (" word")
+            Class::Space if piece.len() == 1 && chars.peek().is_some() => 0,
+            Class::Space => 1 + (piece.len() - 1) / 8,
+            Class::Newline => piece.len().div_ceil(2),
+            Class::Punct => piece.len().div_ceil(2),
+            Class::Cjk => 1,
+            Class::Symbol => piece.len().div_ceil(2),
+        };
+        out.push((end, tokens));
+    }
+    out
+}
+
+/// Tokens for a word or identifier: split at underscores and camelCase
+/// humps, each part one token up to WORD_TOKEN_LEN letters. Non-ASCII
+/// words tokenize worse, at about three characters per token.
+fn word_tokens(word: &str) -> usize {
+    if !word.is_ascii() {
+        return word.chars().count().div_ceil(3);
+    }
+    let mut tokens = 0;
+    let mut part_len = 0;
+    let mut prev_lower = false;
+    let mut flush = |len: usize| {
+        if len > 0 {
+            tokens += 1 + len.saturating_sub(WORD_TOKEN_LEN).div_ceil(WORD_PIECE);
+        }
+    };
+    for c in word.chars() {
+        if c == '_' {
+            flush(part_len);
+            part_len = 0;
+            prev_lower = false;
+            continue;
+        }
+        if c.is_ascii_uppercase() && prev_lower {
+            flush(part_len);
+            part_len = 0;
+        }
+        part_len += 1;
+        prev_lower = c.is_ascii_lowercase();
+    }
+    flush(part_len);
+    // Leading or trailing underscores (`__init__`) are tokens of their own
+    tokens += usize::from(word.starts_with('_')) + usize::from(word.ends_with('_') && word.len() > 1);
+    tokens.max(1)
+}
+
+/// Estimated number of tokens in `text`.
+pub fn estimate(text: &str) -> usize {
+    pieces(text).iter().map(|(_, tokens)| tokens).sum()
+}
+
+/// Truncate `text` to about `max_tokens`, appending "..." when cut.
+pub fn truncate(text: &str, max_tokens: usize) -> String {
+    let pieces = pieces(text);
+    if pieces.iter().map(|(_, t)| t).sum::<usize>() <= max_tokens {
+        return text.to_string();
+    }
+
+    // Leave a token for the ellipsis
+    let limit = max_tokens.saturating_sub(1);
+    let mut used = 0;
+    let mut end = 0;
+    for &(piece_end, tokens) in &pieces {
+        if used + tokens > limit {
+            // Keep part of a long piece (a hash, a long word) rather than
+            // dropping it whole
+            let room = limit - used;
+            if room > 0 {
+                let cut = end + (piece_end - end) * room / tokens;
+                end = text.floor_char_boundary(cut);
+            }
+            break;
+        }
+        used += tokens;
+        end = piece_end;
+    }
+    format!("{}...", &text[..end])
+}
+
+/// Token budget for an injection source (`startup`, `compact`, `clear`,
+/// `recall`), from `[budgets]` in the project or global config, else the
+/// default.
+pub fn budget(project_dir: &str, source: &str) -> usize {
+    configured_budget(project_dir, source).unwrap_or_else(|| {
+        DEFAULT_BUDGETS
+            .iter()
+            .find(|(s, _)| *s == source)
+            .map(|(_, b)| *b)
+            .unwrap_or(1_000)
+    })
+}
+
+/// `[budgets] <source>` from the project's `.claude/claude-rlm.toml`, else
+/// the global config.
+pub fn configured_budget(project_dir: &str, source: &str) -> Option<usize> {
+    crate::llm::config_value(project_dir, "budgets", source)
+        .and_then(|v| v.as_integer())
+        .map(|budget| budget.max(0) as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_estimate() {
+        // English prose: about a token per word
+        assert_eq!(estimate("the quick brown fox jumps over the lazy dog"), 9);
+        // Code: identifiers split at humps and underscores, punctuation counts
+        assert_eq!(estimate("fn main() {"), 4);
+        assert_eq!(estimate("parseHttpRequest(snake_case_name)"), 8);
+        // CJK: about a token per character, far more than bytes / 4 suggests
+        assert_eq!(estimate("日本語のテキスト"), 8);
+        assert_eq!(estimate(""), 0);
+
+        let text = "let tokens = estimate(&content); // 日本語";
+        let cut = truncate(text, 5);
+        assert!(cut.ends_with("...") && estimate(&cut) <= 6, "{}", cut);
+        assert_eq!(truncate(text, 100), text);
+        // A long unbroken piece is cut partway, not dropped
+        let hash = "a".repeat(400);
+        assert!(truncate(&hash, 20).len() > 40);
+    }
+}
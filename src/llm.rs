@@ -1,6 +1,7 @@
 use anyhow::{anyhow, Result};
 use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
+use std::time::Duration;
 
 /// LLM provider configuration.
 ///
@@ -104,14 +105,24 @@ impl LlmConfig {
 
     /// Send a prompt to the configured LLM and return the response text.
     pub fn complete(&self, system: &str, user_message: &str) -> Result<String> {
+        let timeout = match self.provider {
+            Provider::Anthropic => Duration::from_secs(30),
+            Provider::OpenAICompat => Duration::from_secs(60),
+        };
+        self.complete_within(system, user_message, timeout)
+    }
+
+    /// Like `complete`, but fail once `timeout` has passed, counting from
+    /// connecting to the end of the response.
+    pub fn complete_within(&self, system: &str, user_message: &str, timeout: Duration) -> Result<String> {
         match self.provider {
-            Provider::Anthropic => self.complete_anthropic(system, user_message),
-            Provider::OpenAICompat => self.complete_openai(system, user_message),
+            Provider::Anthropic => self.complete_anthropic(system, user_message, timeout),
+            Provider::OpenAICompat => self.complete_openai(system, user_message, timeout),
         }
     }
 
     /// Call the Anthropic Messages API.
-    fn complete_anthropic(&self, system: &str, user_message: &str) -> Result<String> {
+    fn complete_anthropic(&self, system: &str, user_message: &str, timeout: Duration) -> Result<String> {
         let api_key = self.api_key.as_deref()
             .ok_or_else(|| anyhow!("api_key required for Anthropic provider"))?;
 
@@ -134,7 +145,7 @@ impl LlmConfig {
             .header("anthropic-version", "2023-06-01")
             .header("content-type", "application/json")
             .json(&body)
-            .timeout(std::time::Duration::from_secs(30))
+            .timeout(timeout)
             .send()?;
 
         if !resp.status().is_success() {
@@ -152,7 +163,7 @@ impl LlmConfig {
     }
 
     /// Call an OpenAI-compatible Chat Completions API (works with Ollama, OpenRouter, etc.)
-    fn complete_openai(&self, system: &str, user_message: &str) -> Result<String> {
+    fn complete_openai(&self, system: &str, user_message: &str, timeout: Duration) -> Result<String> {
         let client = reqwest::blocking::Client::new();
 
         // Ollama uses /api/chat, but most OpenAI-compat use /v1/chat/completions
@@ -182,7 +193,7 @@ impl LlmConfig {
         let mut req = client
             .post(&url)
             .header("content-type", "application/json")
-            .timeout(std::time::Duration::from_secs(60));
+            .timeout(timeout);
 
         if let Some(key) = &self.api_key {
             req = req.header("Authorization", format!("Bearer {}", key));
@@ -257,6 +268,18 @@ pub fn global_config_path() -> Option<PathBuf> {
     }
 }
 
+/// `key` in `[section]` of the project's `.claude/claude-rlm.toml`, else of
+/// the global config.
+pub fn config_value(project_dir: &str, section: &str, key: &str) -> Option<toml::Value> {
+    let project_config = PathBuf::from(project_dir).join(".claude").join("claude-rlm.toml");
+    std::iter::once(project_config)
+        .chain(global_config_path())
+        .find_map(|path| {
+            let doc = std::fs::read_to_string(&path).ok()?.parse::<toml::Table>().ok()?;
+            doc.get(section)?.as_table()?.get(key).cloned()
+        })
+}
+
 /// Write a key-value pair into a section of the global config TOML.
 /// Creates the file and parent directories if needed. Merges with existing content.
 pub fn write_global_config(section: &str, key: &str, value: toml::Value) -> Result<()> {
@@ -2,6 +2,7 @@
 
 mod db;
 mod hooks;
+mod import;
 mod indexer;
 mod inject;
 mod llm;
@@ -24,12 +25,18 @@ struct Cli {
 #[derive(Subcommand)]
 enum Commands {
     /// Start the MCP server (default mode)
-    Serve,
+    Serve {
+        /// Serve over streamable HTTP at this address (e.g. 127.0.0.1:7878)
+        /// instead of stdio, so multiple local clients share one server.
+        /// Only loopback addresses are accepted.
+        #[arg(long, value_name = "ADDR")]
+        http: Option<String>,
+    },
 
-    /// Index a user prompt (UserPromptSubmit hook)
+    /// Recall related memory and index a user prompt (UserPromptSubmit hook)
     IndexPrompt,
 
-    /// Index a code edit (PostToolUse Edit/Write hook)
+    /// Index a code edit (PostToolUse Edit/Write/MultiEdit hook)
     IndexEdit,
 
     /// Index a file read (PostToolUse Read hook)
@@ -38,6 +45,21 @@ enum Commands {
     /// Index a bash command (PostToolUse Bash hook)
     IndexBash,
 
+    /// Index a code or web search (PostToolUse Grep/Glob/WebSearch hook)
+    IndexSearch,
+
+    /// Index a fetched URL (PostToolUse WebFetch hook)
+    IndexFetch,
+
+    /// Index a notebook edit (PostToolUse NotebookEdit hook)
+    IndexNotebook,
+
+    /// Record a todo list (PostToolUse TodoWrite hook)
+    IndexTodos,
+
+    /// Index a subagent's final report (PostToolUse Task hook)
+    IndexTask,
+
     /// Handle pre-compaction (PreCompact hook)
     PreCompact,
 
@@ -47,6 +69,9 @@ enum Commands {
     /// Handle pre-tool-use (PreToolUse hook)
     PreToolUse,
 
+    /// Index assistant replies from the transcript (Stop hook)
+    Stop,
+
     /// Handle session end (SessionEnd hook)
     SessionEnd {
         /// Trigger knowledge distillation
@@ -57,6 +82,27 @@ enum Commands {
     /// Show index status and statistics
     Status,
 
+    /// Preview the context injected at session start or after compaction
+    Inject {
+        /// Which injection to build
+        #[arg(long, value_parser = ["startup", "compact"])]
+        source: String,
+        /// Session id or prefix (defaults to the most recent session)
+        #[arg(long, value_name = "ID")]
+        session: Option<String>,
+        /// Also show each candidate turn's score components and why it was
+        /// included or cut
+        #[arg(long)]
+        explain: bool,
+    },
+
+    /// Backfill memory from existing Claude Code session transcripts
+    ImportTranscripts {
+        /// Project directory to import (defaults to the current directory)
+        #[arg(long, value_name = "DIR")]
+        project: Option<std::path::PathBuf>,
+    },
+
     /// Disable all hooks (emergency kill switch)
     Disable,
 
@@ -74,7 +120,7 @@ enum Commands {
 enum ConfigAction {
     /// Set a configuration value
     Set {
-        /// The key to set (api-key, model, provider, base-url, auto-update)
+        /// The key to set (api-key, model, provider, base-url, auto-update, recall)
         key: String,
         /// The value to set
         value: String,
@@ -86,7 +132,8 @@ async fn main() -> Result<()> {
     let cli = Cli::parse();
 
     match cli.command {
-        None | Some(Commands::Serve) => run_server().await,
+        None => run_server(None).await,
+        Some(Commands::Serve { http }) => run_server(http).await,
         Some(Commands::IndexPrompt) => run_hook(|| {
             let input = hooks::read_hook_input()?;
             hooks::prompt::handle(&input)
@@ -103,6 +150,26 @@ async fn main() -> Result<()> {
             let input = hooks::read_hook_input()?;
             hooks::tool_use::handle_bash(&input)
         }),
+        Some(Commands::IndexSearch) => run_hook(|| {
+            let input = hooks::read_hook_input()?;
+            hooks::tool_use::handle_search(&input)
+        }),
+        Some(Commands::IndexFetch) => run_hook(|| {
+            let input = hooks::read_hook_input()?;
+            hooks::tool_use::handle_web_fetch(&input)
+        }),
+        Some(Commands::IndexNotebook) => run_hook(|| {
+            let input = hooks::read_hook_input()?;
+            hooks::tool_use::handle_notebook_edit(&input)
+        }),
+        Some(Commands::IndexTodos) => run_hook(|| {
+            let input = hooks::read_hook_input()?;
+            hooks::tool_use::handle_todo_write(&input)
+        }),
+        Some(Commands::IndexTask) => run_hook(|| {
+            let input = hooks::read_hook_input()?;
+            hooks::tool_use::handle_task(&input)
+        }),
         Some(Commands::PreCompact) => run_hook(|| {
             let input = hooks::read_hook_input()?;
             hooks::compact::handle(&input)
@@ -115,11 +182,17 @@ async fn main() -> Result<()> {
             let input = hooks::read_hook_input()?;
             hooks::session::handle_start(&input)
         }),
+        Some(Commands::Stop) => run_hook(|| {
+            let input = hooks::read_hook_input()?;
+            hooks::stop::handle(&input)
+        }),
         Some(Commands::SessionEnd { distill: _ }) => run_hook(|| {
             let input = hooks::read_hook_input()?;
             hooks::session::handle_end(&input)
         }),
         Some(Commands::Status) => run_status(),
+        Some(Commands::Inject { source, session, explain }) => run_inject(&source, session.as_deref(), explain),
+        Some(Commands::ImportTranscripts { project }) => import::run(project),
         Some(Commands::Disable) => run_disable(),
         Some(Commands::Enable) => run_enable(),
         Some(Commands::Config { action }) => run_config(action),
@@ -302,7 +375,16 @@ fn ensure_hooks_synced() {
         None => return,
     };
 
-    let mut changed = false;
+    let original = installed_hooks.clone();
+
+    // Remove stale claude-rlm entries from every hook type, including types
+    // and matchers this binary no longer ships (e.g. a renamed matcher)
+    for entries in installed_hooks.values_mut() {
+        if let Some(arr) = entries.as_array_mut() {
+            arr.retain(|entry| !is_claude_rlm_entry(entry));
+        }
+    }
+    installed_hooks.retain(|_, entries| entries.as_array().is_none_or(|a| !a.is_empty()));
 
     for (hook_type, our_entries) in our_hooks {
         let our_arr = match our_entries.as_array() {
@@ -318,24 +400,17 @@ fn ensure_hooks_synced() {
             None => continue,
         };
 
-        // Remove stale claude-rlm entries
-        let before = existing_arr.len();
-        existing_arr.retain(|entry| !is_claude_rlm_entry(entry));
-        if existing_arr.len() != before {
-            changed = true;
-        }
-
         // Append our current entries, resolving ${CLAUDE_PLUGIN_ROOT} to absolute path
         let root_str = plugin_root.to_string_lossy();
         for entry in our_arr {
             let mut entry = entry.clone();
             resolve_plugin_root(&mut entry, &root_str);
             existing_arr.push(entry);
-            changed = true;
         }
     }
 
-    if !changed {
+    // Only rewrite the file when the merge changed something
+    if *installed_hooks == original {
         return;
     }
 
@@ -388,8 +463,95 @@ fn is_claude_rlm_entry(entry: &serde_json::Value) -> bool {
         .unwrap_or(false)
 }
 
-/// Run the MCP server over stdio.
-async fn run_server() -> Result<()> {
+/// Print the context an injection would emit for a session, exactly, on
+/// stdout; its per-section sizes and, with `explain`, the ranking of every
+/// candidate turn go to stderr.
+fn run_inject(source: &str, session: Option<&str>, explain: bool) -> Result<()> {
+    let project_dir = std::env::current_dir()?;
+    let db = db::Db::open(&project_dir)?;
+
+    let session = {
+        let conn = db.conn();
+        match session {
+            Some(id) => db::search::find_session(&conn, id)?
+                .ok_or_else(|| anyhow::anyhow!("no session matching '{}'", id))?,
+            None => db::search::recent_sessions(&conn, 1)?
+                .into_iter()
+                .next()
+                .ok_or_else(|| anyhow::anyhow!("no sessions recorded for this project"))?,
+        }
+    };
+
+    let preview = match source {
+        "startup" => inject::preview_startup_context(&db, &session.id)?,
+        _ => inject::preview_compact_context(&db, &session.id)?,
+    };
+    print!("{}", preview.context());
+
+    let budget = inject::tokens::budget(&db.project_dir(), source);
+    eprintln!(
+        "\n--- {} context for session {} (budget {} tokens) ---",
+        source, session.id, budget
+    );
+    eprintln!("{:>7} {:>7}  section", "tokens", "bytes");
+    for section in &preview.sections {
+        let name = section.lines().next().unwrap_or("").trim_start_matches("## ");
+        eprintln!(
+            "{:>7} {:>7}  {}",
+            inject::tokens::estimate(section),
+            section.len(),
+            inject::tokens::truncate(name, 12)
+        );
+    }
+    let context = preview.context();
+    eprintln!(
+        "{:>7} {:>7}  total",
+        inject::tokens::estimate(&context),
+        context.len()
+    );
+
+    if explain {
+        if preview.explanations.is_empty() {
+            eprintln!("\nNo turns were ranked: {} context has fixed sections only.", source);
+            return Ok(());
+        }
+        let mut explanations: Vec<&inject::ranking::Explanation> = preview.explanations.iter().collect();
+        explanations.sort_by(|a, b| b.parts.score().total_cmp(&a.parts.score()));
+        eprintln!(
+            "\nCandidate turns by score (score = type × recency × affinity × length × relevance boost):"
+        );
+        eprintln!(
+            "{:>6}  {:<14} {:>6}  {:>5} {:>7} {:>8} {:>6} {:>9}  outcome",
+            "turn", "type", "score", "type", "recency", "affinity", "length", "relevance"
+        );
+        for e in explanations {
+            let p = &e.parts;
+            eprintln!(
+                "{:>6}  {:<14} {:>6.3}  {:>5.2} {:>7.2} {:>8.2} {:>6.2} {:>9.2}  {}",
+                e.turn.turn_number,
+                e.turn.turn_type,
+                p.score(),
+                p.type_weight,
+                p.recency,
+                p.affinity,
+                p.length_bonus,
+                p.relevance,
+                e.outcome
+            );
+            let first_line = e.turn.content.lines().next().unwrap_or("");
+            eprintln!("        {}", inject::tokens::truncate(first_line, 20));
+        }
+    }
+    Ok(())
+}
+
+/// Run the MCP server over stdio, or over streamable HTTP when `http` is set.
+///
+/// Stdio mode serves a single Claude Code window and exits when it closes.
+/// HTTP mode serves any number of local clients (Claude windows, editors,
+/// scripts) from one process, so the project gets a single file watcher and
+/// task poller instead of one per window.
+async fn run_server(http: Option<String>) -> Result<()> {
     // Log to stderr to keep stdout clean for MCP protocol
     tracing_subscriber::fmt()
         .with_writer(std::io::stderr)
@@ -400,6 +562,12 @@ async fn run_server() -> Result<()> {
         )
         .init();
 
+    // The HTTP server has no authentication: anyone who can reach it can
+    // read the project's memory and call its tools
+    if let Some(addr) = &http {
+        check_loopback(addr)?;
+    }
+
     tracing::info!("Starting ClaudeRLM MCP server");
     ensure_hooks_synced();
 
@@ -423,8 +591,8 @@ async fn run_server() -> Result<()> {
         Err(e) => tracing::warn!("Failed to recover stuck tasks: {}", e),
     }
 
-    // Run initial code indexing if needed
-    if !indexer::code::has_index(&db)? {
+    // Run initial code indexing if needed (or if call sites were never indexed)
+    if !indexer::code::has_index(&db)? || !indexer::code::has_call_index(&db)? {
         tracing::info!("No code index found, running initial scan...");
         match indexer::code::index_project(&db, &project_dir) {
             Ok(stats) => tracing::info!(
@@ -448,18 +616,33 @@ async fn run_server() -> Result<()> {
         }
     };
 
-    // Start background task poller
-    tokio::spawn(run_task_poller(db.clone(), project_dir));
+    // Start background task poller. A shared HTTP server outlives any single
+    // Claude session, so it must not honour per-session shutdown signals.
+    let shared = http.is_some();
+    if !shared {
+        if let Err(e) = db::tasks::register_server(&db, std::process::id()) {
+            tracing::warn!("Failed to register server: {}", e);
+        }
+    }
+    tokio::spawn(run_task_poller(db.clone(), project_dir, shared));
 
     // Check for updates in the background
     update::spawn_update_check();
 
+    match http {
+        Some(addr) => serve_http(db, &addr).await,
+        None => serve_stdio(db).await,
+    }
+}
+
+/// Serve MCP over stdio for a single client.
+async fn serve_stdio(db: db::Db) -> Result<()> {
     // Spawn a watchdog that detects stdin close and force-exits.
     // rmcp's async stdin reader may not detect EOF promptly on Windows,
     // causing the process to hang until Claude Code force-kills it (error).
     spawn_stdin_watchdog();
 
-    let server = server::ClaudeRlmServer::new(db);
+    let server = server::ClaudeRlmServer::new(db.clone());
 
     let service = server
         .serve(rmcp::transport::stdio())
@@ -477,6 +660,61 @@ async fn run_server() -> Result<()> {
     // Force-exit immediately. Tokio runtime shutdown can hang waiting for
     // spawn_blocking tasks (file watcher, task poller). Claude Code kills
     // MCP servers that don't exit promptly and reports them as failed.
+    let _ = db::tasks::unregister_server(&db, std::process::id());
+    std::process::exit(0);
+}
+
+/// Refuse to serve HTTP on anything but a loopback address.
+fn check_loopback(addr: &str) -> Result<()> {
+    use std::net::ToSocketAddrs;
+
+    let resolved: Vec<std::net::SocketAddr> = addr
+        .to_socket_addrs()
+        .map_err(|e| anyhow::anyhow!("Invalid address {}: {}", addr, e))?
+        .collect();
+    if resolved.is_empty() || resolved.iter().any(|a| !a.ip().is_loopback()) {
+        anyhow::bail!(
+            "Refusing to serve on {}: the HTTP server has no authentication, so it only binds \
+             loopback addresses (e.g. 127.0.0.1:7878)",
+            addr
+        );
+    }
+    Ok(())
+}
+
+/// Serve MCP over streamable HTTP at `addr`, mounted at `/mcp`.
+///
+/// Each client gets its own MCP session backed by the same database handle.
+/// Runs until Ctrl-C.
+async fn serve_http(db: db::Db, addr: &str) -> Result<()> {
+    use rmcp::transport::streamable_http_server::{
+        session::local::LocalSessionManager, StreamableHttpServerConfig, StreamableHttpService,
+    };
+
+    let service = StreamableHttpService::new(
+        move || Ok(server::ClaudeRlmServer::new(db.clone())),
+        LocalSessionManager::default().into(),
+        StreamableHttpServerConfig::default(),
+    );
+    let cancel = service.config.cancellation_token.clone();
+
+    let router = axum::Router::new().nest_service("/mcp", service);
+    let listener = tokio::net::TcpListener::bind(addr)
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to bind {}: {}", addr, e))?;
+
+    tracing::info!("Serving MCP over HTTP at http://{}/mcp", listener.local_addr()?);
+    eprintln!("[claude-rlm] Serving MCP over HTTP at http://{}/mcp", listener.local_addr()?);
+
+    axum::serve(listener, router)
+        .with_graceful_shutdown(async move {
+            let _ = tokio::signal::ctrl_c().await;
+            tracing::info!("Ctrl-C received, shutting down HTTP server");
+            cancel.cancel();
+        })
+        .await?;
+
+    // Same reasoning as stdio: don't wait on blocking watcher/poller threads.
     std::process::exit(0);
 }
 
@@ -531,7 +769,13 @@ fn spawn_stdin_watchdog() {
 }
 
 /// Poll for background tasks and execute them.
-async fn run_task_poller(db: db::Db, project_dir: std::path::PathBuf) {
+///
+/// When `shared` is true (HTTP mode) `shutdown` tasks are acknowledged but
+/// ignored: one client's SessionEnd must not take the server down for others.
+/// A stdio server likewise ignores them while another window's server is
+/// running, since the signal may be that window's; it still exits when its
+/// own stdin closes.
+async fn run_task_poller(db: db::Db, project_dir: std::path::PathBuf, shared: bool) {
     use tokio::time::{interval, Duration};
 
     let mut poll_interval = interval(Duration::from_millis(300));
@@ -549,6 +793,9 @@ async fn run_task_poller(db: db::Db, project_dir: std::path::PathBuf) {
                 if let Err(e) = db::tasks::prune_old_tasks(&db2, 3600) {
                     tracing::warn!("Failed to prune old tasks: {}", e);
                 }
+                if !shared {
+                    let _ = db::tasks::register_server(&db2, std::process::id());
+                }
             })
             .await;
         }
@@ -574,9 +821,18 @@ async fn run_task_poller(db: db::Db, project_dir: std::path::PathBuf) {
             );
 
             match task.task_type.as_str() {
+                "shutdown" if shared => {
+                    tracing::info!("Task #{}: shutdown ignored (shared HTTP server)", task.id);
+                    let _ = db::tasks::complete_task(&db2, task.id);
+                }
+                "shutdown" if db::tasks::other_live_servers(&db2, std::process::id()).unwrap_or(0) > 0 => {
+                    tracing::info!("Task #{}: shutdown ignored (another window's server is running)", task.id);
+                    let _ = db::tasks::complete_task(&db2, task.id);
+                }
                 "shutdown" => {
                     tracing::info!("Task #{}: shutdown signal received, exiting", task.id);
                     let _ = db::tasks::complete_task(&db2, task.id);
+                    let _ = db::tasks::unregister_server(&db2, std::process::id());
                     std::process::exit(0);
                 }
                 "reindex_stale" => execute_reindex_stale(&db2, &task, &project_dir2),
@@ -627,10 +883,7 @@ fn execute_reindex_stale(db: &db::Db, task: &db::tasks::BackgroundTask, project_
                 } else {
                     // File deleted — remove its symbols
                     let conn = db.conn();
-                    let _ = conn.execute(
-                        "DELETE FROM symbols WHERE file_path = ?1",
-                        rusqlite::params![path.to_string_lossy().as_ref()],
-                    );
+                    let _ = indexer::code::remove_file(&conn, &path.to_string_lossy());
                     reindexed += 1;
                 }
             }
@@ -826,9 +1079,23 @@ fn run_config(action: ConfigAction) -> Result<()> {
                     eprintln!("[claude-rlm] Set update.auto_update = {} in {}", enabled, path.display());
                 }
 
+                // Recall config → [recall] section
+                "recall" => {
+                    let enabled = match value.as_str() {
+                        "true" | "1" | "on" => true,
+                        "false" | "0" | "off" => false,
+                        _ => anyhow::bail!(
+                            "Invalid value for recall: '{}'. Use true/false",
+                            value
+                        ),
+                    };
+                    llm::write_global_config("recall", "enabled", toml::Value::Boolean(enabled))?;
+                    eprintln!("[claude-rlm] Set recall.enabled = {} in {}", enabled, path.display());
+                }
+
                 other => {
                     anyhow::bail!(
-                        "Unknown config key '{}'. Valid keys: api-key, model, provider, base-url, auto-update",
+                        "Unknown config key '{}'. Valid keys: api-key, model, provider, base-url, auto-update, recall",
                         other
                     );
                 }
@@ -840,11 +1107,16 @@ fn run_config(action: ConfigAction) -> Result<()> {
 }
 
 /// Run a hook handler, catching errors and panics.
+///
+/// Handlers are synchronous and may block (LLM calls use reqwest's blocking
+/// client, which can't run on a runtime thread), so they run outside the
+/// async context.
 fn run_hook(f: impl FnOnce() -> Result<()>) -> Result<()> {
     if is_disabled() {
         return Ok(());
     }
 
+    let f = || tokio::task::block_in_place(f);
     match std::panic::catch_unwind(std::panic::AssertUnwindSafe(f)) {
         Ok(Ok(())) => Ok(()),
         Ok(Err(e)) => {
@@ -863,3 +1135,18 @@ fn run_hook(f: impl FnOnce() -> Result<()>) -> Result<()> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_loopback() {
+        assert!(check_loopback("127.0.0.1:7878").is_ok());
+        assert!(check_loopback("[::1]:7878").is_ok());
+        assert!(check_loopback("0.0.0.0:7878").is_err());
+        assert!(check_loopback("[::]:7878").is_err());
+        assert!(check_loopback("192.168.1.20:7878").is_err());
+        assert!(check_loopback("127.0.0.1").is_err());
+    }
+}
@@ -72,6 +72,70 @@ pub struct MemorySymbolsParams {
     pub kind: Option<String>,
 }
 
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct MemorySessionsParams {
+    /// Session to show as a timeline (full ID or prefix). Omit to list sessions.
+    #[schemars(description = "Session ID (or prefix) to show as a timeline; omit to list sessions")]
+    pub session_id: Option<String>,
+
+    /// Only sessions started on or after this date
+    #[schemars(description = "Only sessions started on or after this date (YYYY-MM-DD)")]
+    pub since: Option<String>,
+
+    /// Only sessions started on or before this date
+    #[schemars(description = "Only sessions started on or before this date (YYYY-MM-DD)")]
+    pub until: Option<String>,
+
+    /// Only sessions started on this git branch
+    #[schemars(description = "Optional git branch filter")]
+    pub branch: Option<String>,
+
+    /// Keyword to match in session summaries or user requests
+    #[schemars(description = "Keyword to match in session summaries or requests")]
+    pub keyword: Option<String>,
+
+    /// Maximum number of sessions to list (default: 10)
+    #[schemars(description = "Maximum sessions to list")]
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct MemorySymbolContextParams {
+    /// Symbol name, optionally qualified with its parent
+    #[schemars(description = "Symbol name, optionally qualified (e.g. `Db::open` or `Parser.parse`)")]
+    pub name: String,
+
+    /// Restrict to files whose path contains this string
+    #[schemars(description = "Optional file path (or path fragment) to disambiguate")]
+    pub file_path: Option<String>,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct MemoryBlameParams {
+    /// File path, optionally with a line range (`src/foo.rs:120-140`)
+    #[schemars(description = "File path, optionally with a line range suffix (e.g. `src/inject/ranking.rs:120-140`)")]
+    pub file_path: String,
+
+    /// First line of the range (1-indexed)
+    #[schemars(description = "First line of the range (overrides any range in file_path)")]
+    pub start_line: Option<i64>,
+
+    /// Last line of the range (inclusive)
+    #[schemars(description = "Last line of the range, inclusive")]
+    pub end_line: Option<i64>,
+}
+
+#[derive(Debug, Deserialize, schemars::JsonSchema)]
+pub struct MemoryErrorsParams {
+    /// Error message or snippet to look for
+    #[schemars(description = "Error message or distinctive snippet (e.g. `error[E0502]: cannot borrow`)")]
+    pub error: String,
+
+    /// Maximum number of past occurrences (default: 5)
+    #[schemars(description = "Maximum past occurrences to return")]
+    pub limit: Option<usize>,
+}
+
 // --- Helper: run DB work on a blocking thread ---
 
 fn mcp_err(msg: String) -> McpError {
@@ -82,6 +146,455 @@ fn mcp_err(msg: String) -> McpError {
     }
 }
 
+fn truncate(s: &str, max: usize) -> String {
+    if s.len() <= max {
+        s.to_string()
+    } else {
+        let end = s.floor_char_boundary(max);
+        format!("{}...", &s[..end])
+    }
+}
+
+/// Render a list of sessions, newest first.
+fn format_session_list(sessions: &[search::SessionListEntry]) -> String {
+    let mut output = format!("## Sessions ({})\n\n", sessions.len());
+    for entry in sessions {
+        let s = &entry.session;
+        let branch = s
+            .git_branch
+            .as_deref()
+            .map(|b| format!(" [{}]", b))
+            .unwrap_or_default();
+        let ended = s.ended_at.as_deref().unwrap_or("(in progress)");
+        output.push_str(&format!(
+            "- **{}**{} {} → {} · {} requests, {} edits\n",
+            s.id, branch, s.started_at, ended, entry.request_count, entry.edit_count
+        ));
+        let description = s
+            .summary
+            .as_deref()
+            .or(entry.first_request.as_deref())
+            .unwrap_or("(no activity)");
+        output.push_str(&format!(
+            "  {}\n",
+            truncate(&description.replace('\n', " "), 200)
+        ));
+    }
+    output
+}
+
+/// Render a session as requests, each followed by the activity it caused.
+/// Consecutive identical activity lines are collapsed with a count.
+fn format_session_timeline(
+    session: &search::SessionSummary,
+    timeline: &[search::TimelineEntry],
+) -> String {
+    let mut output = format!("## Session {}\n", session.id);
+    output.push_str(&format!(
+        "Started: {} | Ended: {}",
+        session.started_at,
+        session.ended_at.as_deref().unwrap_or("(in progress)")
+    ));
+    if let Some(branch) = &session.git_branch {
+        output.push_str(&format!(" | Branch: {}", branch));
+    }
+    output.push('\n');
+    if let Some(summary) = &session.summary {
+        output.push_str(&format!("\n{}\n", summary));
+    }
+
+    if timeline.is_empty() {
+        output.push_str("\nNo turns recorded for this session.\n");
+        return output;
+    }
+
+    let mut request_index = 0;
+    for entry in timeline {
+        match &entry.request {
+            Some(r) => {
+                request_index += 1;
+                output.push_str(&format!(
+                    "\n### {}. [{}] {}\n",
+                    request_index,
+                    r.timestamp,
+                    truncate(&r.content.replace('\n', " "), 300)
+                ));
+            }
+            None => output.push_str("\n### Before first request\n"),
+        }
+
+        let mut lines: Vec<(String, usize)> = Vec::new();
+        for turn in &entry.activity {
+            let first_line = turn.content.lines().next().unwrap_or("").trim_end_matches(':');
+            let line = format!("{}: {}", turn.turn_type, truncate(first_line, 160));
+            match lines.last_mut() {
+                Some((last, count)) if *last == line => *count += 1,
+                _ => lines.push((line, 1)),
+            }
+        }
+        for (line, count) in lines {
+            if count > 1 {
+                output.push_str(&format!("- {} (×{})\n", line, count));
+            } else {
+                output.push_str(&format!("- {}\n", line));
+            }
+        }
+    }
+
+    output
+}
+
+/// Maximum source lines shown for a symbol before truncating.
+const MAX_SOURCE_LINES: usize = 150;
+
+/// Qualified display name for a symbol (`Parent::name`).
+fn qualified_symbol_name(sym: &search::SymbolMatch) -> String {
+    match sym.parent_name.as_deref() {
+        Some(p) if !p.is_empty() => format!("{}::{}", p, sym.name),
+        _ => sym.name.clone(),
+    }
+}
+
+/// Read lines `start..=end` (1-indexed) of a file from disk, numbered.
+fn read_source_lines(file_path: &str, start: i64, end: i64) -> Option<String> {
+    let source = std::fs::read_to_string(file_path).ok()?;
+    let first = start.max(1) as usize;
+    let count = (end - start + 1).max(1) as usize;
+    let mut out = String::new();
+    for (i, line) in source.lines().enumerate().skip(first - 1).take(count.min(MAX_SOURCE_LINES)) {
+        out.push_str(&format!("{:>5} | {}\n", i + 1, line));
+    }
+    if count > MAX_SOURCE_LINES {
+        out.push_str(&format!("  ... ({} more lines)\n", count - MAX_SOURCE_LINES));
+    }
+    Some(out)
+}
+
+/// Gather everything known about one symbol: source, docs, edit history,
+/// related knowledge, callers and callees.
+fn symbol_context(
+    conn: &rusqlite::Connection,
+    sym: &search::SymbolMatch,
+) -> anyhow::Result<String> {
+    let mut output = format!(
+        "## {} `{}` — {}:{}-{}\n",
+        sym.kind,
+        qualified_symbol_name(sym),
+        sym.file_path,
+        sym.start_line,
+        sym.end_line
+    );
+    if let Some(sig) = sym.signature.as_deref().filter(|s| !s.is_empty()) {
+        output.push_str(&format!("`{}`\n", sig.replace('\n', " ")));
+    }
+    if let Some(parent) = sym.parent_name.as_deref().filter(|p| !p.is_empty()) {
+        output.push_str(&format!("Parent: {}\n", parent));
+    }
+    if let Some(doc) = sym.doc_comment.as_deref().filter(|d| !d.is_empty()) {
+        output.push_str(&format!("\n{}\n", doc));
+    }
+
+    // 1. Current source, read fresh from disk
+    output.push_str("\n### Source\n```\n");
+    match read_source_lines(&sym.file_path, sym.start_line, sym.end_line) {
+        Some(src) => output.push_str(&src),
+        None => output.push_str("(file not readable)\n"),
+    }
+    output.push_str("```\n");
+
+    // 2. Edits that touched this region. Older edits have no recorded
+    //    regions; fall back to file edits that mention the symbol name.
+    let mut edits = search::region_edits(conn, &sym.file_path, sym.start_line, sym.end_line, 10)?;
+    if edits.is_empty() {
+        edits = search::file_history(conn, &sym.file_path, 50)?
+            .into_iter()
+            .filter(|t| t.turn_type == "code_edit" && t.content.contains(&sym.name))
+            .take(5)
+            .collect();
+        edits.reverse();
+    }
+    if !edits.is_empty() {
+        output.push_str(&format!("\n### Edit History ({})\n", edits.len()));
+        for t in &edits {
+            output.push_str(&format!(
+                "- [{}] session:{} turn #{}: {}\n",
+                t.timestamp,
+                t.session_id,
+                t.turn_number,
+                truncate(&t.content.replace('\n', " "), 200)
+            ));
+        }
+    }
+
+    // 3. Related knowledge (by symbol name, then by file name)
+    let file_name = sym
+        .file_path
+        .rsplit(['/', '\\'])
+        .next()
+        .unwrap_or(&sym.file_path)
+        .to_string();
+    let mut knowledge = search::search_knowledge(conn, &sym.name, 5, None)?;
+    for k in search::search_knowledge(conn, &file_name, 3, None)? {
+        if !knowledge.iter().any(|e| e.id == k.id) {
+            knowledge.push(k);
+        }
+    }
+    if !knowledge.is_empty() {
+        output.push_str("\n### Related Knowledge\n");
+        for k in &knowledge {
+            output.push_str(&format!(
+                "- [{}] **{}**: {}\n",
+                k.category,
+                k.subject,
+                truncate(&k.content, 200)
+            ));
+        }
+    }
+
+    // 4. Callers
+    let callers = search::symbol_callers(conn, &sym.name, 20)?;
+    let callers: Vec<&search::CallSite> = callers
+        .iter()
+        .filter(|c| {
+            !(c.file_path == sym.file_path
+                && c.line >= sym.start_line
+                && c.line <= sym.end_line)
+        })
+        .collect();
+    if !callers.is_empty() {
+        output.push_str(&format!("\n### Callers ({})\n", callers.len()));
+        for c in &callers {
+            match &c.caller {
+                Some(s) => output.push_str(&format!(
+                    "- {} `{}` — {}:{}\n",
+                    s.kind,
+                    qualified_symbol_name(s),
+                    c.file_path,
+                    c.line
+                )),
+                None => output.push_str(&format!("- {}:{}\n", c.file_path, c.line)),
+            }
+        }
+    }
+
+    // 5. Callees
+    let callees = search::symbol_callees(conn, &sym.file_path, sym.start_line, sym.end_line, 20)?;
+    if !callees.is_empty() {
+        output.push_str(&format!("\n### Callees ({})\n", callees.len()));
+        for s in &callees {
+            output.push_str(&format!(
+                "- {} `{}` — {}:{}-{}\n",
+                s.kind,
+                qualified_symbol_name(s),
+                s.file_path,
+                s.start_line,
+                s.end_line
+            ));
+        }
+    }
+
+    Ok(output)
+}
+
+/// Split `path:120-140` (or `path:120`) into the path and line range.
+fn parse_location(location: &str) -> (String, Option<(i64, i64)>) {
+    if let Some((path, range)) = location.rsplit_once(':') {
+        let bounds = match range.split_once('-') {
+            Some((a, b)) => a.trim().parse().ok().zip(b.trim().parse().ok()),
+            None => range.trim().parse().ok().map(|n| (n, n)),
+        };
+        if let Some(bounds) = bounds {
+            return (path.to_string(), Some(bounds));
+        }
+    }
+    (location.to_string(), None)
+}
+
+/// Format past error occurrences: the failing command, the output lines that
+/// matched, what was tried next, and the command run that fixed it.
+fn format_error_occurrences(error: &str, occurrences: &[search::ErrorOccurrence]) -> String {
+    let needle = error.lines().next().unwrap_or(error).trim().to_lowercase();
+    let mut output = format!("## Past occurrences of: {}\n", truncate(error.trim(), 120));
+
+    for occ in occurrences {
+        let f = &occ.failure;
+        let mut lines = f.content.lines();
+        let command = lines.next().unwrap_or("");
+        output.push_str(&format!(
+            "\n### [{}] session {}\n**Failed:** `{}`\n",
+            f.timestamp,
+            f.session_id,
+            truncate(command, 200)
+        ));
+        let excerpt: Vec<&str> = lines
+            .filter(|l| l.to_lowercase().contains(&needle))
+            .take(3)
+            .collect();
+        if !excerpt.is_empty() {
+            output.push_str("```\n");
+            for line in excerpt {
+                output.push_str(&truncate(line, 200));
+                output.push('\n');
+            }
+            output.push_str("```\n");
+        }
+
+        let steps: Vec<&search::TurnSearchResult> = occ
+            .followups
+            .iter()
+            .filter(|t| matches!(t.turn_type.as_str(), "code_edit" | "bash_cmd" | "request"))
+            .collect();
+        if !steps.is_empty() {
+            output.push_str("**Then:**\n");
+            for t in steps {
+                let label = match t.turn_type.as_str() {
+                    "request" => "user",
+                    "code_edit" => "edit",
+                    _ => "run",
+                };
+                let first_line = t.content.lines().next().unwrap_or("");
+                output.push_str(&format!("- {}: {}\n", label, truncate(first_line, 150)));
+            }
+        }
+
+        match &occ.resolved_by {
+            Some(fix) => output.push_str(&format!(
+                "**Resolved:** `{}` succeeded at {}\n",
+                truncate(fix.content.lines().next().unwrap_or(""), 200),
+                fix.timestamp
+            )),
+            None => output.push_str("**Unresolved:** the command was not re-run successfully afterwards.\n"),
+        }
+    }
+    output
+}
+
+/// One dated entry in a blame explanation.
+struct BlameEvent {
+    timestamp: String,
+    text: String,
+}
+
+/// Explain the history of lines `start..=end` of `file_path`: the edits that
+/// touched them grouped under the requests that prompted them, the commits
+/// that included them, and decisions recorded about the file, oldest first.
+fn blame_region(
+    conn: &rusqlite::Connection,
+    project_dir: &str,
+    file_path: &str,
+    start: i64,
+    end: i64,
+) -> anyhow::Result<String> {
+    let mut edits = search::region_edits(conn, file_path, start, end, 50)?;
+
+    // Edits indexed before regions were recorded: match on the current text
+    // of the range instead.
+    let region_lines: Vec<String> = std::fs::read_to_string(file_path)
+        .map(|src| {
+            src.lines()
+                .skip((start.max(1) - 1) as usize)
+                .take((end - start + 1).max(0) as usize)
+                .map(|l| l.trim().to_string())
+                .filter(|l| l.len() >= 12)
+                .collect()
+        })
+        .unwrap_or_default();
+    if !region_lines.is_empty() {
+        for t in search::file_history(conn, file_path, 200)? {
+            if t.turn_type != "code_edit" || edits.iter().any(|e| e.turn_id == t.turn_id) {
+                continue;
+            }
+            let has_regions: i64 = conn.query_row(
+                "SELECT COUNT(*) FROM edit_regions WHERE turn_id = ?1",
+                [t.turn_id],
+                |row| row.get(0),
+            )?;
+            if has_regions == 0 && region_lines.iter().any(|l| t.content.contains(l.as_str())) {
+                edits.push(t);
+            }
+        }
+    }
+
+    let mut events: Vec<BlameEvent> = Vec::new();
+
+    // 1. Edits, grouped under the request that preceded them
+    let mut groups: Vec<(Option<search::TurnSearchResult>, Vec<&search::TurnSearchResult>)> =
+        Vec::new();
+    for edit in &edits {
+        let request = search::preceding_request(conn, &edit.session_id, edit.turn_number)?;
+        let key = request.as_ref().map(|r| r.turn_id);
+        match groups
+            .iter_mut()
+            .find(|(r, _)| key.is_some() && r.as_ref().map(|r| r.turn_id) == key)
+        {
+            Some((_, group)) => group.push(edit),
+            None => groups.push((request, vec![edit])),
+        }
+    }
+    for (request, group) in groups {
+        let mut text = match &request {
+            Some(r) => format!(
+                "**Request** (session {}): {}\n",
+                r.session_id,
+                truncate(&r.content.replace('\n', " "), 300)
+            ),
+            None => "**Edits** (no preceding request)\n".to_string(),
+        };
+        for e in &group {
+            text.push_str(&format!(
+                "  - [{}] {}\n",
+                e.timestamp,
+                truncate(&e.content.replace('\n', " "), 200)
+            ));
+        }
+        let timestamp = request
+            .as_ref()
+            .map(|r| r.timestamp.clone())
+            .unwrap_or_else(|| group[0].timestamp.clone());
+        events.push(BlameEvent { timestamp, text });
+    }
+
+    // 2. Commits that touched the range
+    for c in crate::indexer::git::line_history(std::path::Path::new(project_dir), file_path, start, end, 20) {
+        events.push(BlameEvent {
+            timestamp: c.timestamp,
+            text: format!("**Commit** {}: {}\n", c.hash, c.subject),
+        });
+    }
+
+    // 3. Decisions that mention the file
+    let file_name = file_path.rsplit(['/', '\\']).next().unwrap_or(file_path);
+    for k in search::search_knowledge(conn, file_name, 5, Some("decision"))? {
+        events.push(BlameEvent {
+            timestamp: k.created_at.clone(),
+            text: format!("**Decision** {}: {}\n", k.subject, truncate(&k.content, 300)),
+        });
+    }
+
+    let mut output = format!("## Blame: {}:{}-{}\n", file_path, start, end);
+    if let Some(sym) = search::enclosing_symbol(conn, file_path, start)? {
+        output.push_str(&format!(
+            "In {} `{}` ({}-{})\n",
+            sym.kind,
+            qualified_symbol_name(&sym),
+            sym.start_line,
+            sym.end_line
+        ));
+    }
+
+    if events.is_empty() {
+        output.push_str("\nNo recorded history for these lines.\n");
+        return Ok(output);
+    }
+
+    events.sort_by(|a, b| a.timestamp.cmp(&b.timestamp));
+    output.push('\n');
+    for e in &events {
+        output.push_str(&format!("- [{}] {}", e.timestamp, e.text));
+    }
+    Ok(output)
+}
+
 // --- Server implementation ---
 
 #[tool_router]
@@ -303,6 +816,140 @@ impl ClaudeRlmServer {
         let output = format!("## Symbols\n\n{}", result.join("\n"));
         Ok(CallToolResult::success(vec![Content::text(output)]))
     }
+
+    #[tool(description = "Everything about one symbol in a single call: current source read from disk, doc comment, parent, the past edits that touched it, related knowledge, and its callers and callees. Use instead of a Read plus several memory searches.")]
+    async fn memory_symbol_context(
+        &self,
+        Parameters(params): Parameters<MemorySymbolContextParams>,
+    ) -> Result<CallToolResult, McpError> {
+        let db = self.db.clone();
+        let output = tokio::task::spawn_blocking(move || {
+            let conn = db.conn();
+            let matches =
+                search::find_symbols(&conn, &params.name, params.file_path.as_deref(), 10)?;
+
+            let Some(first) = matches.first() else {
+                return Ok::<_, anyhow::Error>(format!("No symbol found matching '{}'.", params.name));
+            };
+
+            let mut output = symbol_context(&conn, first)?;
+            if matches.len() > 1 {
+                output.push_str("\n### Other Matches\n");
+                for s in &matches[1..] {
+                    output.push_str(&format!(
+                        "- {} `{}` — {}:{}-{}\n",
+                        s.kind,
+                        qualified_symbol_name(s),
+                        s.file_path,
+                        s.start_line,
+                        s.end_line
+                    ));
+                }
+            }
+            Ok(output)
+        })
+        .await
+        .map_err(|e| mcp_err(format!("Task join error: {e}")))?
+        .map_err(|e| mcp_err(format!("Query failed: {e}")))?;
+
+        Ok(CallToolResult::success(vec![Content::text(output)]))
+    }
+
+    #[tool(description = "Explain why a file region looks the way it does. Given a file and line range (e.g. `src/foo.rs:120-140`), returns a chronological history: the edits that touched those lines with the user requests behind them, the commits that included them, and decisions recorded about the file.")]
+    async fn memory_blame(
+        &self,
+        Parameters(params): Parameters<MemoryBlameParams>,
+    ) -> Result<CallToolResult, McpError> {
+        let db = self.db.clone();
+        let output = tokio::task::spawn_blocking(move || {
+            let project_dir = db.project_dir();
+            let (path, range) = parse_location(&params.file_path);
+
+            // Stored paths are absolute; resolve relative input against the project
+            let path = if std::path::Path::new(&path).is_absolute() {
+                path
+            } else {
+                std::path::Path::new(&project_dir)
+                    .join(&path)
+                    .to_string_lossy()
+                    .to_string()
+            };
+
+            let line_count = std::fs::read_to_string(&path)
+                .map(|s| s.lines().count() as i64)
+                .unwrap_or(1)
+                .max(1);
+            let (start, end) = range.unwrap_or((1, line_count));
+            let start = params.start_line.unwrap_or(start).max(1);
+            let end = params.end_line.unwrap_or(end).max(start);
+
+            let conn = db.conn();
+            blame_region(&conn, &project_dir, &path, start, end)
+        })
+        .await
+        .map_err(|e| mcp_err(format!("Task join error: {e}")))?
+        .map_err(|e| mcp_err(format!("Query failed: {e}")))?;
+
+        Ok(CallToolResult::success(vec![Content::text(output)]))
+    }
+
+    #[tool(description = "Look up an error in past sessions. Given an error message or snippet, finds earlier commands that failed with a similar error and shows what followed — the edits and commands tried — until the same command succeeded. Use when hitting a build/test error that may have been seen before.")]
+    async fn memory_errors(
+        &self,
+        Parameters(params): Parameters<MemoryErrorsParams>,
+    ) -> Result<CallToolResult, McpError> {
+        let db = self.db.clone();
+        let output = tokio::task::spawn_blocking(move || {
+            let conn = db.conn();
+            let limit = params.limit.unwrap_or(5);
+            let occurrences = search::error_occurrences(&conn, &params.error, limit)?;
+            if occurrences.is_empty() {
+                return Ok::<_, anyhow::Error>("No past occurrences of this error found.".to_string());
+            }
+            Ok(format_error_occurrences(&params.error, &occurrences))
+        })
+        .await
+        .map_err(|e| mcp_err(format!("Task join error: {e}")))?
+        .map_err(|e| mcp_err(format!("Query failed: {e}")))?;
+
+        Ok(CallToolResult::success(vec![Content::text(output)]))
+    }
+
+    #[tool(description = "Browse past sessions. Without session_id, lists sessions filtered by date range (since/until), git branch, or keyword. With session_id, returns that session's timeline: each user request followed by the edits, reads and commands it led to.")]
+    async fn memory_sessions(
+        &self,
+        Parameters(params): Parameters<MemorySessionsParams>,
+    ) -> Result<CallToolResult, McpError> {
+        let db = self.db.clone();
+        let output = tokio::task::spawn_blocking(move || {
+            let conn = db.conn();
+
+            if let Some(id) = params.session_id.as_deref() {
+                let Some(session) = search::find_session(&conn, id)? else {
+                    return Ok::<_, anyhow::Error>(format!("No session found matching '{}'.", id));
+                };
+                let timeline = search::session_timeline(&conn, &session.id)?;
+                return Ok(format_session_timeline(&session, &timeline));
+            }
+
+            let filter = search::SessionFilter {
+                since: params.since.as_deref(),
+                until: params.until.as_deref(),
+                branch: params.branch.as_deref(),
+                keyword: params.keyword.as_deref(),
+            };
+            let sessions = search::list_sessions(&conn, &filter, params.limit.unwrap_or(10))?;
+            if sessions.is_empty() {
+                return Ok("No matching sessions found.".to_string());
+            }
+            Ok(format_session_list(&sessions))
+        })
+        .await
+        .map_err(|e| mcp_err(format!("Task join error: {e}")))?
+        .map_err(|e| mcp_err(format!("Query failed: {e}")))?;
+
+        Ok(CallToolResult::success(vec![Content::text(output)]))
+    }
 }
 
 #[tool_handler]
@@ -314,7 +961,11 @@ impl ServerHandler for ClaudeRlmServer {
                  Automatically indexes conversation history and code changes. \
                  Use memory_search to find past discussions, memory_decisions \
                  for past decisions, memory_files for file change history, \
-                 and memory_symbols for code structure queries."
+                 memory_symbols for code structure queries, memory_symbol_context \
+                 for one symbol's source, history and references, memory_blame \
+                 for why a file region looks the way it does, memory_errors \
+                 for how a similar error was fixed before, and \
+                 memory_sessions to list past sessions or replay one as a timeline."
                     .to_string(),
             ),
             capabilities: ServerCapabilities::builder().enable_tools().build(),
@@ -322,3 +973,151 @@ impl ServerHandler for ClaudeRlmServer {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::indexer::code;
+    use crate::treesitter::languages::Lang;
+    use crate::indexer::conversation;
+
+    #[test]
+    fn test_symbol_context_shows_callers_callees_and_edits() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("pre_tool_use.rs");
+        std::fs::write(
+            &path,
+            "fn helper() -> u32 {\n    1\n}\n\nfn parse() -> u32 {\n    helper() + 1\n}\n\nfn main() {\n    parse();\n}\n",
+        )
+        .unwrap();
+        let file = path.to_string_lossy().to_string();
+
+        let db = Db::open_memory().unwrap();
+        code::index_file(&db, &path, Lang::Rust).unwrap();
+        conversation::ensure_session(&db, "s1", "/nonexistent").unwrap();
+        let edit = conversation::index_turn(
+            &db,
+            "s1",
+            "assistant",
+            "code_edit",
+            "Edit: make parse add one",
+            None,
+            &[(file.clone(), "edit".to_string())],
+        )
+        .unwrap();
+        conversation::record_edit_regions(&db, edit, &file, &[(6, 6)]).unwrap();
+        let other = conversation::index_turn(
+            &db,
+            "s1",
+            "assistant",
+            "code_edit",
+            "Edit: main only",
+            None,
+            &[(file.clone(), "edit".to_string())],
+        )
+        .unwrap();
+        conversation::record_edit_regions(&db, other, &file, &[(10, 10)]).unwrap();
+
+        let conn = db.conn();
+        let sym = search::find_symbols(&conn, "parse", None, 5).unwrap().remove(0);
+        let out = symbol_context(&conn, &sym).unwrap();
+
+        assert!(out.contains("### Edit History (1)"), "{}", out);
+        assert!(out.contains("make parse add one"), "{}", out);
+        assert!(!out.contains("main only"), "{}", out);
+        assert!(out.contains("### Callers (1)"), "{}", out);
+        assert!(out.contains("`main`"), "{}", out);
+        assert!(out.contains("### Callees (1)"), "{}", out);
+        assert!(out.contains("`helper`"), "{}", out);
+    }
+
+    #[test]
+    fn test_find_symbols_file_filter_is_literal() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("preXtool.rs");
+        std::fs::write(&path, "fn parse() {}\n").unwrap();
+
+        let db = Db::open_memory().unwrap();
+        code::index_file(&db, &path, Lang::Rust).unwrap();
+        let conn = db.conn();
+        assert_eq!(search::find_symbols(&conn, "parse", Some("preXtool"), 5).unwrap().len(), 1);
+        assert!(search::find_symbols(&conn, "parse", Some("pre_tool"), 5).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_parse_location() {
+        assert_eq!(parse_location("src/lib.rs:120"), ("src/lib.rs".to_string(), Some((120, 120))));
+        assert_eq!(
+            parse_location("src/lib.rs:120-140"),
+            ("src/lib.rs".to_string(), Some((120, 140)))
+        );
+        assert_eq!(parse_location("src/lib.rs"), ("src/lib.rs".to_string(), None));
+        assert_eq!(
+            parse_location(r"C:\repo\src\lib.rs:7-9"),
+            (r"C:\repo\src\lib.rs".to_string(), Some((7, 9)))
+        );
+        assert_eq!(parse_location(r"C:\repo\src\lib.rs"), (r"C:\repo\src\lib.rs".to_string(), None));
+    }
+
+    #[test]
+    fn test_blame_region_groups_edits_and_merges_history() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("lib.rs");
+        std::fs::write(&path, "fn a() {}\nfn b() {}\nfn c() {}\n").unwrap();
+        let git = |args: &[&str]| {
+            let status = std::process::Command::new("git")
+                .args(["-c", "user.name=t", "-c", "user.email=t@t"])
+                .args(args)
+                .current_dir(dir.path())
+                .env("GIT_AUTHOR_DATE", "2000-01-01T00:00:00Z")
+                .env("GIT_COMMITTER_DATE", "2000-01-01T00:00:00Z")
+                .stdout(std::process::Stdio::null())
+                .status()
+                .unwrap();
+            assert!(status.success());
+        };
+        git(&["init", "-q"]);
+        git(&["add", "lib.rs"]);
+        git(&["commit", "-q", "-m", "Add lib"]);
+        let file = path.to_string_lossy().to_string();
+        let project_dir = dir.path().to_string_lossy().to_string();
+
+        let db = Db::open_memory().unwrap();
+        conversation::ensure_session(&db, "s1", &project_dir).unwrap();
+        let turn = |turn_type: &str, content: &str, timestamp: &str, region: Option<(i64, i64)>| {
+            let id = conversation::index_turn(&db, "s1", "user", turn_type, content, None, &[]).unwrap();
+            conversation::set_turn_timestamp(&db, id, timestamp).unwrap();
+            if let Some(region) = region {
+                conversation::record_edit_regions(&db, id, &file, &[region]).unwrap();
+            }
+        };
+        turn("request", "Make b faster", "2020-01-01 00:00:00", None);
+        turn("code_edit", "Edit: inline b", "2020-01-01 00:01:00", Some((2, 2)));
+        turn("code_edit", "Edit: b takes a slice", "2020-01-01 00:02:00", Some((1, 2)));
+        turn("request", "Rename c", "2022-01-01 00:00:00", None);
+        turn("code_edit", "Edit: c -> d", "2022-01-01 00:01:00", Some((3, 3)));
+        turn("code_edit", "Edit: b again", "2022-01-01 00:02:00", Some((2, 3)));
+        db.conn()
+            .execute(
+                "INSERT INTO knowledge (category, subject, content, created_at)
+                 VALUES ('decision', 'Keep b', 'lib.rs keeps b free of allocation', '2021-01-01 00:00:00')",
+                [],
+            )
+            .unwrap();
+
+        let out = blame_region(&db.conn(), &project_dir, &file, 2, 2).unwrap();
+        let pos = |needle: &str| out.find(needle).unwrap_or_else(|| panic!("{} not in {}", needle, out));
+
+        // Both edits sit under one "Make b faster" entry
+        assert_eq!(out.matches("Make b faster").count(), 1, "{}", out);
+        assert!(pos("Make b faster") < pos("inline b"));
+        assert!(pos("inline b") < pos("b takes a slice"));
+        // The edit outside the range is left out
+        assert!(!out.contains("c -> d"), "{}", out);
+        // Commits, requests and decisions interleave by time
+        assert!(pos("**Commit**") < pos("Make b faster"));
+        assert!(pos("b takes a slice") < pos("**Decision** Keep b"));
+        assert!(pos("**Decision** Keep b") < pos("Rename c"));
+        assert!(pos("Rename c") < pos("b again"));
+    }
+}
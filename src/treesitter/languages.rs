@@ -57,6 +57,19 @@ impl Lang {
         }
     }
 
+    /// Get the call-site query for this language.
+    /// Every pattern captures the called name as `@callee`.
+    pub fn call_query(&self) -> &'static str {
+        match self {
+            Self::Rust => RUST_CALLS,
+            Self::Python => PYTHON_CALLS,
+            Self::TypeScript | Self::Tsx | Self::JavaScript => JS_CALLS,
+            Self::Go => GO_CALLS,
+            Self::C => C_CALLS,
+            Self::Cpp => CPP_CALLS,
+        }
+    }
+
     pub fn name(&self) -> &'static str {
         match self {
             Self::Rust => "rust",
@@ -389,3 +402,41 @@ const CPP_QUERY: &str = r#"
   path: (_) @name
 ) @import
 "#;
+
+// ---- Per-language call-site queries ----
+// Each pattern captures only the final name segment of the callee
+// (`foo` in `a.b.foo()` or `Bar::foo()`), which is what symbols are keyed on.
+
+const RUST_CALLS: &str = r#"
+(call_expression function: (identifier) @callee)
+(call_expression function: (field_expression field: (field_identifier) @callee))
+(call_expression function: (scoped_identifier name: (identifier) @callee))
+(macro_invocation macro: (identifier) @callee)
+"#;
+
+const PYTHON_CALLS: &str = r#"
+(call function: (identifier) @callee)
+(call function: (attribute attribute: (identifier) @callee))
+"#;
+
+const JS_CALLS: &str = r#"
+(call_expression function: (identifier) @callee)
+(call_expression function: (member_expression property: (property_identifier) @callee))
+(new_expression constructor: (identifier) @callee)
+"#;
+
+const GO_CALLS: &str = r#"
+(call_expression function: (identifier) @callee)
+(call_expression function: (selector_expression field: (field_identifier) @callee))
+"#;
+
+const C_CALLS: &str = r#"
+(call_expression function: (identifier) @callee)
+(call_expression function: (field_expression field: (field_identifier) @callee))
+"#;
+
+const CPP_CALLS: &str = r#"
+(call_expression function: (identifier) @callee)
+(call_expression function: (field_expression field: (field_identifier) @callee))
+(call_expression function: (qualified_identifier name: (identifier) @callee))
+"#;
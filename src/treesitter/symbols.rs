@@ -15,18 +15,29 @@ pub struct ExtractedSymbol {
     pub parent_name: Option<String>,
 }
 
-/// Extract symbols from source code using tree-sitter.
-pub fn extract_symbols(lang: Lang, source: &[u8]) -> Result<Vec<ExtractedSymbol>> {
-    let grammar = lang.grammar();
+/// A call site extracted from source code.
+#[derive(Debug, Clone)]
+pub struct ExtractedCall {
+    pub callee: String,
+    pub line: usize,
+}
 
+/// Parse source code with the grammar for `lang`.
+fn parse(lang: Lang, source: &[u8]) -> Result<tree_sitter::Tree> {
     let mut parser = Parser::new();
     parser
-        .set_language(&grammar)
+        .set_language(&lang.grammar())
         .map_err(|e| anyhow::anyhow!("Failed to set language: {}", e))?;
 
-    let tree = parser
+    parser
         .parse(source, None)
-        .ok_or_else(|| anyhow::anyhow!("Failed to parse source"))?;
+        .ok_or_else(|| anyhow::anyhow!("Failed to parse source"))
+}
+
+/// Extract symbols from source code using tree-sitter.
+pub fn extract_symbols(lang: Lang, source: &[u8]) -> Result<Vec<ExtractedSymbol>> {
+    let grammar = lang.grammar();
+    let tree = parse(lang, source)?;
 
     let query_str = lang.symbol_query();
     let query = Query::new(&grammar, query_str)
@@ -97,6 +108,30 @@ pub fn extract_symbols(lang: Lang, source: &[u8]) -> Result<Vec<ExtractedSymbol>
     Ok(symbols)
 }
 
+/// Extract call sites (callee name + 1-indexed line) using tree-sitter.
+pub fn extract_calls(lang: Lang, source: &[u8]) -> Result<Vec<ExtractedCall>> {
+    let tree = parse(lang, source)?;
+    let query = Query::new(&lang.grammar(), lang.call_query())
+        .map_err(|e| anyhow::anyhow!("Failed to compile call query: {}", e))?;
+
+    let mut cursor = QueryCursor::new();
+    let mut matches = cursor.matches(&query, tree.root_node(), source);
+
+    let mut calls = Vec::new();
+    while let Some(m) = matches.next() {
+        for capture in m.captures {
+            if let Ok(callee) = capture.node.utf8_text(source) {
+                calls.push(ExtractedCall {
+                    callee: callee.to_string(),
+                    line: capture.node.start_position().row + 1,
+                });
+            }
+        }
+    }
+
+    Ok(calls)
+}
+
 /// Determine the kind of symbol from the query pattern index.
 /// We use the capture name of the outer (non-@name) capture.
 fn kind_from_pattern(query_str: &str, pattern_idx: usize) -> String {
@@ -212,3 +247,35 @@ fn find_parent_symbol(node: tree_sitter::Node, source: &[u8]) -> Option<String>
     }
     None
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_call_queries_compile() {
+        for lang in [
+            Lang::Rust,
+            Lang::Python,
+            Lang::TypeScript,
+            Lang::Tsx,
+            Lang::JavaScript,
+            Lang::Go,
+            Lang::C,
+            Lang::Cpp,
+        ] {
+            assert!(extract_calls(lang, b"").is_ok(), "call query for {}", lang.name());
+        }
+    }
+
+    #[test]
+    fn test_extract_calls_rust() {
+        let src = b"fn a() {\n    b();\n    self.c(1);\n    Foo::d();\n    println!(\"x\");\n}\n";
+        let calls = extract_calls(Lang::Rust, src).unwrap();
+        let names: Vec<(&str, usize)> = calls.iter().map(|c| (c.callee.as_str(), c.line)).collect();
+        assert!(names.contains(&("b", 2)));
+        assert!(names.contains(&("c", 3)));
+        assert!(names.contains(&("d", 4)));
+        assert!(names.contains(&("println", 5)));
+    }
+}
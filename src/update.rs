@@ -222,10 +222,17 @@ async fn check_and_apply_update() -> anyhow::Result<()> {
 /// Check if the binary was updated in a previous session.
 /// Returns the new version string if an `.updated` marker exists, then deletes it.
 pub fn check_version_updated() -> Option<String> {
+    let version = pending_version_update();
+    if let Ok(exe) = std::env::current_exe() {
+        let _ = std::fs::remove_file(suffixed_path(&exe, ".updated"));
+    }
+    version
+}
+
+/// The version in the `.updated` marker, if any, leaving the marker in place.
+pub fn pending_version_update() -> Option<String> {
     let exe = std::env::current_exe().ok()?;
-    let marker = suffixed_path(&exe, ".updated");
-    let version = std::fs::read_to_string(&marker).ok()?;
-    let _ = std::fs::remove_file(&marker);
+    let version = std::fs::read_to_string(suffixed_path(&exe, ".updated")).ok()?;
     let version = version.trim().to_string();
     if version.is_empty() { None } else { Some(version) }
 }
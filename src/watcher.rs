@@ -79,10 +79,7 @@ fn process_events(rx: mpsc::Receiver<notify::Result<Event>>, db: Db) {
                     // File was deleted, remove its symbols
                     let conn = db.conn();
                     let path_str = file_path.to_string_lossy();
-                    if let Err(e) = conn.execute(
-                        "DELETE FROM symbols WHERE file_path = ?1",
-                        rusqlite::params![path_str.as_ref()],
-                    ) {
+                    if let Err(e) = code::remove_file(&conn, &path_str) {
                         tracing::warn!("Failed to remove symbols for {}: {}", path_str, e);
                     }
                     continue;